@@ -0,0 +1,376 @@
+//! Human-in-the-loop approval gate for sensitive actions.
+//!
+//! With an [`ApprovalConfig`] installed, holding a gated scope (by default
+//! [`AuthScope::RoboticsAction`] and [`AuthScope::ExternalAPI`]) is no longer enough: the
+//! first authorized check files an [`ApprovalRequest`] in the `approvals` tree and fails
+//! with an error starting with [`APPROVAL_PENDING_PREFIX`]. Once an approver signs off, the
+//! next identical check passes, once. Facts of gated types are queued with the request and
+//! written when it is approved.
+//!
+//! Requests nobody resolves within [`ApprovalConfig::timeout`] are settled by
+//! [`TimeoutPolicy`]. Every new and resolved request is announced as an [`IpcMessage`]
+//! (see [`IpcBus::forward_approvals`](crate::IpcBus::forward_approvals)).
+
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::auth::{AuthScope, Resource};
+use crate::ipc::IpcMessage;
+use crate::AgentFact;
+
+/// KB tree holding approval requests, keyed by request id.
+pub const APPROVALS_TREE: &str = "approvals";
+
+/// Prefix of the error returned while a request waits for approval.
+pub const APPROVAL_PENDING_PREFIX: &str = "Approval pending";
+
+/// Environment variable that installs the default [`ApprovalConfig`] on new cores.
+pub const PAGI_REQUIRE_APPROVALS_ENV: &str = "PAGI_REQUIRE_APPROVALS";
+
+/// Capacity of the notification channel.
+const NOTICE_CAPACITY: usize = 256;
+
+/// Whether an authorization error means "wait for approval" rather than "denied".
+pub fn is_approval_pending(err: &str) -> bool {
+    err.starts_with(APPROVAL_PENDING_PREFIX)
+}
+
+/// What happens to requests nobody resolves in time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TimeoutPolicy {
+    Deny,
+    Allow,
+}
+
+/// Which actions need approval, and for how long to wait.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApprovalConfig {
+    /// Gated scopes, compared by base scope.
+    pub scopes: Vec<AuthScope>,
+    /// Fact types whose writes are queued for approval.
+    pub fact_types: Vec<String>,
+    pub timeout: Duration,
+    pub on_timeout: TimeoutPolicy,
+}
+
+impl Default for ApprovalConfig {
+    fn default() -> Self {
+        Self {
+            scopes: vec![AuthScope::RoboticsAction, AuthScope::ExternalAPI],
+            fact_types: vec!["RoboticsAction".to_string()],
+            timeout: Duration::from_secs(300),
+            on_timeout: TimeoutPolicy::Deny,
+        }
+    }
+}
+
+impl ApprovalConfig {
+    pub fn gates_scope(&self, scope: &AuthScope) -> bool {
+        self.scopes.iter().any(|s| s.base() == scope.base())
+    }
+
+    pub fn gates_fact(&self, fact_type: &str) -> bool {
+        self.fact_types.iter().any(|t| t == fact_type)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ApprovalStatus {
+    Pending,
+    Approved {
+        approver: String,
+        at: u64,
+    },
+    Rejected {
+        approver: String,
+        reason: String,
+        at: u64,
+    },
+    /// Settled by [`TimeoutPolicy`].
+    TimedOut {
+        allowed: bool,
+        at: u64,
+    },
+}
+
+impl ApprovalStatus {
+    /// `None` while pending, otherwise whether the action may proceed.
+    pub fn allows(&self) -> Option<bool> {
+        match self {
+            ApprovalStatus::Pending => None,
+            ApprovalStatus::Approved { .. } => Some(true),
+            ApprovalStatus::Rejected { .. } => Some(false),
+            ApprovalStatus::TimedOut { allowed, .. } => Some(*allowed),
+        }
+    }
+}
+
+/// A gated action waiting for (or settled by) an approver.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApprovalRequest {
+    pub id: String,
+    pub requester: String,
+    pub scope: AuthScope,
+    pub resource: Resource,
+    /// Human-readable description shown to approvers.
+    pub summary: String,
+    /// Fact written once the request is approved, for queued fact writes.
+    pub fact: Option<AgentFact>,
+    pub created_at: u64,
+    pub expires_at: u64,
+    pub status: ApprovalStatus,
+    /// Set once the approved action has run (or the rejection was reported), so each
+    /// approval is used at most once.
+    pub consumed: bool,
+}
+
+impl ApprovalRequest {
+    /// The error returned to the requester while the request is pending.
+    pub fn pending_error(&self) -> String {
+        format!(
+            "{APPROVAL_PENDING_PREFIX}: request '{}' for agent '{}' to use {:?} on {} awaits \
+             approval",
+            self.id,
+            self.requester,
+            self.scope.base(),
+            self.resource
+        )
+    }
+
+    fn notice(&self) -> IpcMessage {
+        match self.status {
+            ApprovalStatus::Pending => IpcMessage::ApprovalRequested {
+                request_id: self.id.clone(),
+                requester: self.requester.clone(),
+                scope: self.scope.clone(),
+                resource: self.resource.to_string(),
+                summary: self.summary.clone(),
+                expires_at: self.expires_at,
+            },
+            _ => IpcMessage::ApprovalResolved {
+                request_id: self.id.clone(),
+                requester: self.requester.clone(),
+                status: self.status.clone(),
+            },
+        }
+    }
+}
+
+/// KB-backed approval queue. Clones share the configuration and notification channel.
+#[derive(Debug, Clone)]
+pub struct ApprovalQueue {
+    db: sled::Db,
+    tree: sled::Tree,
+    config: Arc<RwLock<Option<ApprovalConfig>>>,
+    notices: broadcast::Sender<IpcMessage>,
+}
+
+impl ApprovalQueue {
+    /// Opens the `approvals` tree. The gate starts disabled.
+    pub fn open(db: &sled::Db) -> Result<Self, sled::Error> {
+        Ok(Self {
+            db: db.clone(),
+            tree: db.open_tree(APPROVALS_TREE)?,
+            config: Arc::new(RwLock::new(None)),
+            notices: broadcast::channel(NOTICE_CAPACITY).0,
+        })
+    }
+
+    /// Installs (or with `None`, removes) the gate configuration.
+    pub fn configure(&self, config: Option<ApprovalConfig>) {
+        *self.config.write().expect("approval config lock poisoned") = config;
+    }
+
+    pub fn config(&self) -> Option<ApprovalConfig> {
+        self.config
+            .read()
+            .expect("approval config lock poisoned")
+            .clone()
+    }
+
+    /// Receives an [`IpcMessage::ApprovalRequested`] or [`IpcMessage::ApprovalResolved`]
+    /// for every change.
+    pub fn subscribe(&self) -> broadcast::Receiver<IpcMessage> {
+        self.notices.subscribe()
+    }
+
+    /// Files a pending request expiring `timeout` after `now` and announces it.
+    #[allow(clippy::too_many_arguments)]
+    pub fn enqueue(
+        &self,
+        requester: &str,
+        scope: &AuthScope,
+        resource: &Resource,
+        summary: String,
+        fact: Option<AgentFact>,
+        timeout: Duration,
+        now: u64,
+    ) -> Result<ApprovalRequest, sled::Error> {
+        let request = ApprovalRequest {
+            id: format!("apr-{}", self.db.generate_id()?),
+            requester: requester.to_string(),
+            scope: scope.base().clone(),
+            resource: resource.clone(),
+            summary,
+            fact,
+            created_at: now,
+            expires_at: now.saturating_add(timeout.as_secs()),
+            status: ApprovalStatus::Pending,
+            consumed: false,
+        };
+        self.put(&request)?;
+        let _ = self.notices.send(request.notice());
+        Ok(request)
+    }
+
+    pub fn get(&self, id: &str) -> Option<ApprovalRequest> {
+        let v = self.tree.get(id.as_bytes()).ok()??;
+        serde_json::from_slice(&v).ok()
+    }
+
+    /// All requests, ordered by id.
+    pub fn all(&self) -> Vec<ApprovalRequest> {
+        self.tree
+            .iter()
+            .filter_map(|res| res.ok())
+            .filter_map(|(_, v)| serde_json::from_slice(&v).ok())
+            .collect()
+    }
+
+    /// The unconsumed check-style request (no queued fact) for this action, if any.
+    pub fn find_open(
+        &self,
+        requester: &str,
+        scope: &AuthScope,
+        resource: &Resource,
+    ) -> Option<ApprovalRequest> {
+        self.all().into_iter().find(|r| {
+            !r.consumed
+                && r.fact.is_none()
+                && r.requester == requester
+                && &r.scope == scope.base()
+                && &r.resource == resource
+        })
+    }
+
+    /// Settles a pending request and announces it. Fails if it is unknown or already
+    /// settled.
+    pub fn resolve(&self, id: &str, status: ApprovalStatus) -> Result<ApprovalRequest, String> {
+        let mut request = self
+            .get(id)
+            .ok_or_else(|| format!("Unknown approval request '{id}'"))?;
+        if request.status != ApprovalStatus::Pending {
+            return Err(format!("Approval request '{id}' is no longer pending"));
+        }
+        request.status = status;
+        self.put(&request)
+            .map_err(|e| format!("Approval write failed: {e}"))?;
+        let _ = self.notices.send(request.notice());
+        Ok(request)
+    }
+
+    pub fn mark_consumed(&self, id: &str) -> Result<(), sled::Error> {
+        if let Some(mut request) = self.get(id) {
+            request.consumed = true;
+            self.put(&request)?;
+        }
+        Ok(())
+    }
+
+    /// Settles every pending request that expired before `now` according to `policy`.
+    pub fn expire(&self, policy: TimeoutPolicy, now: u64) -> Vec<ApprovalRequest> {
+        let status = ApprovalStatus::TimedOut {
+            allowed: policy == TimeoutPolicy::Allow,
+            at: now,
+        };
+        self.all()
+            .into_iter()
+            .filter(|r| r.status == ApprovalStatus::Pending && now >= r.expires_at)
+            .filter_map(|r| self.resolve(&r.id, status.clone()).ok())
+            .collect()
+    }
+
+    fn put(&self, request: &ApprovalRequest) -> Result<(), sled::Error> {
+        let value = serde_json::to_vec(request).expect("failed to serialize ApprovalRequest");
+        self.tree.insert(request.id.as_bytes(), value)?;
+        self.tree.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_are_announced_resolved_once_and_expired_by_policy() {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("failed to open temporary sled db");
+        let queue = ApprovalQueue::open(&db).expect("open");
+        let mut notices = queue.subscribe();
+        let resource = Resource::tree("arm");
+        let timeout = Duration::from_secs(30);
+
+        let request = queue
+            .enqueue(
+                "RoboticsAgent",
+                &AuthScope::RoboticsAction,
+                &resource,
+                "move arm".to_string(),
+                None,
+                timeout,
+                1_000,
+            )
+            .expect("enqueue");
+        assert!(matches!(
+            notices.try_recv(),
+            Ok(IpcMessage::ApprovalRequested { .. })
+        ));
+        assert!(is_approval_pending(&request.pending_error()));
+        assert_eq!(
+            queue
+                .find_open("RoboticsAgent", &AuthScope::RoboticsAction, &resource)
+                .map(|r| r.id),
+            Some(request.id.clone())
+        );
+
+        let approved = ApprovalStatus::Approved {
+            approver: "Operator".to_string(),
+            at: 1_001,
+        };
+        queue
+            .resolve(&request.id, approved.clone())
+            .expect("resolve");
+        assert!(queue.resolve(&request.id, approved).is_err());
+        assert!(matches!(
+            notices.try_recv(),
+            Ok(IpcMessage::ApprovalResolved { .. })
+        ));
+        queue.mark_consumed(&request.id).expect("consume");
+        assert!(queue
+            .find_open("RoboticsAgent", &AuthScope::RoboticsAction, &resource)
+            .is_none());
+
+        let stale = queue
+            .enqueue(
+                "SearchAgent",
+                &AuthScope::ExternalAPI,
+                &Resource::default(),
+                "call API".to_string(),
+                None,
+                timeout,
+                1_000,
+            )
+            .expect("enqueue");
+        assert!(queue.expire(TimeoutPolicy::Allow, 1_029).is_empty());
+        let expired = queue.expire(TimeoutPolicy::Allow, 1_030);
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].id, stale.id);
+        assert_eq!(expired[0].status.allows(), Some(true));
+    }
+}
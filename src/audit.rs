@@ -0,0 +1,340 @@
+//! Tamper-evident audit log.
+//!
+//! Every authorization decision and KB mutation made through the core is appended to the
+//! `audit` tree. Records are keyed by a big-endian sequence number and chained: each record
+//! stores the hash of its predecessor and a SHA-256 over its own contents plus that hash, so
+//! editing, deleting or reordering a record breaks [`AuditLog::verify`] from that point on.
+//!
+//! The chain is unkeyed, so someone able to rewrite the whole tree can recompute every hash.
+//! To detect that (or a truncated tail), store the [`AuditHead`] returned by `verify`
+//! outside the KB and later check it with [`AuditLog::verify_anchor`].
+//!
+//! The tree is only written through [`AuditLog::append`]; there is no public API to modify
+//! or remove records.
+
+use std::io::Write;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::auth::{AuthScope, Resource};
+use crate::{to_hex, unix_now};
+
+/// KB tree holding audit records.
+pub const AUDIT_TREE: &str = "audit";
+
+/// `prev_hash` of the first record.
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AuditOutcome {
+    Allowed,
+    Denied,
+    Succeeded,
+    Failed,
+}
+
+/// What happened, as supplied by the caller.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEvent {
+    pub identity_id: String,
+    /// `authorize` for decisions, otherwise the mutating operation (e.g. `record_fact`).
+    pub action: String,
+    /// The scope checked, for authorization decisions.
+    pub scope: Option<AuthScope>,
+    pub resource: Resource,
+    pub outcome: AuditOutcome,
+    /// Denial reason, error, or other context.
+    pub detail: Option<String>,
+}
+
+/// A stored, chained audit record.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub seq: u64,
+    pub timestamp: u64,
+    #[serde(flatten)]
+    pub event: AuditEvent,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl AuditRecord {
+    fn compute_hash(seq: u64, timestamp: u64, event: &AuditEvent, prev_hash: &str) -> String {
+        let body = serde_json::to_vec(&(seq, timestamp, event, prev_hash))
+            .expect("failed to serialize audit record");
+        to_hex(&Sha256::digest(body))
+    }
+}
+
+/// Where verification found the chain broken.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditViolation {
+    /// Sequence number (or position, for undecodable records) of the first bad record.
+    pub seq: u64,
+    pub reason: String,
+}
+
+impl std::fmt::Display for AuditViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "audit chain broken at record {}: {}",
+            self.seq, self.reason
+        )
+    }
+}
+
+/// The end of a verified chain: how many records it has and the last record's hash
+/// (the genesis hash for an empty log).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditHead {
+    pub len: u64,
+    pub hash: String,
+}
+
+/// Append-only handle on the `audit` tree.
+///
+/// The chain head is read from the tree on every append, so any number of handles on the
+/// same `Db` (e.g. several cores built with `from_db`) extend one chain.
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    tree: sled::Tree,
+}
+
+impl AuditLog {
+    /// Opens the `audit` tree; appends continue after its last record.
+    pub fn open(db: &sled::Db) -> Result<Self, sled::Error> {
+        Ok(Self {
+            tree: db.open_tree(AUDIT_TREE)?,
+        })
+    }
+
+    /// Appends `event` to the chain, flushes it to disk and returns the stored record.
+    ///
+    /// The record is claimed with a compare-and-swap on its sequence key; if another handle
+    /// took that key first, the append retries after the new tail.
+    pub fn append(&self, event: AuditEvent) -> Result<AuditRecord, sled::Error> {
+        loop {
+            let (seq, prev_hash) = match self.tree.last()? {
+                Some((k, v)) => {
+                    let last_seq = k
+                        .as_ref()
+                        .try_into()
+                        .map(u64::from_be_bytes)
+                        .unwrap_or(self.tree.len() as u64);
+                    // Keep appending after a corrupt tail; verify() will report it.
+                    let last_hash = serde_json::from_slice::<AuditRecord>(&v)
+                        .map_or_else(|_| GENESIS_HASH.to_string(), |last| last.hash);
+                    (last_seq + 1, last_hash)
+                }
+                None => (0, GENESIS_HASH.to_string()),
+            };
+            let timestamp = unix_now();
+            let hash = AuditRecord::compute_hash(seq, timestamp, &event, &prev_hash);
+            let record = AuditRecord {
+                seq,
+                timestamp,
+                event: event.clone(),
+                prev_hash,
+                hash,
+            };
+
+            let value = serde_json::to_vec(&record).expect("failed to serialize audit record");
+            let claimed =
+                self.tree
+                    .compare_and_swap(seq.to_be_bytes(), None::<&[u8]>, Some(value))?;
+            if claimed.is_ok() {
+                self.tree.flush()?;
+                return Ok(record);
+            }
+        }
+    }
+
+    /// All records in sequence order. Undecodable entries are skipped.
+    pub fn records(&self) -> Vec<AuditRecord> {
+        self.tree
+            .iter()
+            .filter_map(|res| res.ok())
+            .filter_map(|(_, v)| serde_json::from_slice(&v).ok())
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Walks the whole chain and returns its head if it is intact.
+    pub fn verify(&self) -> Result<AuditHead, AuditViolation> {
+        let mut prev_hash = GENESIS_HASH.to_string();
+        let mut count = 0u64;
+
+        for res in self.tree.iter() {
+            let (key, value) = res.map_err(|e| AuditViolation {
+                seq: count,
+                reason: format!("read failed: {e}"),
+            })?;
+            let record: AuditRecord =
+                serde_json::from_slice(&value).map_err(|e| AuditViolation {
+                    seq: count,
+                    reason: format!("undecodable record: {e}"),
+                })?;
+
+            let violation = |reason: &str| AuditViolation {
+                seq: record.seq,
+                reason: reason.to_string(),
+            };
+            if record.seq != count || key.as_ref() != record.seq.to_be_bytes() {
+                return Err(violation("sequence gap or reordered record"));
+            }
+            if record.prev_hash != prev_hash {
+                return Err(violation("prev_hash does not match the previous record"));
+            }
+            let expected =
+                AuditRecord::compute_hash(record.seq, record.timestamp, &record.event, &prev_hash);
+            if record.hash != expected {
+                return Err(violation("record contents do not match its hash"));
+            }
+
+            prev_hash = record.hash;
+            count += 1;
+        }
+
+        Ok(AuditHead {
+            len: count,
+            hash: prev_hash,
+        })
+    }
+
+    /// Like [`AuditLog::verify`], and also checks that the chain still contains `anchor`, a
+    /// head recorded earlier: the record at `anchor.len - 1` must still carry `anchor.hash`.
+    pub fn verify_anchor(&self, anchor: &AuditHead) -> Result<AuditHead, AuditViolation> {
+        let head = self.verify()?;
+        let Some(seq) = anchor.len.checked_sub(1) else {
+            return Ok(head);
+        };
+        let hash = self
+            .tree
+            .get(seq.to_be_bytes())
+            .ok()
+            .flatten()
+            .and_then(|v| serde_json::from_slice::<AuditRecord>(&v).ok())
+            .map(|record| record.hash);
+        if hash.as_deref() != Some(anchor.hash.as_str()) {
+            return Err(AuditViolation {
+                seq,
+                reason: "chain does not contain the anchored head".to_string(),
+            });
+        }
+        Ok(head)
+    }
+
+    /// Writes every record as one JSON object per line. Returns the number written.
+    pub fn export_jsonl<W: Write>(&self, mut out: W) -> std::io::Result<usize> {
+        let mut n = 0;
+        for record in self.records() {
+            serde_json::to_writer(&mut out, &record)?;
+            out.write_all(b"\n")?;
+            n += 1;
+        }
+        out.flush()?;
+        Ok(n)
+    }
+
+    /// Raw tree access for tests that simulate tampering.
+    #[cfg(test)]
+    pub(crate) fn tree(&self) -> &sled::Tree {
+        &self.tree
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_detects_edits_and_deletions() {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("failed to open temporary sled db");
+        let log = AuditLog::open(&db).expect("open audit");
+        for (id, outcome) in [
+            ("SearchAgent", AuditOutcome::Allowed),
+            ("CalendarAgent", AuditOutcome::Denied),
+            ("SearchAgent", AuditOutcome::Succeeded),
+        ] {
+            log.append(AuditEvent {
+                identity_id: id.to_string(),
+                action: "authorize".to_string(),
+                scope: Some(AuthScope::WriteFacts),
+                resource: Resource::fact("facts", "AnalysisResult", id),
+                outcome,
+                detail: None,
+            })
+            .expect("append");
+        }
+        let anchor = log.verify().expect("intact");
+        assert_eq!(anchor.len, 3);
+
+        let mut out = Vec::new();
+        assert_eq!(log.export_jsonl(&mut out).expect("export"), 3);
+        assert_eq!(String::from_utf8(out).unwrap().lines().count(), 3);
+
+        // Reopening resumes the chain.
+        let reopened = AuditLog::open(&db).expect("reopen");
+        let next = reopened
+            .append(AuditEvent {
+                identity_id: "Orchestrator".to_string(),
+                action: "put_policy".to_string(),
+                scope: None,
+                resource: Resource::tree("policies"),
+                outcome: AuditOutcome::Succeeded,
+                detail: None,
+            })
+            .expect("append");
+        assert_eq!(next.seq, 3);
+        assert_eq!(
+            reopened.verify_anchor(&anchor),
+            Ok(AuditHead {
+                len: 4,
+                hash: next.hash,
+            })
+        );
+
+        // Rewriting the whole chain (re-hashing every record) only shows against an anchor.
+        let forged = sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("failed to open temporary sled db");
+        let forged_log = AuditLog::open(&forged).expect("open audit");
+        for record in log.records() {
+            forged_log
+                .append(AuditEvent {
+                    outcome: AuditOutcome::Allowed,
+                    ..record.event
+                })
+                .expect("append");
+        }
+        assert!(forged_log.verify().is_ok());
+        assert_eq!(forged_log.verify_anchor(&anchor).unwrap_err().seq, 2);
+
+        // Flip a denial into an allow.
+        let key = 1u64.to_be_bytes();
+        let mut record: AuditRecord =
+            serde_json::from_slice(&log.tree().get(key).unwrap().unwrap()).unwrap();
+        record.event.outcome = AuditOutcome::Allowed;
+        log.tree()
+            .insert(key, serde_json::to_vec(&record).unwrap())
+            .unwrap();
+        assert_eq!(log.verify().unwrap_err().seq, 1);
+
+        // Deleting a record breaks the chain as well.
+        log.tree().remove(key).unwrap();
+        assert_eq!(log.verify().unwrap_err().seq, 2);
+    }
+}
@@ -0,0 +1,428 @@
+//! Authorization / identity (PoLP).
+//!
+//! An [`AgentIdentity`] holds a list of granted [`AuthScope`]s. A plain scope such as
+//! [`AuthScope::WriteFacts`] applies to every resource; [`AuthScope::Scoped`] narrows a scope
+//! to the resources matching a [`ResourcePattern`], e.g. "may write facts of type
+//! `AnalysisResult` only" or "may read only its own facts" (`agent_id = $self`).
+//!
+//! The [`AuthorizationGatekeeper`] checks a required scope against a concrete [`Resource`]
+//! and can be configured with scope implications (e.g. `WriteFacts` ⇒ `ReadFacts`), which
+//! are applied to grants before matching and keep the grant's resource restriction.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Placeholder in a [`ResourcePattern::agent_id`] that matches the requesting identity's id.
+pub const SELF_AGENT: &str = "$self";
+
+/// Tree name gatekeeper configuration is authorized against. Implications are kept in
+/// memory; nothing is stored under it.
+pub const GATEKEEPER_RESOURCE: &str = "gatekeeper";
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub enum AuthScope {
+    ReadFacts,
+    WriteFacts,
+    WritePolicy,
+    ExternalAPI,
+    RoboticsAction,
+    /// `scope` restricted to resources matching `resource`.
+    Scoped {
+        scope: Box<AuthScope>,
+        resource: ResourcePattern,
+    },
+}
+
+impl AuthScope {
+    /// Restricts `scope` to `resource`. Re-scoping an already scoped grant replaces its
+    /// pattern rather than nesting.
+    pub fn scoped(scope: AuthScope, resource: ResourcePattern) -> Self {
+        AuthScope::Scoped {
+            scope: Box::new(scope.base().clone()),
+            resource,
+        }
+    }
+
+    /// The unrestricted scope this grant is a (possibly narrowed) form of.
+    pub fn base(&self) -> &AuthScope {
+        match self {
+            AuthScope::Scoped { scope, .. } => scope.base(),
+            other => other,
+        }
+    }
+
+    /// The resource restriction, or `None` for a grant that applies everywhere.
+    pub fn resource_pattern(&self) -> Option<&ResourcePattern> {
+        match self {
+            AuthScope::Scoped { resource, .. } => Some(resource),
+            _ => None,
+        }
+    }
+
+    /// Whether this grant satisfies `required` on `resource` for the agent `principal`.
+    pub fn permits(&self, required: &AuthScope, resource: &Resource, principal: &str) -> bool {
+        if self.base() != required.base() {
+            return false;
+        }
+        let grant_ok = self
+            .resource_pattern()
+            .is_none_or(|p| p.matches(resource, principal));
+        // A scoped requirement additionally constrains the resource itself.
+        let required_ok = required
+            .resource_pattern()
+            .is_none_or(|p| p.matches(resource, principal));
+        grant_ok && required_ok
+    }
+}
+
+/// The resource an operation touches. Attributes that don't apply are left `None`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Resource {
+    pub tree: Option<String>,
+    pub fact_type: Option<String>,
+    pub agent_id: Option<String>,
+    pub directive_kind: Option<String>,
+}
+
+impl Resource {
+    /// A fact stored in `tree`, authored by `agent_id`.
+    pub fn fact(tree: &str, fact_type: &str, agent_id: &str) -> Self {
+        Self {
+            tree: Some(tree.to_string()),
+            fact_type: Some(fact_type.to_string()),
+            agent_id: Some(agent_id.to_string()),
+            directive_kind: None,
+        }
+    }
+
+    /// A whole KB tree.
+    pub fn tree(tree: &str) -> Self {
+        Self {
+            tree: Some(tree.to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// A symbolic directive, classified by its kind (the text before the first `:`, e.g.
+    /// `Rerun` or `TASK`).
+    pub fn directive(directive: &str) -> Self {
+        let kind = directive.split(':').next().unwrap_or(directive).trim();
+        Self {
+            directive_kind: Some(kind.to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+impl fmt::Display for Resource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = [
+            ("tree", &self.tree),
+            ("fact_type", &self.fact_type),
+            ("agent_id", &self.agent_id),
+            ("directive_kind", &self.directive_kind),
+        ]
+        .iter()
+        .filter_map(|(k, v)| v.as_ref().map(|v| format!("{k}={v}")))
+        .collect();
+        if parts.is_empty() {
+            f.write_str("*")
+        } else {
+            f.write_str(&parts.join(", "))
+        }
+    }
+}
+
+/// Which resources a scoped grant covers.
+///
+/// Each attribute is a glob (`*` matches any run of characters); `None` means "any". An
+/// attribute that is set only matches resources that carry that attribute, so a grant
+/// narrowed to a fact type never covers a whole-tree operation.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourcePattern {
+    #[serde(default)]
+    pub tree: Option<String>,
+    #[serde(default)]
+    pub fact_type: Option<String>,
+    /// May be [`SELF_AGENT`] to mean the requesting identity.
+    #[serde(default)]
+    pub agent_id: Option<String>,
+    #[serde(default)]
+    pub directive_kind: Option<String>,
+}
+
+impl ResourcePattern {
+    pub fn fact_type(pattern: &str) -> Self {
+        Self {
+            fact_type: Some(pattern.to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub fn agent_id(pattern: &str) -> Self {
+        Self {
+            agent_id: Some(pattern.to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// Facts authored by the requesting identity.
+    pub fn own_facts() -> Self {
+        Self::agent_id(SELF_AGENT)
+    }
+
+    pub fn tree(pattern: &str) -> Self {
+        Self {
+            tree: Some(pattern.to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub fn directive_kind(pattern: &str) -> Self {
+        Self {
+            directive_kind: Some(pattern.to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub fn matches(&self, resource: &Resource, principal: &str) -> bool {
+        let attr = |pattern: &Option<String>, value: &Option<String>| match (pattern, value) {
+            (None, _) => true,
+            (Some(p), Some(v)) if p == SELF_AGENT => v == principal,
+            (Some(p), Some(v)) => glob_match(p, v),
+            (Some(p), None) => p == "*",
+        };
+        attr(&self.tree, &resource.tree)
+            && attr(&self.fact_type, &resource.fact_type)
+            && attr(&self.agent_id, &resource.agent_id)
+            && attr(&self.directive_kind, &resource.directive_kind)
+    }
+}
+
+/// Glob match where `*` matches any (possibly empty) run of characters.
+pub fn glob_match(pattern: &str, value: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let v: Vec<char> = value.chars().collect();
+    let (mut pi, mut vi) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+
+    while vi < v.len() {
+        if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, vi));
+            pi += 1;
+        } else if pi < p.len() && p[pi] == v[vi] {
+            pi += 1;
+            vi += 1;
+        } else if let Some((sp, sv)) = star {
+            pi = sp + 1;
+            vi = sv + 1;
+            star = Some((sp, sv + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|c| *c == '*')
+}
+
+#[derive(Clone)]
+pub struct AgentIdentity {
+    pub id: String,
+    pub scopes: Vec<AuthScope>,
+    /// Signed credential proving `id` and the scopes actually granted (see
+    /// [`credentials`](crate::credentials)). When present it is verified on every gated
+    /// call and its scopes replace `scopes`.
+    pub credential: Option<String>,
+}
+
+impl AgentIdentity {
+    /// An identity without a credential; only accepted while the core doesn't require
+    /// credentials.
+    pub fn new(id: impl Into<String>, scopes: Vec<AuthScope>) -> Self {
+        Self {
+            id: id.into(),
+            scopes,
+            credential: None,
+        }
+    }
+
+    pub fn with_credential(mut self, credential: &str) -> Self {
+        self.credential = Some(credential.to_string());
+        self
+    }
+}
+
+impl fmt::Debug for AgentIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Never log the credential itself.
+        f.debug_struct("AgentIdentity")
+            .field("id", &self.id)
+            .field("scopes", &self.scopes)
+            .field(
+                "credential",
+                &self.credential.as_ref().map(|_| "<redacted>"),
+            )
+            .finish()
+    }
+}
+
+/// Checks identities against required scopes.
+///
+/// The default gatekeeper has no implications, so a grant only satisfies its own scope.
+#[derive(Debug, Clone, Default)]
+pub struct AuthorizationGatekeeper {
+    /// `(from, to)`: holding `from` (on some resources) also grants `to` on the same ones.
+    implications: Vec<(AuthScope, AuthScope)>,
+}
+
+impl AuthorizationGatekeeper {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Configures `from` to imply `to`, e.g. `WriteFacts` ⇒ `ReadFacts`. Implications are
+    /// transitive.
+    pub fn with_implication(mut self, from: AuthScope, to: AuthScope) -> Self {
+        self.add_implication(from, to);
+        self
+    }
+
+    pub fn add_implication(&mut self, from: AuthScope, to: AuthScope) {
+        let pair = (from.base().clone(), to.base().clone());
+        if !self.implications.contains(&pair) {
+            self.implications.push(pair);
+        }
+    }
+
+    pub fn implications(&self) -> &[(AuthScope, AuthScope)] {
+        &self.implications
+    }
+
+    /// The identity's grants plus everything they imply.
+    pub fn effective_scopes(&self, identity: &AgentIdentity) -> Vec<AuthScope> {
+        let mut scopes = identity.scopes.clone();
+        let mut i = 0;
+        while i < scopes.len() {
+            for (from, to) in &self.implications {
+                if scopes[i].base() != from {
+                    continue;
+                }
+                let implied = match scopes[i].resource_pattern() {
+                    Some(p) => AuthScope::scoped(to.clone(), p.clone()),
+                    None => to.clone(),
+                };
+                if !scopes.contains(&implied) {
+                    scopes.push(implied);
+                }
+            }
+            i += 1;
+        }
+        scopes
+    }
+
+    /// Checks `required` on `resource`.
+    pub fn authorize(
+        &self,
+        identity: &AgentIdentity,
+        required: &AuthScope,
+        resource: &Resource,
+    ) -> Result<(), String> {
+        if self
+            .effective_scopes(identity)
+            .iter()
+            .any(|g| g.permits(required, resource, &identity.id))
+        {
+            return Ok(());
+        }
+        if resource.is_empty() {
+            Err(format!(
+                "Permission denied: agent '{}' missing required scope {:?}",
+                identity.id,
+                required.base()
+            ))
+        } else {
+            Err(format!(
+                "Permission denied: agent '{}' missing required scope {:?} on {resource}",
+                identity.id,
+                required.base()
+            ))
+        }
+    }
+
+    /// Whether the identity holds `required` on at least some resource. Callers that use
+    /// this must still check or filter each resource they return.
+    pub fn authorize_any(
+        &self,
+        identity: &AgentIdentity,
+        required: &AuthScope,
+    ) -> Result<(), String> {
+        if self
+            .effective_scopes(identity)
+            .iter()
+            .any(|g| g.base() == required.base())
+        {
+            Ok(())
+        } else {
+            Err(format!(
+                "Permission denied: agent '{}' missing required scope {:?}",
+                identity.id,
+                required.base()
+            ))
+        }
+    }
+
+    /// Checks an unrestricted `required` scope with the default (implication-free) rules.
+    pub fn can_access(identity: &AgentIdentity, required: AuthScope) -> Result<(), String> {
+        Self::default().authorize(identity, &required, &Resource::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scoped_grants_match_resources_and_follow_implications() {
+        let analyst = AgentIdentity::new(
+            "SearchAgent",
+            vec![
+                AuthScope::scoped(
+                    AuthScope::WriteFacts,
+                    ResourcePattern::fact_type("Analysis*"),
+                ),
+                AuthScope::scoped(AuthScope::ReadFacts, ResourcePattern::own_facts()),
+            ],
+        );
+        let gk = AuthorizationGatekeeper::new();
+        let write = AuthScope::WriteFacts;
+        let read = AuthScope::ReadFacts;
+
+        let own = Resource::fact("facts", "AnalysisResult", "SearchAgent");
+        let other = Resource::fact("facts", "CalendarEvent", "CalendarAgent");
+        assert!(gk.authorize(&analyst, &write, &own).is_ok());
+        assert!(gk.authorize(&analyst, &write, &other).is_err());
+        assert!(gk.authorize(&analyst, &read, &own).is_ok());
+        assert!(gk.authorize(&analyst, &read, &other).is_err());
+        // Narrowed grants never cover resource-less (whole KB) checks.
+        assert!(AuthorizationGatekeeper::can_access(&analyst, AuthScope::WriteFacts).is_err());
+        assert!(gk.authorize_any(&analyst, &read).is_ok());
+
+        let writer = AgentIdentity::new(
+            "CalendarAgent",
+            vec![AuthScope::scoped(
+                AuthScope::WriteFacts,
+                ResourcePattern::agent_id("Calendar*"),
+            )],
+        );
+        assert!(gk.authorize(&writer, &read, &other).is_err());
+        let implying = AuthorizationGatekeeper::new()
+            .with_implication(AuthScope::WriteFacts, AuthScope::ReadFacts);
+        assert!(implying.authorize(&writer, &read, &other).is_ok());
+        // The implied grant keeps the writer's resource restriction.
+        assert!(implying.authorize(&writer, &read, &own).is_err());
+    }
+}
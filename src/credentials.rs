@@ -0,0 +1,427 @@
+//! Signed, expiring agent credentials.
+//!
+//! The core's [`CredentialAuthority`] mints HMAC-SHA256 tokens carrying an identity, its
+//! scopes, an expiry and a random nonce:
+//!
+//! ```text
+//! pagi1.<kid>.<hex(json claims)>.<hex(mac)>
+//! ```
+//!
+//! `kid` names the signing key, so keys can be rotated: [`CredentialAuthority::rotate`] makes
+//! a fresh key active for minting while tokens signed with older keys keep verifying until
+//! the old key is retired.
+//!
+//! An authority opened on a knowledge base ([`CredentialAuthority::open`]) keeps its keys and
+//! revocation list in the `credentials` tree, so credentials survive restarts and every core
+//! opened on the same KB accepts them. Anyone who can read the KB files can mint credentials;
+//! protect its directory accordingly.
+//!
+//! A verified credential replaces the scopes on a caller-built [`AgentIdentity`], so with
+//! enforcement on (the default; see
+//! [`PAGICoreModel::require_credentials`](crate::PAGICoreModel::require_credentials)) an agent
+//! can't grant itself scopes by constructing the struct.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::auth::{AgentIdentity, AuthScope};
+use crate::ipc::{IpcAuthenticator, PeerCredentials};
+use crate::{from_hex, to_hex, unix_now};
+
+/// Version prefix of the token format.
+const TOKEN_PREFIX: &str = "pagi1";
+
+/// Environment variable that, set to `0`, turns credential enforcement off for new cores.
+/// Meant for migrating existing deployments only.
+pub const PAGI_REQUIRE_CREDENTIALS_ENV: &str = "PAGI_REQUIRE_CREDENTIALS";
+
+/// KB tree holding signing keys under `key/<kid>`, revoked nonces under `revoked/<nonce>`
+/// and the active key id.
+pub const CREDENTIALS_TREE: &str = "credentials";
+
+const ACTIVE_KEY: &str = "active";
+const NEXT_KID_KEY: &str = "next_kid";
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// What a credential asserts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CredentialClaims {
+    /// Agent id.
+    pub sub: String,
+    pub scopes: Vec<AuthScope>,
+    /// Issued at (unix seconds).
+    pub iat: u64,
+    /// Expires at (unix seconds).
+    pub exp: u64,
+    /// Random, per-credential; used for revocation.
+    pub nonce: String,
+}
+
+#[derive(Debug, Default)]
+struct Keyring {
+    active: String,
+    keys: BTreeMap<String, Vec<u8>>,
+    next_kid: u64,
+    /// Revoked nonce -> expiry of the revoked credential.
+    revoked: HashMap<String, u64>,
+}
+
+/// Mints and verifies credentials. Clones share keys and the revocation list.
+#[derive(Debug, Clone)]
+pub struct CredentialAuthority {
+    keyring: Arc<RwLock<Keyring>>,
+    /// Where the keyring is persisted; `None` for in-memory authorities.
+    tree: Option<sled::Tree>,
+}
+
+impl CredentialAuthority {
+    /// Creates an in-memory authority with one freshly generated key.
+    pub fn new() -> Result<Self, String> {
+        let authority = Self {
+            keyring: Arc::new(RwLock::new(Keyring::default())),
+            tree: None,
+        };
+        authority.rotate()?;
+        Ok(authority)
+    }
+
+    /// Opens the authority stored in `db`, generating its first key if there is none.
+    /// Revocations of credentials that have since expired are dropped.
+    pub fn open(db: &sled::Db) -> Result<Self, String> {
+        let tree = db.open_tree(CREDENTIALS_TREE).map_err(write_err)?;
+        let mut ring = Keyring::default();
+        let now = unix_now();
+        for entry in tree.iter() {
+            let (k, v) = entry.map_err(write_err)?;
+            let k = String::from_utf8_lossy(&k);
+            if let Some(kid) = k.strip_prefix("key/") {
+                ring.keys.insert(kid.to_string(), v.to_vec());
+            } else if let Some(nonce) = k.strip_prefix("revoked/") {
+                let exp = decode_u64(&v);
+                if exp > now {
+                    ring.revoked.insert(nonce.to_string(), exp);
+                } else {
+                    tree.remove(k.as_bytes()).map_err(write_err)?;
+                }
+            } else if k == ACTIVE_KEY {
+                ring.active = String::from_utf8_lossy(&v).into_owned();
+            } else if k == NEXT_KID_KEY {
+                ring.next_kid = decode_u64(&v);
+            }
+        }
+        let empty = ring.keys.is_empty();
+        let authority = Self {
+            keyring: Arc::new(RwLock::new(ring)),
+            tree: Some(tree),
+        };
+        if empty {
+            authority.rotate()?;
+        }
+        Ok(authority)
+    }
+
+    /// Generates a new signing key, makes it active and returns its id. Older keys keep
+    /// verifying until [`CredentialAuthority::retire`]d.
+    pub fn rotate(&self) -> Result<String, String> {
+        let mut secret = vec![0u8; 32];
+        getrandom::getrandom(&mut secret)
+            .map_err(|e| format!("Failed to generate credential key: {e}"))?;
+
+        let mut ring = self.write();
+        // Skip ids taken by keys installed with `install_key`.
+        let kid = loop {
+            ring.next_kid += 1;
+            let kid = format!("k{}", ring.next_kid);
+            if !ring.keys.contains_key(&kid) {
+                break kid;
+            }
+        };
+        self.persist(|tree| {
+            tree.insert(NEXT_KID_KEY, &ring.next_kid.to_be_bytes())?;
+            tree.insert(format!("key/{kid}"), secret.as_slice())?;
+            tree.insert(ACTIVE_KEY, kid.as_bytes())?;
+            Ok(())
+        })?;
+        ring.keys.insert(kid.clone(), secret);
+        ring.active = kid.clone();
+        Ok(kid)
+    }
+
+    /// Installs an externally managed key and makes it active (e.g. one shared between
+    /// orchestrators). Ids already in use are rejected; retire the old key first.
+    pub fn install_key(&self, kid: &str, secret: &[u8]) -> Result<(), String> {
+        if kid.is_empty() || kid.contains('.') {
+            return Err(format!("Invalid credential key id '{kid}'"));
+        }
+        let mut ring = self.write();
+        if ring.keys.contains_key(kid) {
+            return Err(format!("Credential key id '{kid}' is already in use"));
+        }
+        self.persist(|tree| {
+            tree.insert(format!("key/{kid}"), secret)?;
+            tree.insert(ACTIVE_KEY, kid.as_bytes())?;
+            Ok(())
+        })?;
+        ring.keys.insert(kid.to_string(), secret.to_vec());
+        ring.active = kid.to_string();
+        Ok(())
+    }
+
+    /// Removes a key; credentials signed with it stop verifying. The active key can't be
+    /// retired.
+    pub fn retire(&self, kid: &str) -> Result<bool, String> {
+        let mut ring = self.write();
+        if ring.active == kid {
+            return Err(format!("Refusing to retire active credential key '{kid}'"));
+        }
+        self.persist(|tree| tree.remove(format!("key/{kid}")).map(|_| ()))?;
+        Ok(ring.keys.remove(kid).is_some())
+    }
+
+    pub fn active_key_id(&self) -> String {
+        self.read().active.clone()
+    }
+
+    /// Mints a credential for `identity` (its id and scopes) valid for `ttl`.
+    pub fn mint(&self, identity: &AgentIdentity, ttl: Duration) -> Result<String, String> {
+        let mut nonce = [0u8; 16];
+        getrandom::getrandom(&mut nonce)
+            .map_err(|e| format!("Failed to generate credential nonce: {e}"))?;
+        let iat = unix_now();
+        let claims = CredentialClaims {
+            sub: identity.id.clone(),
+            scopes: identity.scopes.clone(),
+            iat,
+            exp: iat.saturating_add(ttl.as_secs()),
+            nonce: to_hex(&nonce),
+        };
+
+        let ring = self.read();
+        let secret = ring
+            .keys
+            .get(&ring.active)
+            .ok_or_else(|| "No active credential key".to_string())?;
+        let payload = to_hex(&serde_json::to_vec(&claims).expect("failed to serialize claims"));
+        let signed = format!("{TOKEN_PREFIX}.{}.{payload}", ring.active);
+        let mac = sign(secret, &signed);
+        Ok(format!("{signed}.{mac}"))
+    }
+
+    /// Checks signature, expiry and revocation, and returns the claims.
+    pub fn verify(&self, token: &str) -> Result<CredentialClaims, String> {
+        self.verify_at(token, unix_now())
+    }
+
+    pub fn verify_at(&self, token: &str, now: u64) -> Result<CredentialClaims, String> {
+        let mut parts = token.split('.');
+        let (Some(prefix), Some(kid), Some(payload), Some(mac), None) = (
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+        ) else {
+            return Err("Malformed credential".to_string());
+        };
+        if prefix != TOKEN_PREFIX {
+            return Err(format!("Unsupported credential version '{prefix}'"));
+        }
+
+        let ring = self.read();
+        let secret = ring
+            .keys
+            .get(kid)
+            .ok_or_else(|| format!("Credential signed with unknown key '{kid}'"))?;
+        let mac_bytes = from_hex(mac).ok_or_else(|| "Malformed credential".to_string())?;
+        let mut verifier =
+            HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any length");
+        verifier.update(format!("{prefix}.{kid}.{payload}").as_bytes());
+        verifier
+            .verify_slice(&mac_bytes)
+            .map_err(|_| "Credential signature mismatch".to_string())?;
+
+        let claims: CredentialClaims = from_hex(payload)
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .ok_or_else(|| "Malformed credential claims".to_string())?;
+        if now >= claims.exp {
+            return Err(format!("Credential for '{}' expired", claims.sub));
+        }
+        if ring.revoked.contains_key(&claims.nonce) {
+            return Err(format!("Credential for '{}' was revoked", claims.sub));
+        }
+        Ok(claims)
+    }
+
+    /// Revokes a credential before it expires.
+    pub fn revoke(&self, token: &str) -> Result<(), String> {
+        let claims = self.verify(token)?;
+        let mut ring = self.write();
+        self.persist(|tree| {
+            tree.insert(
+                format!("revoked/{}", claims.nonce),
+                &claims.exp.to_be_bytes(),
+            )
+            .map(|_| ())
+        })?;
+        ring.revoked.insert(claims.nonce, claims.exp);
+        Ok(())
+    }
+
+    /// Verifies `identity.credential` and returns the identity it proves: same id, scopes
+    /// taken from the credential.
+    pub fn resolve(&self, identity: &AgentIdentity) -> Result<AgentIdentity, String> {
+        let token = identity
+            .credential
+            .as_deref()
+            .ok_or_else(|| format!("Agent '{}' presented no credential", identity.id))?;
+        let claims = self.verify(token)?;
+        if claims.sub != identity.id {
+            return Err(format!(
+                "Credential was issued to '{}', not '{}'",
+                claims.sub, identity.id
+            ));
+        }
+        Ok(AgentIdentity::new(claims.sub, claims.scopes).with_credential(token))
+    }
+
+    /// Applies `update` to the backing tree (if any) and flushes it.
+    fn persist(
+        &self,
+        update: impl FnOnce(&sled::Tree) -> Result<(), sled::Error>,
+    ) -> Result<(), String> {
+        let Some(tree) = &self.tree else {
+            return Ok(());
+        };
+        update(tree).map_err(write_err)?;
+        tree.flush().map_err(write_err)?;
+        Ok(())
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, Keyring> {
+        self.keyring.read().expect("credential keyring poisoned")
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, Keyring> {
+        self.keyring.write().expect("credential keyring poisoned")
+    }
+}
+
+fn decode_u64(bytes: &[u8]) -> u64 {
+    bytes.try_into().map(u64::from_be_bytes).unwrap_or(0)
+}
+
+fn write_err(e: sled::Error) -> String {
+    format!("Credential keyring write failed: {e}")
+}
+
+fn sign(secret: &[u8], data: &str) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(data.as_bytes());
+    to_hex(&mac.finalize().into_bytes())
+}
+
+/// Lets an [`IpcBus`](crate::IpcBus) accept credentials as `Hello` tokens.
+impl IpcAuthenticator for CredentialAuthority {
+    fn authenticate(
+        &self,
+        agent_id: &str,
+        token: &str,
+        _peer: &PeerCredentials,
+    ) -> Result<AgentIdentity, String> {
+        self.resolve(&AgentIdentity::new(agent_id, Vec::new()).with_credential(token))
+            .map_err(|e| format!("IPC authentication failed for '{agent_id}': {e}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn credentials_verify_expire_rotate_and_reject_tampering() {
+        let authority = CredentialAuthority::new().expect("authority");
+        let identity = AgentIdentity::new("SearchAgent", vec![AuthScope::WriteFacts]);
+        let token = authority
+            .mint(&identity, Duration::from_secs(60))
+            .expect("mint");
+
+        let claims = authority.verify(&token).expect("valid");
+        assert_eq!(claims.sub, "SearchAgent");
+        assert!(authority.verify_at(&token, claims.exp).is_err());
+
+        // Claiming extra scopes breaks the signature.
+        let forged_claims = CredentialClaims {
+            scopes: vec![AuthScope::WritePolicy],
+            ..claims.clone()
+        };
+        let parts: Vec<&str> = token.split('.').collect();
+        let forged = format!(
+            "{}.{}.{}.{}",
+            parts[0],
+            parts[1],
+            to_hex(&serde_json::to_vec(&forged_claims).unwrap()),
+            parts[3]
+        );
+        assert!(authority.verify(&forged).is_err());
+
+        // Caller-built scopes are replaced by the credential's.
+        let inflated =
+            AgentIdentity::new("SearchAgent", vec![AuthScope::WritePolicy]).with_credential(&token);
+        let resolved = authority.resolve(&inflated).expect("resolve");
+        assert_eq!(resolved.scopes, vec![AuthScope::WriteFacts]);
+        let stolen = AgentIdentity::new("CalendarAgent", vec![]).with_credential(&token);
+        assert!(authority.resolve(&stolen).is_err());
+
+        // Rotation keeps old tokens valid until the old key is retired.
+        let old_kid = authority.active_key_id();
+        let new_kid = authority.rotate().expect("rotate");
+        assert_ne!(old_kid, new_kid);
+        assert!(authority.verify(&token).is_ok());
+        assert!(authority.retire(&new_kid).is_err());
+        assert!(authority.retire(&old_kid).expect("retire"));
+        assert!(authority.verify(&token).is_err());
+
+        let fresh = authority
+            .mint(&identity, Duration::from_secs(60))
+            .expect("mint");
+        authority.revoke(&fresh).expect("revoke");
+        assert!(authority.verify(&fresh).is_err());
+
+        // Ids in use can't be reinstalled, and rotation skips installed ids.
+        assert!(authority.install_key(&new_kid, b"other secret").is_err());
+        authority
+            .install_key("k3", b"external secret")
+            .expect("install");
+        assert_eq!(authority.rotate().expect("rotate"), "k4");
+    }
+
+    #[test]
+    fn persisted_keyring_and_revocations_survive_reopen() {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("failed to open temporary sled db");
+        let identity = AgentIdentity::new("SearchAgent", vec![AuthScope::WriteFacts]);
+        let authority = CredentialAuthority::open(&db).expect("open");
+        let kept = authority
+            .mint(&identity, Duration::from_secs(60))
+            .expect("mint");
+        let revoked = authority
+            .mint(&identity, Duration::from_secs(60))
+            .expect("mint");
+        authority.revoke(&revoked).expect("revoke");
+        let kid = authority.rotate().expect("rotate");
+
+        let reopened = CredentialAuthority::open(&db).expect("reopen");
+        assert_eq!(reopened.active_key_id(), kid);
+        assert!(reopened.verify(&kept).is_ok());
+        assert!(reopened.verify(&revoked).is_err());
+        assert_ne!(reopened.rotate().expect("rotate"), kid);
+    }
+}
@@ -0,0 +1,323 @@
+//! Scope delegation and just-in-time privilege elevation.
+//!
+//! A [`Delegation`] lends some of one identity's scopes to another for a limited time and,
+//! optionally, a limited number of uses. Delegations are consulted only when the delegate's
+//! own scopes and the policies don't already allow a request, and explicit policy denies
+//! still win.
+//!
+//! Elevation is the pull-based variant: an agent files an [`ElevationRequest`] and an
+//! approver who holds the requested scopes signs off, which creates a delegation from the
+//! approver. Both trees live in the KB, so grants and approvals survive restarts and show
+//! up in the audit log.
+
+use serde::{Deserialize, Serialize};
+
+use crate::auth::{AgentIdentity, AuthScope};
+
+/// KB tree holding delegations, keyed by delegation id.
+pub const DELEGATIONS_TREE: &str = "delegations";
+
+/// KB tree holding elevation requests, keyed by request id.
+pub const ELEVATION_REQUESTS_TREE: &str = "elevation_requests";
+
+/// Why a delegation exists.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DelegationOrigin {
+    /// Pushed by the delegator with [`PAGICoreModel::delegate`](crate::PAGICoreModel::delegate).
+    Direct,
+    /// Created by approving an elevation request.
+    Elevation { request_id: String },
+}
+
+/// Scopes lent from `delegator` to `delegate`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Delegation {
+    pub id: String,
+    pub delegator: String,
+    pub delegate: String,
+    pub scopes: Vec<AuthScope>,
+    pub created_at: u64,
+    /// Unix seconds after which the delegation no longer applies.
+    pub expires_at: u64,
+    /// `None` for unlimited uses until expiry.
+    pub max_uses: Option<u32>,
+    pub uses: u32,
+    pub origin: DelegationOrigin,
+    /// Set when revoked explicitly, by expiry sweep, or after the last use.
+    pub revoked: bool,
+}
+
+impl Delegation {
+    /// Whether the delegation can still be used at `now`.
+    pub fn is_active(&self, now: u64) -> bool {
+        !self.revoked && now < self.expires_at && self.max_uses.is_none_or(|max| self.uses < max)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ElevationStatus {
+    Pending,
+    Approved {
+        approver: String,
+        delegation_id: String,
+        at: u64,
+    },
+    Denied {
+        approver: String,
+        reason: String,
+        at: u64,
+    },
+}
+
+/// An agent asking for temporary extra scopes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ElevationRequest {
+    pub id: String,
+    pub requester: String,
+    pub scopes: Vec<AuthScope>,
+    /// How long the resulting delegation lasts once approved.
+    pub ttl_secs: u64,
+    pub max_uses: Option<u32>,
+    pub justification: String,
+    pub created_at: u64,
+    pub status: ElevationStatus,
+}
+
+/// Whether `grants` let the holder hand out `scope`: an identical grant, or an
+/// unrestricted grant of the same base scope.
+pub fn covers(grants: &[AuthScope], scope: &AuthScope) -> bool {
+    grants
+        .iter()
+        .any(|g| g == scope || (g.resource_pattern().is_none() && g.base() == scope.base()))
+}
+
+/// `identity` with the scopes of `delegations` added.
+pub fn elevate(identity: &AgentIdentity, delegations: &[Delegation]) -> AgentIdentity {
+    let mut elevated = identity.clone();
+    for scope in delegations.iter().flat_map(|d| &d.scopes) {
+        if !elevated.scopes.contains(scope) {
+            elevated.scopes.push(scope.clone());
+        }
+    }
+    elevated
+}
+
+/// KB-backed store of delegations and elevation requests.
+#[derive(Debug, Clone)]
+pub struct DelegationStore {
+    db: sled::Db,
+    delegations: sled::Tree,
+    requests: sled::Tree,
+}
+
+impl DelegationStore {
+    pub fn open(db: &sled::Db) -> Result<Self, sled::Error> {
+        Ok(Self {
+            db: db.clone(),
+            delegations: db.open_tree(DELEGATIONS_TREE)?,
+            requests: db.open_tree(ELEVATION_REQUESTS_TREE)?,
+        })
+    }
+
+    /// Stores a new delegation. Callers are responsible for checking the delegator holds
+    /// the scopes.
+    #[allow(clippy::too_many_arguments)]
+    pub fn create(
+        &self,
+        delegator: &AgentIdentity,
+        delegate: &str,
+        scopes: Vec<AuthScope>,
+        ttl_secs: u64,
+        max_uses: Option<u32>,
+        origin: DelegationOrigin,
+        now: u64,
+    ) -> Result<Delegation, sled::Error> {
+        let delegation = Delegation {
+            id: format!("dlg-{}", self.db.generate_id()?),
+            delegator: delegator.id.clone(),
+            delegate: delegate.to_string(),
+            scopes,
+            created_at: now,
+            expires_at: now.saturating_add(ttl_secs),
+            max_uses,
+            uses: 0,
+            origin,
+            revoked: false,
+        };
+        self.put_delegation(&delegation)?;
+        Ok(delegation)
+    }
+
+    pub fn get(&self, id: &str) -> Option<Delegation> {
+        let v = self.delegations.get(id.as_bytes()).ok()??;
+        serde_json::from_slice(&v).ok()
+    }
+
+    /// All delegations, ordered by id.
+    pub fn all(&self) -> Vec<Delegation> {
+        self.delegations
+            .iter()
+            .filter_map(|res| res.ok())
+            .filter_map(|(_, v)| serde_json::from_slice(&v).ok())
+            .collect()
+    }
+
+    /// Delegations `delegate` can use at `now`.
+    pub fn active_for(&self, delegate: &str, now: u64) -> Vec<Delegation> {
+        self.all()
+            .into_iter()
+            .filter(|d| d.delegate == delegate && d.is_active(now))
+            .collect()
+    }
+
+    /// Counts one use; the delegation is revoked once it reaches `max_uses`. Returns the
+    /// updated delegation, or `None` if it was no longer usable.
+    pub fn consume(&self, id: &str, now: u64) -> Result<Option<Delegation>, sled::Error> {
+        let mut used = None;
+        self.delegations.fetch_and_update(id.as_bytes(), |old| {
+            // The closure may rerun if the entry changed concurrently.
+            used = None;
+            let mut d: Delegation = serde_json::from_slice(old?).ok()?;
+            if !d.is_active(now) {
+                return Some(serde_json::to_vec(&d).expect("failed to serialize Delegation"));
+            }
+            d.uses += 1;
+            if d.max_uses.is_some_and(|max| d.uses >= max) {
+                d.revoked = true;
+            }
+            let bytes = serde_json::to_vec(&d).expect("failed to serialize Delegation");
+            used = Some(d);
+            Some(bytes)
+        })?;
+        Ok(used)
+    }
+
+    /// Marks a delegation revoked. Returns whether it was active.
+    pub fn revoke(&self, id: &str) -> Result<bool, sled::Error> {
+        let Some(mut d) = self.get(id) else {
+            return Ok(false);
+        };
+        let was_active = !d.revoked;
+        d.revoked = true;
+        self.put_delegation(&d)?;
+        Ok(was_active)
+    }
+
+    /// Revokes every delegation that expired before `now` and returns them.
+    pub fn revoke_expired(&self, now: u64) -> Result<Vec<Delegation>, sled::Error> {
+        let mut expired = Vec::new();
+        for mut d in self.all() {
+            if !d.revoked && now >= d.expires_at {
+                d.revoked = true;
+                self.put_delegation(&d)?;
+                expired.push(d);
+            }
+        }
+        Ok(expired)
+    }
+
+    pub fn create_request(
+        &self,
+        requester: &str,
+        scopes: Vec<AuthScope>,
+        ttl_secs: u64,
+        max_uses: Option<u32>,
+        justification: &str,
+        now: u64,
+    ) -> Result<ElevationRequest, sled::Error> {
+        let request = ElevationRequest {
+            id: format!("elv-{}", self.db.generate_id()?),
+            requester: requester.to_string(),
+            scopes,
+            ttl_secs,
+            max_uses,
+            justification: justification.to_string(),
+            created_at: now,
+            status: ElevationStatus::Pending,
+        };
+        self.put_request(&request)?;
+        Ok(request)
+    }
+
+    pub fn request(&self, id: &str) -> Option<ElevationRequest> {
+        let v = self.requests.get(id.as_bytes()).ok()??;
+        serde_json::from_slice(&v).ok()
+    }
+
+    pub fn requests(&self) -> Vec<ElevationRequest> {
+        self.requests
+            .iter()
+            .filter_map(|res| res.ok())
+            .filter_map(|(_, v)| serde_json::from_slice(&v).ok())
+            .collect()
+    }
+
+    pub fn put_request(&self, request: &ElevationRequest) -> Result<(), sled::Error> {
+        let value = serde_json::to_vec(request).expect("failed to serialize ElevationRequest");
+        self.requests.insert(request.id.as_bytes(), value)?;
+        self.requests.flush()?;
+        Ok(())
+    }
+
+    fn put_delegation(&self, delegation: &Delegation) -> Result<(), sled::Error> {
+        let value = serde_json::to_vec(delegation).expect("failed to serialize Delegation");
+        self.delegations.insert(delegation.id.as_bytes(), value)?;
+        self.delegations.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delegations_expire_and_run_out_of_uses() {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("failed to open temporary sled db");
+        let store = DelegationStore::open(&db).expect("open");
+        let admin = AgentIdentity::new("Orchestrator", vec![AuthScope::WritePolicy]);
+
+        let limited = store
+            .create(
+                &admin,
+                "CybersecurityAgent",
+                vec![AuthScope::WritePolicy],
+                60,
+                Some(2),
+                DelegationOrigin::Direct,
+                1_000,
+            )
+            .expect("create");
+        assert_eq!(store.active_for("CybersecurityAgent", 1_000).len(), 1);
+        assert!(store.consume(&limited.id, 1_001).unwrap().is_some());
+        let last = store
+            .consume(&limited.id, 1_002)
+            .unwrap()
+            .expect("second use");
+        assert!(last.revoked);
+        assert!(store.consume(&limited.id, 1_003).unwrap().is_none());
+
+        let timed = store
+            .create(
+                &admin,
+                "CybersecurityAgent",
+                vec![AuthScope::WritePolicy],
+                60,
+                None,
+                DelegationOrigin::Direct,
+                1_000,
+            )
+            .expect("create");
+        assert!(store.active_for("CybersecurityAgent", 1_059).len() == 1);
+        assert!(store.active_for("CybersecurityAgent", 1_060).is_empty());
+        let expired = store.revoke_expired(1_060).expect("sweep");
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].id, timed.id);
+
+        assert!(covers(&admin.scopes, &AuthScope::WritePolicy));
+        assert!(!covers(&admin.scopes, &AuthScope::WriteFacts));
+    }
+}
@@ -0,0 +1,722 @@
+//! Fact embeddings and approximate nearest-neighbour search.
+//!
+//! Every fact written to the KB is embedded by the core's [`Embedder`] (by default the
+//! offline [`HashingEmbedder`]). The vector is stored in the `fact_embeddings` tree under the
+//! fact's key. Vectors are also inserted into an HNSW graph, whose links live in the
+//! `hnsw_graph` tree. The graph is loaded into memory on open and written through on every
+//! insert, so it survives restarts without being rebuilt. Removing a fact unlinks its node
+//! right away: nodes that pointed to it are reconnected through its neighbours, so the graph
+//! stays connected and searches don't slow down as facts are removed.
+//!
+//! [`PAGICoreModel::semantic_search`](crate::PAGICoreModel::semantic_search) embeds the query,
+//! walks the graph and applies [`FactFilter`] and authorization to the candidates.
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+
+use serde::{Deserialize, Serialize};
+
+use crate::AgentFact;
+
+/// KB tree holding one little-endian `f32` vector per fact, keyed like the `facts` tree.
+pub const FACT_EMBEDDINGS_TREE: &str = "fact_embeddings";
+
+/// KB tree holding each HNSW node's links, keyed like the `facts` tree.
+pub const HNSW_GRAPH_TREE: &str = "hnsw_graph";
+
+/// KB tree holding the entry point and the id of the embedder that produced the vectors.
+pub const VECTOR_META_TREE: &str = "vector_meta";
+
+const ENTRY_KEY: &str = "entry";
+const EMBEDDER_KEY: &str = "embedder";
+
+/// Upper bound on node levels, far above what any realistic KB reaches.
+const MAX_LEVEL: usize = 16;
+
+/// Turns text into fixed-size vectors.
+pub trait Embedder: Send + Sync {
+    /// Identifies the model and its settings. Vectors from embedders with different ids are
+    /// not comparable, so changing it triggers a reindex.
+    fn id(&self) -> String;
+
+    fn dimensions(&self) -> usize;
+
+    fn embed(&self, text: &str) -> Vec<f32>;
+}
+
+/// Deterministic bag-of-words embedder for offline use.
+///
+/// Lowercased words and word bigrams are hashed into `dimensions` buckets with a random
+/// sign (the "hashing trick"), then the vector is L2-normalized. Texts that share words end
+/// up close; synonyms do not.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HashingEmbedder {
+    dimensions: usize,
+}
+
+impl HashingEmbedder {
+    pub fn new(dimensions: usize) -> Self {
+        Self {
+            dimensions: dimensions.max(1),
+        }
+    }
+}
+
+impl Default for HashingEmbedder {
+    fn default() -> Self {
+        Self::new(256)
+    }
+}
+
+impl Embedder for HashingEmbedder {
+    fn id(&self) -> String {
+        format!("hashing-bow-{}", self.dimensions)
+    }
+
+    fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    fn embed(&self, text: &str) -> Vec<f32> {
+        let lowered = text.to_lowercase();
+        let words: Vec<&str> = lowered
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .collect();
+
+        let mut vector = vec![0.0f32; self.dimensions];
+        let mut add = |feature: &str, weight: f32| {
+            let h = fnv1a(feature.as_bytes());
+            let sign = if h >> 63 == 1 { -1.0 } else { 1.0 };
+            vector[(h % self.dimensions as u64) as usize] += sign * weight;
+        };
+        for word in &words {
+            add(word, 1.0);
+        }
+        for pair in words.windows(2) {
+            add(&format!("{} {}", pair[0], pair[1]), 0.5);
+        }
+        normalize(&mut vector);
+        vector
+    }
+}
+
+/// The text embedded for a fact.
+pub fn fact_text(fact: &AgentFact) -> String {
+    format!("{} {}", fact.fact_type, fact.content)
+}
+
+/// Restricts [`semantic_search`](crate::PAGICoreModel::semantic_search) results. Empty lists
+/// match everything.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FactFilter {
+    pub fact_types: Vec<String>,
+    pub agent_ids: Vec<String>,
+    /// Inclusive lower bound on `timestamp`.
+    pub since: Option<u64>,
+    /// Exclusive upper bound on `timestamp`.
+    pub until: Option<u64>,
+}
+
+impl FactFilter {
+    pub fn fact_type(fact_type: impl Into<String>) -> Self {
+        Self {
+            fact_types: vec![fact_type.into()],
+            ..Default::default()
+        }
+    }
+
+    pub fn matches(&self, fact: &AgentFact) -> bool {
+        (self.fact_types.is_empty() || self.fact_types.contains(&fact.fact_type))
+            && (self.agent_ids.is_empty() || self.agent_ids.contains(&fact.agent_id))
+            && self.since.is_none_or(|since| fact.timestamp >= since)
+            && self.until.is_none_or(|until| fact.timestamp < until)
+    }
+}
+
+/// A search hit: the fact and its cosine similarity to the query.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScoredFact {
+    /// Key of the fact in the `facts` tree.
+    pub key: String,
+    pub fact: AgentFact,
+    pub score: f32,
+}
+
+/// HNSW construction settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HnswParams {
+    /// Links per node above layer 0 (twice as many on layer 0).
+    pub m: usize,
+    /// Candidate list size while inserting.
+    pub ef_construction: usize,
+}
+
+impl Default for HnswParams {
+    fn default() -> Self {
+        Self {
+            m: 16,
+            ef_construction: 100,
+        }
+    }
+}
+
+/// Persisted form of a node's links; neighbours are referenced by fact key.
+#[derive(Debug, Serialize, Deserialize)]
+struct NodeRecord {
+    links: Vec<Vec<String>>,
+}
+
+/// A distance to a node, ordered by distance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Candidate(f32, usize);
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0).then(self.1.cmp(&other.1))
+    }
+}
+
+/// In-memory HNSW graph over normalized vectors, with cosine distance.
+#[derive(Debug, Default)]
+struct Graph {
+    keys: Vec<String>,
+    ids: HashMap<String, usize>,
+    vectors: Vec<Vec<f32>>,
+    /// `links[node][layer]`.
+    links: Vec<Vec<Vec<usize>>>,
+    entry: Option<usize>,
+    /// Slots of removed nodes. Nothing links to them; they are reclaimed on the next open.
+    deleted: HashSet<usize>,
+}
+
+impl Graph {
+    fn distance(&self, query: &[f32], node: usize) -> f32 {
+        1.0 - dot(query, &self.vectors[node])
+    }
+
+    fn top_level(&self, node: usize) -> usize {
+        self.links[node].len() - 1
+    }
+
+    /// The `ef` nodes closest to `query` reachable on `layer` from `entries`, closest first.
+    fn search_layer(
+        &self,
+        query: &[f32],
+        entries: &[usize],
+        ef: usize,
+        layer: usize,
+    ) -> Vec<Candidate> {
+        let mut visited: HashSet<usize> = entries.iter().copied().collect();
+        let mut candidates = BinaryHeap::new();
+        let mut found = BinaryHeap::new();
+        for &e in entries {
+            let c = Candidate(self.distance(query, e), e);
+            candidates.push(Reverse(c));
+            found.push(c);
+        }
+        while let Some(Reverse(current)) = candidates.pop() {
+            if found.len() >= ef && found.peek().is_some_and(|f: &Candidate| current.0 > f.0) {
+                break;
+            }
+            let Some(neighbours) = self.links[current.1].get(layer) else {
+                continue;
+            };
+            for &n in neighbours {
+                if !visited.insert(n) {
+                    continue;
+                }
+                let c = Candidate(self.distance(query, n), n);
+                if found.len() < ef || found.peek().is_some_and(|f| c.0 < f.0) {
+                    candidates.push(Reverse(c));
+                    found.push(c);
+                    if found.len() > ef {
+                        found.pop();
+                    }
+                }
+            }
+        }
+        found.into_sorted_vec()
+    }
+
+    /// Greedy descent from the entry point to layer 1; the entry points for layer 0.
+    fn descend(&self, query: &[f32], to_layer: usize) -> Option<Vec<usize>> {
+        let entry = self.entry?;
+        let mut entries = vec![entry];
+        for layer in (to_layer + 1..=self.top_level(entry)).rev() {
+            entries = vec![self.search_layer(query, &entries, 1, layer)[0].1];
+        }
+        Some(entries)
+    }
+
+    /// Adds a node and returns the nodes whose links changed (including the new one).
+    fn insert(
+        &mut self,
+        key: String,
+        vector: Vec<f32>,
+        level: usize,
+        params: HnswParams,
+    ) -> Vec<usize> {
+        let id = self.keys.len();
+        self.ids.insert(key.clone(), id);
+        self.keys.push(key);
+        self.vectors.push(vector);
+        self.links.push(vec![Vec::new(); level + 1]);
+
+        let Some(entry) = self.entry else {
+            self.entry = Some(id);
+            return vec![id];
+        };
+        let top = self.top_level(entry);
+        let query = self.vectors[id].clone();
+        let mut entries = self
+            .descend(&query, level)
+            .expect("graph has an entry point");
+        let mut changed = vec![id];
+        for layer in (0..=level.min(top)).rev() {
+            let found = self.search_layer(&query, &entries, params.ef_construction, layer);
+            let max_links = if layer == 0 { 2 * params.m } else { params.m };
+            let neighbours: Vec<usize> = found.iter().take(params.m).map(|c| c.1).collect();
+            for &n in &neighbours {
+                self.links[n][layer].push(id);
+                if self.links[n][layer].len() > max_links {
+                    self.prune(n, layer, max_links);
+                }
+                changed.push(n);
+            }
+            self.links[id][layer] = neighbours;
+            entries = found.iter().map(|c| c.1).collect();
+        }
+        if level > top {
+            self.entry = Some(id);
+        }
+        changed
+    }
+
+    /// Unlinks `id` and returns the nodes whose links changed. Every node that linked to it
+    /// gets its neighbours on that layer instead (pruned back to the usual limit). Finding
+    /// those nodes scans the whole graph.
+    fn remove(&mut self, id: usize, params: HnswParams) -> Vec<usize> {
+        let removed = std::mem::take(&mut self.links[id]);
+        let mut changed = Vec::new();
+        for (layer, neighbours) in removed.iter().enumerate() {
+            let max_links = if layer == 0 { 2 * params.m } else { params.m };
+            for node in 0..self.links.len() {
+                let Some(links) = self.links[node].get_mut(layer) else {
+                    continue;
+                };
+                let Some(pos) = links.iter().position(|&n| n == id) else {
+                    continue;
+                };
+                links.swap_remove(pos);
+                for &n in neighbours {
+                    if n != node && !links.contains(&n) {
+                        links.push(n);
+                    }
+                }
+                if links.len() > max_links {
+                    self.prune(node, layer, max_links);
+                }
+                changed.push(node);
+            }
+        }
+        self.vectors[id] = Vec::new();
+        self.deleted.insert(id);
+        if self.entry == Some(id) {
+            self.entry = (0..self.links.len())
+                .filter(|n| !self.deleted.contains(n))
+                .max_by_key(|&n| self.top_level(n));
+        }
+        changed.sort_unstable();
+        changed.dedup();
+        changed
+    }
+
+    /// Keeps the `max_links` closest neighbours of `node` on `layer`.
+    fn prune(&mut self, node: usize, layer: usize, max_links: usize) {
+        let vector = &self.vectors[node];
+        let mut scored: Vec<Candidate> = self.links[node][layer]
+            .iter()
+            .map(|&n| Candidate(self.distance(vector, n), n))
+            .collect();
+        scored.sort();
+        self.links[node][layer] = scored.into_iter().take(max_links).map(|c| c.1).collect();
+    }
+
+    /// Up to `ef` nearest nodes to `query`, closest first.
+    fn search(&self, query: &[f32], ef: usize) -> Vec<Candidate> {
+        match self.descend(query, 0) {
+            Some(entries) => self.search_layer(query, &entries, ef.max(1), 0),
+            None => Vec::new(),
+        }
+    }
+
+    fn record(&self, node: usize) -> NodeRecord {
+        NodeRecord {
+            links: self.links[node]
+                .iter()
+                .map(|layer| layer.iter().map(|&n| self.keys[n].clone()).collect())
+                .collect(),
+        }
+    }
+}
+
+/// Fact vectors and their HNSW graph, persisted to sled. Clones share the graph.
+#[derive(Clone)]
+pub struct VectorIndex {
+    embeddings: sled::Tree,
+    graph_tree: sled::Tree,
+    meta: sled::Tree,
+    graph: Arc<RwLock<Graph>>,
+    params: HnswParams,
+}
+
+impl std::fmt::Debug for VectorIndex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VectorIndex")
+            .field("len", &self.len())
+            .field("params", &self.params)
+            .finish()
+    }
+}
+
+impl VectorIndex {
+    /// Opens the trees and loads the graph. Vectors without a stored node (e.g. after a
+    /// crash mid-insert) are linked in again.
+    pub fn open(db: &sled::Db) -> Result<Self, sled::Error> {
+        let index = Self {
+            embeddings: db.open_tree(FACT_EMBEDDINGS_TREE)?,
+            graph_tree: db.open_tree(HNSW_GRAPH_TREE)?,
+            meta: db.open_tree(VECTOR_META_TREE)?,
+            graph: Arc::new(RwLock::new(Graph::default())),
+            params: HnswParams::default(),
+        };
+        index.load()?;
+        Ok(index)
+    }
+
+    fn load(&self) -> Result<(), sled::Error> {
+        let mut guard = self.graph.write().expect("vector index lock poisoned");
+        let graph = &mut *guard;
+        let mut records = Vec::new();
+        let mut unlinked = Vec::new();
+        for entry in self.embeddings.iter() {
+            let (k, v) = entry?;
+            let key = String::from_utf8_lossy(&k).into_owned();
+            let vector = decode_vector(&v);
+            match self.graph_tree.get(&k)? {
+                Some(record) => {
+                    let Ok(record) = serde_json::from_slice::<NodeRecord>(&record) else {
+                        unlinked.push((key, vector));
+                        continue;
+                    };
+                    graph.ids.insert(key.clone(), graph.keys.len());
+                    graph.keys.push(key);
+                    graph.vectors.push(vector);
+                    records.push(record);
+                }
+                None => unlinked.push((key, vector)),
+            }
+        }
+        graph.links = records
+            .into_iter()
+            .map(|record| {
+                record
+                    .links
+                    .into_iter()
+                    .map(|layer| {
+                        layer
+                            .iter()
+                            .filter_map(|k| graph.ids.get(k).copied())
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect();
+        let entry = self
+            .meta
+            .get(ENTRY_KEY)?
+            .and_then(|k| graph.ids.get(String::from_utf8_lossy(&k).as_ref()).copied())
+            .or_else(|| (0..graph.keys.len()).max_by_key(|&n| graph.top_level(n)));
+        graph.entry = entry;
+        drop(guard);
+
+        for (key, vector) in unlinked {
+            self.link(&key, vector)?;
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        let graph = self.graph.read().expect("vector index lock poisoned");
+        graph.keys.len() - graph.deleted.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.graph
+            .read()
+            .expect("vector index lock poisoned")
+            .ids
+            .contains_key(key)
+    }
+
+    /// Id of the embedder that produced the stored vectors, if recorded.
+    pub fn embedder_id(&self) -> Option<String> {
+        let v = self.meta.get(EMBEDDER_KEY).ok()??;
+        Some(String::from_utf8_lossy(&v).into_owned())
+    }
+
+    pub fn set_embedder_id(&self, id: &str) -> Result<(), sled::Error> {
+        self.meta.insert(EMBEDDER_KEY, id.as_bytes())?;
+        self.meta.flush()?;
+        Ok(())
+    }
+
+    /// Stores the vector for fact `key` and links it into the graph. Existing keys are
+    /// left unchanged.
+    pub fn insert(&self, key: &str, mut vector: Vec<f32>) -> Result<(), sled::Error> {
+        if self.contains(key) {
+            return Ok(());
+        }
+        normalize(&mut vector);
+        self.embeddings
+            .insert(key.as_bytes(), encode_vector(&vector))?;
+        self.link(key, vector)
+    }
+
+    fn link(&self, key: &str, vector: Vec<f32>) -> Result<(), sled::Error> {
+        let mut graph = self.graph.write().expect("vector index lock poisoned");
+        if graph.ids.contains_key(key) {
+            return Ok(());
+        }
+        let changed = graph.insert(
+            key.to_string(),
+            vector,
+            level_for(key, self.params.m),
+            self.params,
+        );
+        let mut batch = sled::Batch::default();
+        for node in changed {
+            let record =
+                serde_json::to_vec(&graph.record(node)).expect("failed to serialize NodeRecord");
+            batch.insert(graph.keys[node].as_bytes(), record);
+        }
+        self.graph_tree.apply_batch(batch)?;
+        if let Some(entry) = graph.entry {
+            self.meta.insert(ENTRY_KEY, graph.keys[entry].as_bytes())?;
+        }
+        self.embeddings.flush()?;
+        self.graph_tree.flush()?;
+        self.meta.flush()?;
+        Ok(())
+    }
+
+    /// Up to `ef` fact keys nearest to `query`, with cosine similarity, most similar first.
+    pub fn search(&self, query: &[f32], ef: usize) -> Vec<(String, f32)> {
+        let mut query = query.to_vec();
+        normalize(&mut query);
+        let graph = self.graph.read().expect("vector index lock poisoned");
+        graph
+            .search(&query, ef)
+            .into_iter()
+            .map(|c| (graph.keys[c.1].clone(), 1.0 - c.0))
+            .collect()
+    }
+
+    /// Drops the vector for fact `key` and repairs the links around it; returns the bytes of
+    /// its vector and node record, 0 if there was none.
+    pub fn remove(&self, key: &str) -> Result<u64, sled::Error> {
+        let mut graph = self.graph.write().expect("vector index lock poisoned");
+        let Some(id) = graph.ids.remove(key) else {
+            return Ok(0);
+        };
+        let changed = graph.remove(id, self.params);
+        let mut freed = 0;
+        for tree in [&self.embeddings, &self.graph_tree] {
+            if let Some(value) = tree.get(key.as_bytes())? {
+                freed += (key.len() + value.len()) as u64;
+            }
+        }
+        self.embeddings.remove(key.as_bytes())?;
+        let mut batch = sled::Batch::default();
+        for node in changed {
+            let record =
+                serde_json::to_vec(&graph.record(node)).expect("failed to serialize NodeRecord");
+            batch.insert(graph.keys[node].as_bytes(), record);
+        }
+        batch.remove(key.as_bytes());
+        self.graph_tree.apply_batch(batch)?;
+        match graph.entry {
+            Some(entry) => self.meta.insert(ENTRY_KEY, graph.keys[entry].as_bytes())?,
+            None => self.meta.remove(ENTRY_KEY)?,
+        };
+        self.embeddings.flush()?;
+        self.graph_tree.flush()?;
+        self.meta.flush()?;
+        Ok(freed)
+    }
+
+    /// Cosine similarity between `query` and the stored vector for `key`.
+    pub fn similarity(&self, key: &str, query: &[f32]) -> Option<f32> {
+        let mut query = query.to_vec();
+        normalize(&mut query);
+        let graph = self.graph.read().expect("vector index lock poisoned");
+        let id = *graph.ids.get(key)?;
+        Some(dot(&query, &graph.vectors[id]))
+    }
+
+    /// Drops every vector and link, e.g. before reindexing with another embedder.
+    pub fn clear(&self) -> Result<(), sled::Error> {
+        let mut graph = self.graph.write().expect("vector index lock poisoned");
+        self.embeddings.clear()?;
+        self.graph_tree.clear()?;
+        self.meta.remove(ENTRY_KEY)?;
+        *graph = Graph::default();
+        Ok(())
+    }
+}
+
+/// Level of a new node, drawn from the usual exponential distribution but seeded by its key
+/// so rebuilding the graph gives the same shape.
+fn level_for(key: &str, m: usize) -> usize {
+    let h = fnv1a(key.as_bytes());
+    let uniform = ((h >> 11) as f64 + 1.0) / (1u64 << 53) as f64;
+    let ml = 1.0 / (m.max(2) as f64).ln();
+    ((-uniform.ln() * ml) as usize).min(MAX_LEVEL)
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |h, b| {
+        (h ^ u64::from(*b)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn normalize(vector: &mut [f32]) {
+    let norm = dot(vector, vector).sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+}
+
+fn encode_vector(vector: &[f32]) -> Vec<u8> {
+    vector.iter().flat_map(|x| x.to_le_bytes()).collect()
+}
+
+fn decode_vector(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hnsw_finds_the_same_neighbours_as_brute_force_and_survives_reopen() {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("failed to open temporary sled db");
+        let embedder = HashingEmbedder::new(64);
+        let topics = [
+            "rust",
+            "python",
+            "senolytics",
+            "calendar",
+            "firewall",
+            "robot",
+        ];
+        let texts: Vec<String> = (0..120)
+            .map(|i| {
+                let a = topics[i % topics.len()];
+                let b = topics[(i / topics.len()) % topics.len()];
+                format!("{a} notes {i} about {b}")
+            })
+            .collect();
+
+        let index = VectorIndex::open(&db).expect("open");
+        for (i, text) in texts.iter().enumerate() {
+            index
+                .insert(&format!("{i:020}_0"), embedder.embed(text))
+                .expect("insert");
+        }
+        assert_eq!(index.len(), texts.len());
+
+        let query = embedder.embed("senolytics notes about robot");
+        let mut exact: Vec<(String, f32)> = texts
+            .iter()
+            .enumerate()
+            .map(|(i, t)| (format!("{i:020}_0"), dot(&query, &embedder.embed(t))))
+            .collect();
+        exact.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        let found = index.search(&query, 40);
+        assert_eq!(found[0].0, exact[0].0);
+        let top5: HashSet<&String> = found.iter().take(5).map(|(k, _)| k).collect();
+        let hits = exact
+            .iter()
+            .take(5)
+            .filter(|(k, _)| top5.contains(k))
+            .count();
+        assert!(hits >= 4, "recall@5 = {hits}/5");
+
+        drop(index);
+        let reopened = VectorIndex::open(&db).expect("reopen");
+        assert_eq!(reopened.len(), texts.len());
+        assert_eq!(reopened.search(&query, 40)[0].0, exact[0].0);
+
+        assert!(reopened.remove(&exact[0].0).expect("remove") > 0);
+        assert_eq!(reopened.remove(&exact[0].0), Ok(0));
+        assert_eq!(reopened.len(), texts.len() - 1);
+        assert_eq!(reopened.search(&query, 40)[0].0, exact[1].0);
+        drop(reopened);
+        let reopened = VectorIndex::open(&db).expect("reopen");
+        assert_eq!(reopened.len(), texts.len() - 1);
+        assert_eq!(reopened.search(&query, 40)[0].0, exact[1].0);
+
+        // Removing most nodes (the entry point among them) leaves no links to removed nodes
+        // and keeps the survivors reachable.
+        let entry = String::from_utf8_lossy(&reopened.meta.get(ENTRY_KEY).unwrap().unwrap())
+            .into_owned();
+        reopened.remove(&entry).expect("remove entry");
+        for (key, _) in exact.iter().skip(1).filter(|(k, _)| *k != entry).skip(10) {
+            reopened.remove(key).expect("remove");
+        }
+        let survivors: Vec<&String> = exact
+            .iter()
+            .skip(1)
+            .map(|(k, _)| k)
+            .filter(|k| reopened.contains(k))
+            .collect();
+        assert_eq!(reopened.len(), survivors.len());
+        {
+            let graph = reopened.graph.read().unwrap();
+            for (node, layers) in graph.links.iter().enumerate() {
+                assert!(graph.deleted.contains(&node) || !layers[0].is_empty());
+                assert!(layers.iter().flatten().all(|n| !graph.deleted.contains(n)));
+            }
+        }
+        let found = reopened.search(&query, survivors.len());
+        assert_eq!(found.len(), survivors.len());
+        assert_eq!(&found[0].0, survivors[0]);
+    }
+}
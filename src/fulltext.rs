@@ -0,0 +1,533 @@
+//! Full-text inverted index over fact content.
+//!
+//! Fact content is split into lowercase tokens (letters, digits and `_`, so `CYBER_ALERT` is
+//! one token). Every token's positions are stored in the `fulltext_postings` tree under
+//! `<token>\0<fact key>`, and each fact's token count under its key in `fulltext_docs`. The
+//! index is updated as facts are written, so lookups never scan the `facts` tree.
+//!
+//! [`FullTextQuery`] supports terms, `"quoted phrases"`, `AND` (also implied between terms),
+//! `OR`, `NOT` / `-term` and parentheses. Matches are ranked with BM25 over the query's
+//! non-negated terms.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+/// KB tree mapping `<token>\0<fact key>` to the token's positions (little-endian `u32`s).
+pub const FULLTEXT_POSTINGS_TREE: &str = "fulltext_postings";
+
+/// KB tree mapping fact keys to their token count (little-endian `u32`).
+pub const FULLTEXT_DOCS_TREE: &str = "fulltext_docs";
+
+/// BM25 term-frequency saturation.
+const BM25_K1: f32 = 1.2;
+
+/// BM25 length normalization.
+const BM25_B: f32 = 0.75;
+
+/// Lowercase tokens of `text`: runs of letters, digits and `_`.
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|t| !t.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Token positions of one document.
+type DocTerms = HashMap<String, Vec<u32>>;
+
+fn doc_terms(text: &str) -> DocTerms {
+    let mut terms = DocTerms::new();
+    for (pos, token) in tokenize(text).into_iter().enumerate() {
+        terms.entry(token).or_default().push(pos as u32);
+    }
+    terms
+}
+
+/// A parsed full-text query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FullTextQuery {
+    Term(String),
+    /// Consecutive tokens.
+    Phrase(Vec<String>),
+    And(Vec<FullTextQuery>),
+    Or(Vec<FullTextQuery>),
+    Not(Box<FullTextQuery>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Lexeme {
+    Word(String),
+    Quoted(String),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+impl FullTextQuery {
+    /// Parses `rapamycin AND (mice OR rats) -"press release"`. `NOT` binds tightest, then
+    /// `AND`, then `OR`.
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let lexemes = lex(input)?;
+        let mut pos = 0;
+        let query = parse_or(&lexemes, &mut pos)?;
+        if pos < lexemes.len() {
+            return Err(format!("Unexpected {:?} in query '{input}'", lexemes[pos]));
+        }
+        Ok(query)
+    }
+
+    /// Whether `text` matches, without the index (e.g. for a single fact).
+    pub fn matches_text(&self, text: &str) -> bool {
+        self.matches(&doc_terms(text))
+    }
+
+    fn matches(&self, doc: &DocTerms) -> bool {
+        match self {
+            FullTextQuery::Term(t) => doc.contains_key(t),
+            FullTextQuery::Phrase(ts) => phrase_at(ts, |t| doc.get(t).cloned().unwrap_or_default()),
+            FullTextQuery::And(qs) => qs.iter().all(|q| q.matches(doc)),
+            FullTextQuery::Or(qs) => qs.iter().any(|q| q.matches(doc)),
+            FullTextQuery::Not(q) => !q.matches(doc),
+        }
+    }
+
+    /// Terms that contribute to ranking: everything not under a `NOT`.
+    fn positive_terms(&self, out: &mut Vec<String>) {
+        match self {
+            FullTextQuery::Term(t) => out.push(t.clone()),
+            FullTextQuery::Phrase(ts) => out.extend(ts.iter().cloned()),
+            FullTextQuery::And(qs) | FullTextQuery::Or(qs) => {
+                qs.iter().for_each(|q| q.positive_terms(out))
+            }
+            FullTextQuery::Not(_) => {}
+        }
+    }
+}
+
+/// Whether the tokens occur consecutively, given each token's positions.
+fn phrase_at(tokens: &[String], positions: impl Fn(&str) -> Vec<u32>) -> bool {
+    let Some((first, rest)) = tokens.split_first() else {
+        return false;
+    };
+    let rest: Vec<HashSet<u32>> = rest
+        .iter()
+        .map(|t| positions(t).into_iter().collect())
+        .collect();
+    positions(first).into_iter().any(|start| {
+        rest.iter()
+            .enumerate()
+            .all(|(i, p)| p.contains(&(start + i as u32 + 1)))
+    })
+}
+
+fn lex(input: &str) -> Result<Vec<Lexeme>, String> {
+    let mut out = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                out.push(Lexeme::Open);
+            }
+            ')' => {
+                chars.next();
+                out.push(Lexeme::Close);
+            }
+            '"' => {
+                chars.next();
+                let phrase: String = chars.by_ref().take_while(|&c| c != '"').collect();
+                out.push(Lexeme::Quoted(phrase));
+            }
+            '-' => {
+                chars.next();
+                out.push(Lexeme::Not);
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' || c == '"' {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                out.push(match word.as_str() {
+                    "AND" => Lexeme::And,
+                    "OR" => Lexeme::Or,
+                    "NOT" => Lexeme::Not,
+                    _ => Lexeme::Word(word),
+                });
+            }
+        }
+    }
+    if out.is_empty() {
+        return Err("Empty full-text query".to_string());
+    }
+    Ok(out)
+}
+
+fn parse_or(lexemes: &[Lexeme], pos: &mut usize) -> Result<FullTextQuery, String> {
+    let mut parts = vec![parse_and(lexemes, pos)?];
+    while lexemes.get(*pos) == Some(&Lexeme::Or) {
+        *pos += 1;
+        parts.push(parse_and(lexemes, pos)?);
+    }
+    Ok(if parts.len() == 1 {
+        parts.remove(0)
+    } else {
+        FullTextQuery::Or(parts)
+    })
+}
+
+fn parse_and(lexemes: &[Lexeme], pos: &mut usize) -> Result<FullTextQuery, String> {
+    let mut parts = vec![parse_unary(lexemes, pos)?];
+    loop {
+        match lexemes.get(*pos) {
+            Some(Lexeme::And) => *pos += 1,
+            Some(Lexeme::Word(_) | Lexeme::Quoted(_) | Lexeme::Not | Lexeme::Open) => {}
+            _ => break,
+        }
+        parts.push(parse_unary(lexemes, pos)?);
+    }
+    Ok(if parts.len() == 1 {
+        parts.remove(0)
+    } else {
+        FullTextQuery::And(parts)
+    })
+}
+
+fn parse_unary(lexemes: &[Lexeme], pos: &mut usize) -> Result<FullTextQuery, String> {
+    let lexeme = lexemes
+        .get(*pos)
+        .ok_or_else(|| "Full-text query ends early".to_string())?;
+    *pos += 1;
+    match lexeme {
+        Lexeme::Not => Ok(FullTextQuery::Not(Box::new(parse_unary(lexemes, pos)?))),
+        Lexeme::Open => {
+            let inner = parse_or(lexemes, pos)?;
+            if lexemes.get(*pos) != Some(&Lexeme::Close) {
+                return Err("Unbalanced parentheses in full-text query".to_string());
+            }
+            *pos += 1;
+            Ok(inner)
+        }
+        Lexeme::Word(text) | Lexeme::Quoted(text) => {
+            let mut tokens = tokenize(text);
+            match tokens.len() {
+                0 => Err(format!("'{text}' has no searchable characters")),
+                1 => Ok(FullTextQuery::Term(tokens.remove(0))),
+                _ => Ok(FullTextQuery::Phrase(tokens)),
+            }
+        }
+        other => Err(format!("Unexpected {other:?} in full-text query")),
+    }
+}
+
+/// Corpus size, for BM25.
+#[derive(Debug, Default)]
+struct Stats {
+    docs: u64,
+    total_len: u64,
+}
+
+/// Inverted index over fact content, persisted to sled. Clones share statistics.
+#[derive(Debug, Clone)]
+pub struct FullTextIndex {
+    postings: sled::Tree,
+    docs: sled::Tree,
+    stats: Arc<Mutex<Stats>>,
+}
+
+impl FullTextIndex {
+    pub fn open(db: &sled::Db) -> Result<Self, sled::Error> {
+        let docs = db.open_tree(FULLTEXT_DOCS_TREE)?;
+        let mut stats = Stats::default();
+        for entry in docs.iter() {
+            let (_, len) = entry?;
+            stats.docs += 1;
+            stats.total_len += u64::from(decode_u32s(&len).first().copied().unwrap_or(0));
+        }
+        Ok(Self {
+            postings: db.open_tree(FULLTEXT_POSTINGS_TREE)?,
+            docs,
+            stats: Arc::new(Mutex::new(stats)),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.stats.lock().expect("fulltext stats poisoned").docs as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.docs.contains_key(key.as_bytes()).unwrap_or(false)
+    }
+
+    /// Indexes `text` under fact `key`. Already indexed keys are left unchanged.
+    pub fn insert(&self, key: &str, text: &str) -> Result<(), sled::Error> {
+        if self.contains(key) {
+            return Ok(());
+        }
+        let terms = doc_terms(text);
+        let len: u32 = terms.values().map(|p| p.len() as u32).sum();
+        let mut batch = sled::Batch::default();
+        for (term, positions) in &terms {
+            batch.insert(posting_key(term, key), encode_u32s(positions));
+        }
+        self.postings.apply_batch(batch)?;
+        self.docs.insert(key.as_bytes(), encode_u32s(&[len]))?;
+        self.postings.flush()?;
+        self.docs.flush()?;
+
+        let mut stats = self.stats.lock().expect("fulltext stats poisoned");
+        stats.docs += 1;
+        stats.total_len += u64::from(len);
+        Ok(())
+    }
+
+    /// Drops fact `key`, whose content was `text`; returns the bytes of the postings and
+    /// document row removed, 0 if it was not indexed.
+    pub fn remove(&self, key: &str, text: &str) -> Result<u64, sled::Error> {
+        let Some(len) = self.docs.remove(key.as_bytes())? else {
+            return Ok(0);
+        };
+        let mut freed = (key.len() + len.len()) as u64;
+        let mut batch = sled::Batch::default();
+        for (term, positions) in doc_terms(text) {
+            let posting = posting_key(&term, key);
+            freed += (posting.len() + positions.len() * std::mem::size_of::<u32>()) as u64;
+            batch.remove(posting);
+        }
+        self.postings.apply_batch(batch)?;
+        self.postings.flush()?;
+        self.docs.flush()?;
+
+        let mut stats = self.stats.lock().expect("fulltext stats poisoned");
+        stats.docs = stats.docs.saturating_sub(1);
+        stats.total_len = stats
+            .total_len
+            .saturating_sub(u64::from(decode_u32s(&len).first().copied().unwrap_or(0)));
+        Ok(freed)
+    }
+
+    /// Keys of the facts matching `query`, in key order.
+    pub fn matching_keys(&self, query: &FullTextQuery) -> Vec<String> {
+        let mut keys: Vec<String> = self.eval(query).into_iter().collect();
+        keys.sort();
+        keys
+    }
+
+    /// Every matching fact key with its BM25 score, best first (newer first on ties).
+    pub fn search(&self, query: &FullTextQuery) -> Vec<(String, f32)> {
+        let matched = self.eval(query);
+        let mut terms = Vec::new();
+        query.positive_terms(&mut terms);
+        terms.sort();
+        terms.dedup();
+
+        let (n, avg_len) = {
+            let stats = self.stats.lock().expect("fulltext stats poisoned");
+            let n = stats.docs.max(1) as f32;
+            (n, (stats.total_len as f32 / n).max(1.0))
+        };
+        let idf: Vec<f32> = terms
+            .iter()
+            .map(|t| {
+                let df = self.docs_with(t).len() as f32;
+                ((n - df + 0.5) / (df + 0.5) + 1.0).ln()
+            })
+            .collect();
+
+        let mut scored: Vec<(String, f32)> = matched
+            .into_iter()
+            .map(|key| {
+                let len = self
+                    .docs
+                    .get(key.as_bytes())
+                    .ok()
+                    .flatten()
+                    .and_then(|v| decode_u32s(&v).first().copied())
+                    .unwrap_or(0) as f32;
+                let score = terms
+                    .iter()
+                    .zip(&idf)
+                    .map(|(t, idf)| {
+                        let tf = self.positions(t, &key).len() as f32;
+                        idf * tf * (BM25_K1 + 1.0)
+                            / (tf + BM25_K1 * (1.0 - BM25_B + BM25_B * len / avg_len))
+                    })
+                    .sum();
+                (key, score)
+            })
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| b.0.cmp(&a.0)));
+        scored
+    }
+
+    fn eval(&self, query: &FullTextQuery) -> HashSet<String> {
+        match query {
+            FullTextQuery::Term(t) => self.docs_with(t),
+            FullTextQuery::Phrase(ts) => {
+                let Some(first) = ts.first() else {
+                    return HashSet::new();
+                };
+                let mut candidates = self.docs_with(first);
+                for t in &ts[1..] {
+                    let docs = self.docs_with(t);
+                    candidates.retain(|k| docs.contains(k));
+                }
+                candidates.retain(|key| phrase_at(ts, |t| self.positions(t, key)));
+                candidates
+            }
+            FullTextQuery::And(qs) => {
+                let (negated, positive): (Vec<_>, Vec<_>) =
+                    qs.iter().partition(|q| matches!(q, FullTextQuery::Not(_)));
+                let mut result = match positive.split_first() {
+                    Some((first, rest)) => {
+                        let mut result = self.eval(first);
+                        for q in rest {
+                            let docs = self.eval(q);
+                            result.retain(|k| docs.contains(k));
+                        }
+                        result
+                    }
+                    None => self.all_docs(),
+                };
+                for q in negated {
+                    if let FullTextQuery::Not(inner) = q {
+                        let excluded = self.eval(inner);
+                        result.retain(|k| !excluded.contains(k));
+                    }
+                }
+                result
+            }
+            FullTextQuery::Or(qs) => qs.iter().flat_map(|q| self.eval(q)).collect(),
+            FullTextQuery::Not(q) => {
+                let excluded = self.eval(q);
+                let mut all = self.all_docs();
+                all.retain(|k| !excluded.contains(k));
+                all
+            }
+        }
+    }
+
+    fn docs_with(&self, term: &str) -> HashSet<String> {
+        let prefix = posting_key(term, "");
+        self.postings
+            .scan_prefix(&prefix)
+            .keys()
+            .filter_map(|k| k.ok())
+            .map(|k| String::from_utf8_lossy(&k[prefix.len()..]).into_owned())
+            .collect()
+    }
+
+    fn positions(&self, term: &str, key: &str) -> Vec<u32> {
+        self.postings
+            .get(posting_key(term, key))
+            .ok()
+            .flatten()
+            .map(|v| decode_u32s(&v))
+            .unwrap_or_default()
+    }
+
+    fn all_docs(&self) -> HashSet<String> {
+        self.docs
+            .iter()
+            .keys()
+            .filter_map(|k| k.ok())
+            .map(|k| String::from_utf8_lossy(&k).into_owned())
+            .collect()
+    }
+}
+
+fn posting_key(term: &str, key: &str) -> Vec<u8> {
+    let mut out = Vec::with_capacity(term.len() + 1 + key.len());
+    out.extend_from_slice(term.as_bytes());
+    out.push(0);
+    out.extend_from_slice(key.as_bytes());
+    out
+}
+
+fn encode_u32s(values: &[u32]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+fn decode_u32s(bytes: &[u8]) -> Vec<u32> {
+    bytes
+        .chunks_exact(4)
+        .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn boolean_and_phrase_queries_rank_with_bm25() {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("failed to open temporary sled db");
+        let index = FullTextIndex::open(&db).expect("open");
+        let docs = [
+            ("k1", "CYBER_ALERT: phishing campaign against finance"),
+            (
+                "k2",
+                "Failure: SearchAgent timeout while searching rapamycin mice",
+            ),
+            (
+                "k3",
+                "rapamycin extends lifespan in mice; rapamycin dosing matters",
+            ),
+            ("k4", "mice and rapamycin: no effect observed"),
+        ];
+        for (key, text) in docs {
+            index.insert(key, text).expect("insert");
+        }
+        index.insert("k1", "ignored").expect("reinsert");
+        assert_eq!(index.len(), 4);
+
+        let q = |s: &str| FullTextQuery::parse(s).expect("parse");
+        assert_eq!(index.matching_keys(&q("cyber_alert")), ["k1"]);
+        assert_eq!(
+            index.matching_keys(&q("rapamycin mice")),
+            ["k2", "k3", "k4"]
+        );
+        assert_eq!(index.matching_keys(&q("\"rapamycin mice\"")), ["k2"]);
+        assert_eq!(
+            index.matching_keys(&q("rapamycin AND NOT (timeout OR \"no effect\")")),
+            ["k3"]
+        );
+        assert_eq!(
+            index.matching_keys(&q("phishing OR lifespan")),
+            ["k1", "k3"]
+        );
+        assert_eq!(index.matching_keys(&q("-rapamycin")), ["k1"]);
+
+        // k3 mentions rapamycin twice.
+        let ranked = index.search(&q("rapamycin"));
+        assert_eq!(ranked[0].0, "k3");
+        assert!(ranked[0].1 > ranked[1].1);
+
+        assert!(q("anti-aging").matches_text("Anti-aging compounds"));
+        assert!(!q("anti-aging").matches_text("aging is anti"));
+        assert!(FullTextQuery::parse("(rapamycin").is_err());
+        assert!(FullTextQuery::parse("  ").is_err());
+
+        let reopened = FullTextIndex::open(&db).expect("reopen");
+        assert_eq!(reopened.len(), 4);
+        assert!(reopened.remove("k3", docs[2].1).expect("remove") > 0);
+        assert_eq!(reopened.remove("k3", docs[2].1), Ok(0));
+        assert_eq!(reopened.matching_keys(&q("lifespan")), Vec::<String>::new());
+        assert_eq!(reopened.len(), 3);
+    }
+}
@@ -0,0 +1,586 @@
+//! Knowledge graph of the entities facts mention and the relations between them.
+//!
+//! Nodes are [`Entity`]s (a kind and a name, e.g. `compound:rapamycin`) with JSON properties.
+//! Edges are typed, directed relations between two nodes, e.g. `compound:rapamycin
+//! -extends_lifespan_of-> organism:mice`. Both record the keys of the facts they were
+//! extracted from, so a relation can always be traced back to its evidence.
+//!
+//! Edges are stored under `<from>\0<relation>\0<to>` in `graph_edges` and mirrored in
+//! `graph_edges_in`, so neighbours in either direction are a prefix scan away.
+
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+/// KB tree holding nodes, keyed by node id.
+pub const GRAPH_NODES_TREE: &str = "graph_nodes";
+
+/// KB tree holding edges, keyed by `<from>\0<relation>\0<to>`.
+pub const GRAPH_EDGES_TREE: &str = "graph_edges";
+
+/// KB tree indexing edges by target, keyed by `<to>\0<relation>\0<from>`.
+pub const GRAPH_INBOUND_TREE: &str = "graph_edges_in";
+
+/// KB tree linking facts to the nodes extracted from them, keyed by `<fact key>\0<node id>`.
+pub const GRAPH_SOURCES_TREE: &str = "graph_sources";
+
+/// Something a fact mentions: a compound, a host, a sensor...
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Entity {
+    pub kind: String,
+    pub name: String,
+}
+
+impl Entity {
+    pub fn new(kind: impl Into<String>, name: impl Into<String>) -> Self {
+        Self {
+            kind: kind.into(),
+            name: name.into(),
+        }
+    }
+
+    /// `<kind>:<name>`, lowercased, so `Compound:Rapamycin` and `compound:rapamycin` are
+    /// the same node.
+    pub fn id(&self) -> String {
+        format!(
+            "{}:{}",
+            self.kind.trim().to_lowercase(),
+            self.name.trim().to_lowercase()
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GraphNode {
+    pub id: String,
+    pub kind: String,
+    /// The name as first written.
+    pub name: String,
+    pub properties: BTreeMap<String, serde_json::Value>,
+    /// Keys of the facts this node was extracted from, oldest first.
+    pub sources: Vec<String>,
+    pub updated_at: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GraphEdge {
+    pub from: String,
+    pub relation: String,
+    pub to: String,
+    pub properties: BTreeMap<String, serde_json::Value>,
+    /// Keys of the facts this relation was extracted from, oldest first.
+    pub sources: Vec<String>,
+    pub updated_at: u64,
+}
+
+impl GraphEdge {
+    /// The node at the other end from `id`.
+    pub fn other(&self, id: &str) -> &str {
+        if self.from == id {
+            &self.to
+        } else {
+            &self.from
+        }
+    }
+
+    /// `from -relation-> to`, as shown to planners.
+    pub fn describe(&self) -> String {
+        format!("{} -{}-> {}", self.from, self.relation, self.to)
+    }
+}
+
+/// A subject-relation-object statement to upsert.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Triple {
+    pub subject: Entity,
+    pub relation: String,
+    pub object: Entity,
+    /// Merged into the edge's properties; later values win.
+    #[serde(default)]
+    pub properties: BTreeMap<String, serde_json::Value>,
+}
+
+impl Triple {
+    pub fn new(subject: Entity, relation: impl Into<String>, object: Entity) -> Self {
+        Self {
+            subject,
+            relation: relation.into(),
+            object,
+            properties: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_property(mut self, key: impl Into<String>, value: serde_json::Value) -> Self {
+        self.properties.insert(key.into(), value);
+        self
+    }
+
+    /// Rejects empty parts and NUL bytes, which would corrupt edge keys.
+    pub fn validate(&self) -> Result<(), String> {
+        let parts = [
+            &self.subject.kind,
+            &self.subject.name,
+            &self.relation,
+            &self.object.kind,
+            &self.object.name,
+        ];
+        if parts
+            .iter()
+            .any(|p| p.trim().is_empty() || p.contains('\0'))
+        {
+            return Err(format!(
+                "Invalid triple '{} -{}-> {}': parts must be non-empty and free of NUL bytes",
+                self.subject.id(),
+                self.relation,
+                self.object.id()
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Which edges of a node to follow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    Outgoing,
+    Incoming,
+    Both,
+}
+
+/// The nodes and edges within some distance of a node.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Subgraph {
+    /// Breadth-first order, starting with the centre.
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+/// Persistent graph over the `graph_*` trees.
+#[derive(Debug, Clone)]
+pub struct KnowledgeGraph {
+    nodes: sled::Tree,
+    edges: sled::Tree,
+    inbound: sled::Tree,
+    sources: sled::Tree,
+}
+
+impl KnowledgeGraph {
+    pub fn open(db: &sled::Db) -> Result<Self, sled::Error> {
+        Ok(Self {
+            nodes: db.open_tree(GRAPH_NODES_TREE)?,
+            edges: db.open_tree(GRAPH_EDGES_TREE)?,
+            inbound: db.open_tree(GRAPH_INBOUND_TREE)?,
+            sources: db.open_tree(GRAPH_SOURCES_TREE)?,
+        })
+    }
+
+    /// Creates or updates the node for `entity`, merging `properties` and linking `source`.
+    pub fn upsert_node(
+        &self,
+        entity: &Entity,
+        properties: &BTreeMap<String, serde_json::Value>,
+        source: Option<&str>,
+        now: u64,
+    ) -> Result<GraphNode, String> {
+        let id = entity.id();
+        let mut node = self.node(&id).unwrap_or_else(|| GraphNode {
+            id: id.clone(),
+            kind: entity.kind.trim().to_string(),
+            name: entity.name.trim().to_string(),
+            properties: BTreeMap::new(),
+            sources: Vec::new(),
+            updated_at: now,
+        });
+        node.properties.extend(properties.clone());
+        if let Some(source) = source {
+            if !node.sources.iter().any(|s| s == source) {
+                node.sources.push(source.to_string());
+            }
+            self.sources
+                .insert(join(&[source, &id]), &[])
+                .map_err(write_err)?;
+        }
+        node.updated_at = now;
+        let value = serde_json::to_vec(&node).expect("failed to serialize GraphNode");
+        self.nodes.insert(id.as_bytes(), value).map_err(write_err)?;
+        self.nodes.flush().map_err(write_err)?;
+        self.sources.flush().map_err(write_err)?;
+        Ok(node)
+    }
+
+    /// Creates or updates both nodes and the edge between them, linking each to `source`.
+    pub fn upsert_triple(
+        &self,
+        triple: &Triple,
+        source: Option<&str>,
+        now: u64,
+    ) -> Result<GraphEdge, String> {
+        triple.validate()?;
+        let none = BTreeMap::new();
+        let from = self.upsert_node(&triple.subject, &none, source, now)?.id;
+        let to = self.upsert_node(&triple.object, &none, source, now)?.id;
+        let relation = triple.relation.trim().to_string();
+
+        let key = join(&[&from, &relation, &to]);
+        let mut edge = self
+            .edges
+            .get(&key)
+            .ok()
+            .flatten()
+            .and_then(|v| serde_json::from_slice::<GraphEdge>(&v).ok())
+            .unwrap_or_else(|| GraphEdge {
+                from: from.clone(),
+                relation: relation.clone(),
+                to: to.clone(),
+                properties: BTreeMap::new(),
+                sources: Vec::new(),
+                updated_at: now,
+            });
+        edge.properties.extend(triple.properties.clone());
+        if let Some(source) = source {
+            if !edge.sources.iter().any(|s| s == source) {
+                edge.sources.push(source.to_string());
+            }
+        }
+        edge.updated_at = now;
+        let value = serde_json::to_vec(&edge).expect("failed to serialize GraphEdge");
+        self.edges.insert(key, value).map_err(write_err)?;
+        self.inbound
+            .insert(join(&[&to, &relation, &from]), &[])
+            .map_err(write_err)?;
+        self.edges.flush().map_err(write_err)?;
+        self.inbound.flush().map_err(write_err)?;
+        Ok(edge)
+    }
+
+    pub fn node(&self, id: &str) -> Option<GraphNode> {
+        let v = self.nodes.get(id.as_bytes()).ok()??;
+        serde_json::from_slice(&v).ok()
+    }
+
+    /// Edges touching `id` in `direction`, optionally only those of one `relation`.
+    pub fn edges(&self, id: &str, direction: Direction, relation: Option<&str>) -> Vec<GraphEdge> {
+        let prefix = match relation {
+            Some(r) => join(&[id, r, ""]),
+            None => join(&[id, ""]),
+        };
+        let mut out = Vec::new();
+        if direction != Direction::Incoming {
+            out.extend(
+                self.edges
+                    .scan_prefix(&prefix)
+                    .values()
+                    .filter_map(|v| v.ok())
+                    .filter_map(|v| serde_json::from_slice::<GraphEdge>(&v).ok()),
+            );
+        }
+        if direction != Direction::Outgoing {
+            for key in self
+                .inbound
+                .scan_prefix(&prefix)
+                .keys()
+                .filter_map(|k| k.ok())
+            {
+                let key = String::from_utf8_lossy(&key);
+                let mut parts = key.splitn(3, '\0');
+                let (Some(to), Some(relation), Some(from)) =
+                    (parts.next(), parts.next(), parts.next())
+                else {
+                    continue;
+                };
+                // A self-loop was already listed as outgoing.
+                if direction == Direction::Both && from == to {
+                    continue;
+                }
+                if let Some(edge) = self.edge(from, relation, to) {
+                    out.push(edge);
+                }
+            }
+        }
+        out
+    }
+
+    pub fn edge(&self, from: &str, relation: &str, to: &str) -> Option<GraphEdge> {
+        let v = self.edges.get(join(&[from, relation, to])).ok()??;
+        serde_json::from_slice(&v).ok()
+    }
+
+    /// Nodes and edges within `depth` hops of `id`. Empty if `id` is unknown.
+    pub fn neighborhood(
+        &self,
+        id: &str,
+        depth: usize,
+        direction: Direction,
+        relation: Option<&str>,
+    ) -> Subgraph {
+        let Some(start) = self.node(id) else {
+            return Subgraph::default();
+        };
+        let mut seen = HashSet::from([start.id.clone()]);
+        let mut seen_edges = HashSet::new();
+        let mut subgraph = Subgraph {
+            nodes: vec![start],
+            edges: Vec::new(),
+        };
+        let mut queue = VecDeque::from([(id.to_string(), 0)]);
+        while let Some((current, hops)) = queue.pop_front() {
+            if hops >= depth {
+                continue;
+            }
+            for edge in self.edges(&current, direction, relation) {
+                let next = edge.other(&current).to_string();
+                if seen.insert(next.clone()) {
+                    if let Some(node) = self.node(&next) {
+                        subgraph.nodes.push(node);
+                    }
+                    queue.push_back((next, hops + 1));
+                }
+                if seen_edges.insert((edge.from.clone(), edge.relation.clone(), edge.to.clone())) {
+                    subgraph.edges.push(edge);
+                }
+            }
+        }
+        subgraph
+    }
+
+    /// A shortest chain of edges from `from` to `to`, at most `max_depth` long.
+    pub fn path(
+        &self,
+        from: &str,
+        to: &str,
+        max_depth: usize,
+        direction: Direction,
+    ) -> Option<Vec<GraphEdge>> {
+        if from == to {
+            return self.node(from).map(|_| Vec::new());
+        }
+        let mut parents: HashMap<String, GraphEdge> = HashMap::new();
+        let mut queue = VecDeque::from([(from.to_string(), 0)]);
+        while let Some((current, hops)) = queue.pop_front() {
+            if hops >= max_depth {
+                continue;
+            }
+            for edge in self.edges(&current, direction, None) {
+                let next = edge.other(&current).to_string();
+                if next == from || parents.contains_key(&next) {
+                    continue;
+                }
+                parents.insert(next.clone(), edge);
+                if next == to {
+                    let mut path = Vec::new();
+                    let mut at = next;
+                    while let Some(edge) = parents.get(&at) {
+                        at = edge.other(&at).to_string();
+                        path.push(edge.clone());
+                    }
+                    path.reverse();
+                    return Some(path);
+                }
+                queue.push_back((next, hops + 1));
+            }
+        }
+        None
+    }
+
+    /// Nodes extracted from the fact stored under `fact_key`.
+    pub fn nodes_for_fact(&self, fact_key: &str) -> Vec<GraphNode> {
+        let prefix = join(&[fact_key, ""]);
+        self.sources
+            .scan_prefix(&prefix)
+            .keys()
+            .filter_map(|k| k.ok())
+            .filter_map(|k| self.node(&String::from_utf8_lossy(&k[prefix.len()..])))
+            .collect()
+    }
+
+    /// Edges extracted from any of `fact_keys`, in the order the facts are given.
+    pub fn edges_for_facts(&self, fact_keys: &[String]) -> Vec<GraphEdge> {
+        let mut seen = HashSet::new();
+        let mut out = Vec::new();
+        for key in fact_keys {
+            for node in self.nodes_for_fact(key) {
+                for edge in self.edges(&node.id, Direction::Outgoing, None) {
+                    if edge.sources.contains(key)
+                        && seen.insert((edge.from.clone(), edge.relation.clone(), edge.to.clone()))
+                    {
+                        out.push(edge);
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    /// Removes fact `fact_key` from every node's and edge's sources, e.g. when the fact is
+    /// deleted. Edges and nodes left with no evidence are dropped, unless they never had any
+    /// (upserted without a source). Returns how many bytes the graph trees shrank by.
+    pub fn unlink_fact(&self, fact_key: &str) -> Result<u64, String> {
+        let prefix = join(&[fact_key, ""]);
+        let ids: Vec<String> = self
+            .sources
+            .scan_prefix(&prefix)
+            .keys()
+            .filter_map(|k| k.ok())
+            .map(|k| String::from_utf8_lossy(&k[prefix.len()..]).into_owned())
+            .collect();
+        let mut freed = 0;
+        for id in &ids {
+            for mut edge in self.edges(id, Direction::Outgoing, None) {
+                let Some(pos) = edge.sources.iter().position(|s| s == fact_key) else {
+                    continue;
+                };
+                edge.sources.remove(pos);
+                let key = join(&[&edge.from, &edge.relation, &edge.to]);
+                if edge.sources.is_empty() {
+                    let inbound = join(&[&edge.to, &edge.relation, &edge.from]);
+                    let old = self.edges.remove(&key).map_err(write_err)?;
+                    freed += row_len(&key, old);
+                    let old = self.inbound.remove(&inbound).map_err(write_err)?;
+                    freed += row_len(&inbound, old);
+                } else {
+                    let value = serde_json::to_vec(&edge).expect("failed to serialize GraphEdge");
+                    let len = value.len();
+                    let old = self.edges.insert(&key, value).map_err(write_err)?;
+                    freed += row_len(&key, old).saturating_sub((key.len() + len) as u64);
+                }
+            }
+        }
+        for id in &ids {
+            let source = join(&[fact_key, id]);
+            let old = self.sources.remove(&source).map_err(write_err)?;
+            freed += row_len(&source, old);
+            let Some(mut node) = self.node(id) else {
+                continue;
+            };
+            node.sources.retain(|s| s != fact_key);
+            if node.sources.is_empty() && self.edges(id, Direction::Both, None).is_empty() {
+                let old = self.nodes.remove(id.as_bytes()).map_err(write_err)?;
+                freed += row_len(id.as_bytes(), old);
+            } else {
+                let value = serde_json::to_vec(&node).expect("failed to serialize GraphNode");
+                let len = value.len();
+                let old = self.nodes.insert(id.as_bytes(), value).map_err(write_err)?;
+                freed += row_len(id.as_bytes(), old).saturating_sub((id.len() + len) as u64);
+            }
+        }
+        for tree in [&self.nodes, &self.edges, &self.inbound, &self.sources] {
+            tree.flush().map_err(write_err)?;
+        }
+        Ok(freed)
+    }
+}
+
+/// Size of a key and its value, if there was one.
+fn row_len(key: &[u8], value: Option<sled::IVec>) -> u64 {
+    value.map_or(0, |v| (key.len() + v.len()) as u64)
+}
+
+fn join(parts: &[&str]) -> Vec<u8> {
+    parts.join("\0").into_bytes()
+}
+
+fn write_err(e: sled::Error) -> String {
+    format!("Graph write failed: {e}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn triples_build_queryable_neighbourhoods_and_paths() {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("failed to open temporary sled db");
+        let graph = KnowledgeGraph::open(&db).expect("open");
+        let rapamycin = Entity::new("Compound", "Rapamycin");
+        let mtor = Entity::new("protein", "mTOR");
+        let mice = Entity::new("organism", "mice");
+        let aging = Entity::new("process", "aging");
+
+        graph
+            .upsert_triple(
+                &Triple::new(rapamycin.clone(), "inhibits", mtor.clone()),
+                Some("f1"),
+                1,
+            )
+            .expect("upsert");
+        graph
+            .upsert_triple(
+                &Triple::new(mtor.clone(), "drives", aging.clone()),
+                Some("f2"),
+                2,
+            )
+            .expect("upsert");
+        let edge = graph
+            .upsert_triple(
+                &Triple::new(
+                    Entity::new("compound", "rapamycin"),
+                    "extends_lifespan_of",
+                    mice,
+                )
+                .with_property("effect", serde_json::json!("+14%")),
+                Some("f3"),
+                3,
+            )
+            .expect("upsert");
+        assert_eq!(
+            edge.describe(),
+            "compound:rapamycin -extends_lifespan_of-> organism:mice"
+        );
+        assert!(graph
+            .upsert_triple(&Triple::new(mtor.clone(), " ", aging.clone()), None, 4)
+            .is_err());
+
+        let node = graph.node("compound:rapamycin").expect("node");
+        assert_eq!(node.name, "Rapamycin");
+        assert_eq!(node.sources, ["f1", "f3"]);
+        assert_eq!(graph.edges(&mtor.id(), Direction::Incoming, None).len(), 1);
+        assert_eq!(graph.edges(&mtor.id(), Direction::Both, None).len(), 2);
+        assert_eq!(
+            graph
+                .edges(&rapamycin.id(), Direction::Outgoing, Some("inhibits"))
+                .len(),
+            1
+        );
+
+        let one_hop = graph.neighborhood(&mtor.id(), 1, Direction::Both, None);
+        assert_eq!(one_hop.nodes.len(), 3);
+        assert_eq!(one_hop.nodes[0].id, "protein:mtor");
+        let two_hops = graph.neighborhood(&mtor.id(), 2, Direction::Both, None);
+        assert_eq!(two_hops.nodes.len(), 4);
+        assert_eq!(two_hops.edges.len(), 3);
+
+        let path = graph
+            .path(&rapamycin.id(), &aging.id(), 3, Direction::Outgoing)
+            .expect("path");
+        assert_eq!(
+            path.iter().map(|e| e.relation.as_str()).collect::<Vec<_>>(),
+            ["inhibits", "drives"]
+        );
+        assert!(graph
+            .path(&aging.id(), &rapamycin.id(), 3, Direction::Outgoing)
+            .is_none());
+        assert!(graph
+            .path(&rapamycin.id(), &aging.id(), 1, Direction::Both)
+            .is_none());
+
+        let from_f3 = graph.edges_for_facts(&["f3".to_string()]);
+        assert_eq!(from_f3.len(), 1);
+        assert_eq!(from_f3[0].properties["effect"], "+14%");
+
+        assert!(graph.unlink_fact("f3").expect("unlink") > 0);
+        assert!(graph.node("organism:mice").is_none());
+        assert!(graph
+            .edges(&rapamycin.id(), Direction::Outgoing, None)
+            .iter()
+            .all(|e| e.to != "organism:mice"));
+        assert_eq!(graph.unlink_fact("f3"), Ok(0));
+        assert_eq!(
+            graph.node("compound:rapamycin").expect("node").sources,
+            ["f1"]
+        );
+        assert!(graph.edges_for_facts(&["f3".to_string()]).is_empty());
+    }
+}
@@ -0,0 +1,209 @@
+//! IPC connection authentication.
+//!
+//! The core issues an opaque token per [`AgentIdentity`]; a client presents it in its
+//! [`Hello`](super::IpcMessage::Hello) and the bus resolves it back to the identity. On
+//! Unix the token is also bound to the user id that issued it, and connections whose peer
+//! credentials don't match are rejected even if they hold a valid token.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::AgentIdentity;
+
+/// Credentials of the process on the other end of a local socket, where the OS exposes
+/// them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PeerCredentials {
+    pub pid: Option<u32>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+}
+
+impl PeerCredentials {
+    /// Reads peer credentials from a connected Unix socket.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn from_fd(fd: std::os::unix::io::RawFd) -> Self {
+        let mut cred = libc::ucred {
+            pid: 0,
+            uid: 0,
+            gid: 0,
+        };
+        let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+        // SAFETY: `cred` and `len` are valid for writes and sized for SO_PEERCRED.
+        let rc = unsafe {
+            libc::getsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                libc::SO_PEERCRED,
+                &mut cred as *mut libc::ucred as *mut libc::c_void,
+                &mut len,
+            )
+        };
+        if rc != 0 {
+            return Self::default();
+        }
+        Self {
+            pid: Some(cred.pid as u32),
+            uid: Some(cred.uid),
+            gid: Some(cred.gid),
+        }
+    }
+
+    /// Reads peer credentials from a connected Unix socket.
+    #[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+    pub fn from_fd(fd: std::os::unix::io::RawFd) -> Self {
+        let mut uid: libc::uid_t = 0;
+        let mut gid: libc::gid_t = 0;
+        // SAFETY: `uid` and `gid` are valid for writes.
+        let rc = unsafe { libc::getpeereid(fd, &mut uid, &mut gid) };
+        if rc != 0 {
+            return Self::default();
+        }
+        Self {
+            pid: None,
+            uid: Some(uid),
+            gid: Some(gid),
+        }
+    }
+}
+
+/// Resolves the token presented in a `Hello` to a verified identity.
+pub trait IpcAuthenticator: Send + Sync {
+    fn authenticate(
+        &self,
+        agent_id: &str,
+        token: &str,
+        peer: &PeerCredentials,
+    ) -> Result<AgentIdentity, String>;
+}
+
+#[derive(Debug, Clone)]
+struct IssuedToken {
+    identity: AgentIdentity,
+    /// User id allowed to present this token; `None` where peer credentials are unavailable.
+    uid: Option<u32>,
+}
+
+/// In-memory registry of IPC tokens issued by the core.
+///
+/// Cloning the registry shares the underlying table, so the core can keep issuing tokens
+/// after a clone has been handed to an [`IpcBus`](super::IpcBus).
+#[derive(Debug, Clone, Default)]
+pub struct IpcTokenRegistry {
+    tokens: Arc<Mutex<HashMap<String, IssuedToken>>>,
+}
+
+impl IpcTokenRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Issues a fresh random token for `identity`.
+    pub fn issue(&self, identity: &AgentIdentity) -> Result<String, String> {
+        let mut raw = [0u8; 32];
+        getrandom::getrandom(&mut raw).map_err(|e| format!("Failed to generate IPC token: {e}"))?;
+        let token = crate::to_hex(&raw);
+
+        self.tokens
+            .lock()
+            .expect("IPC token registry poisoned")
+            .insert(
+                token.clone(),
+                IssuedToken {
+                    identity: identity.clone(),
+                    uid: current_uid(),
+                },
+            );
+        Ok(token)
+    }
+
+    /// Revokes a token. Returns whether it was known.
+    pub fn revoke(&self, token: &str) -> bool {
+        self.tokens
+            .lock()
+            .expect("IPC token registry poisoned")
+            .remove(token)
+            .is_some()
+    }
+}
+
+impl IpcAuthenticator for IpcTokenRegistry {
+    fn authenticate(
+        &self,
+        agent_id: &str,
+        token: &str,
+        peer: &PeerCredentials,
+    ) -> Result<AgentIdentity, String> {
+        let tokens = self.tokens.lock().expect("IPC token registry poisoned");
+        let issued = tokens
+            .get(token)
+            .ok_or_else(|| format!("IPC authentication failed for '{agent_id}': unknown token"))?;
+
+        if issued.identity.id != agent_id {
+            return Err(format!(
+                "IPC authentication failed for '{agent_id}': token was issued to '{}'",
+                issued.identity.id
+            ));
+        }
+
+        if let (Some(expected), Some(actual)) = (issued.uid, peer.uid) {
+            if expected != actual {
+                return Err(format!(
+                    "IPC authentication failed for '{agent_id}': peer uid {actual} does not match token uid {expected}"
+                ));
+            }
+        }
+
+        Ok(issued.identity.clone())
+    }
+}
+
+#[cfg(unix)]
+fn current_uid() -> Option<u32> {
+    // SAFETY: geteuid has no preconditions and cannot fail.
+    Some(unsafe { libc::geteuid() })
+}
+
+#[cfg(not(unix))]
+fn current_uid() -> Option<u32> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AuthScope;
+
+    #[test]
+    fn registry_rejects_unknown_mismatched_and_foreign_uid_tokens() {
+        let registry = IpcTokenRegistry::new();
+        let identity = AgentIdentity::new("SearchAgent", vec![AuthScope::WriteFacts]);
+        let token = registry.issue(&identity).expect("issue");
+        let me = PeerCredentials {
+            uid: current_uid(),
+            ..Default::default()
+        };
+
+        let verified = registry
+            .authenticate("SearchAgent", &token, &me)
+            .expect("valid token");
+        assert_eq!(verified.id, "SearchAgent");
+
+        assert!(registry.authenticate("SearchAgent", "bogus", &me).is_err());
+        assert!(registry.authenticate("CalendarAgent", &token, &me).is_err());
+
+        #[cfg(unix)]
+        {
+            let stranger = PeerCredentials {
+                uid: current_uid().map(|u| u.wrapping_add(1)),
+                ..Default::default()
+            };
+            assert!(registry
+                .authenticate("SearchAgent", &token, &stranger)
+                .is_err());
+        }
+
+        assert!(registry.revoke(&token));
+        assert!(registry.authenticate("SearchAgent", &token, &me).is_err());
+    }
+}
@@ -0,0 +1,1007 @@
+//! Async IPC server ([`IpcBus`]) and client ([`IpcClient`]).
+//!
+//! The bus owns a tokio listener (local socket or TCP, see [`IpcEndpoint`]) and runs the
+//! accept loop itself: every
+//! connection gets its own task that decodes [`IpcMessage`] frames and republishes them on
+//! a broadcast channel, so the orchestrator no longer needs to know up front how many
+//! messages to expect. Messages can also be pushed the other way with
+//! [`IpcBus::broadcast`], which fans out to every connected client.
+//!
+//! Every client opens with a `Hello`, which the bus acknowledges by echoing a `Hello`
+//! carrying the accepted agent id. On a bus created with [`IpcBus::bind_authenticated`]
+//! the `Hello` must carry a token the [`IpcAuthenticator`] accepts; every later message on
+//! that connection is tagged with the verified [`AgentIdentity`]. TCP buses must be
+//! authenticated, since remote peers have no OS credentials to check.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use tokio::sync::{broadcast, mpsc, watch, Mutex as AsyncMutex};
+use tokio::task::JoinHandle;
+use tracing::{event, Level};
+
+use super::auth::{IpcAuthenticator, PeerCredentials};
+use super::liveness::AgentRunState;
+use super::protocol::{read_message_async, write_message_async, IpcMessage};
+use super::rpc::{self, RpcCall, RpcReply};
+use super::transport::{self, IpcEndpoint, IpcListener, IpcReader, IpcWriter};
+use crate::stream::AgentEventEnvelope;
+use crate::{AgentFact, AgentIdentity, PAGICoreModel};
+
+/// Capacity of the inbound/outbound broadcast channels. Slow subscribers that fall further
+/// behind than this observe `RecvError::Lagged` rather than blocking connections.
+const CHANNEL_CAPACITY: usize = 1024;
+
+/// Capacity of each connection's direct-reply queue.
+const REPLY_CAPACITY: usize = 64;
+
+/// A decoded inbound message, tagged with the connection it arrived on.
+#[derive(Debug, Clone)]
+pub struct IpcEnvelope {
+    /// Per-bus connection counter (starts at 1).
+    pub connection_id: u64,
+    /// The `agent_id` the client announced in its `Hello`, if it has sent one yet.
+    pub agent_id: Option<String>,
+    /// The identity verified during the handshake. Always `Some` on an authenticated bus.
+    pub identity: Option<AgentIdentity>,
+    pub message: IpcMessage,
+}
+
+/// State shared by the accept loop and every connection task.
+struct BusShared {
+    inbound: broadcast::Sender<IpcEnvelope>,
+    outbound: broadcast::Sender<IpcMessage>,
+    authenticator: Option<Arc<dyn IpcAuthenticator>>,
+    /// Core that RPC requests are executed against, once [`IpcBus::serve_rpc`] is called.
+    rpc_core: RwLock<Option<Arc<PAGICoreModel>>>,
+}
+
+/// Tokio-based IPC server bound to a local socket name or TCP address.
+pub struct IpcBus {
+    endpoint: IpcEndpoint,
+    name: String,
+    shared: Arc<BusShared>,
+    shutdown: watch::Sender<bool>,
+    accept_task: Option<JoinHandle<()>>,
+}
+
+impl std::fmt::Debug for IpcBus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IpcBus")
+            .field("name", &self.name)
+            .field("authenticated", &self.shared.authenticator.is_some())
+            .field("subscribers", &self.shared.inbound.receiver_count())
+            .field("connected_clients", &self.shared.outbound.receiver_count())
+            .finish()
+    }
+}
+
+impl IpcBus {
+    /// Binds the listener and spawns the accept loop. Must be called from within a tokio
+    /// runtime.
+    ///
+    /// `name` is parsed with [`IpcEndpoint::parse`]. Fails if another live server already
+    /// owns a local socket `name`; a stale socket left by a crashed run is reclaimed (see
+    /// [`prepare_socket_path`](super::prepare_socket_path)).
+    ///
+    /// Connections are not authenticated: the `agent_id` in each client's `Hello` is taken
+    /// at face value. Use [`IpcBus::bind_authenticated`] when the socket is reachable by
+    /// untrusted local processes. TCP endpoints are rejected here for the same reason.
+    ///
+    /// Agents connect with [`IpcClient::connect`] using the same `name` (normally
+    /// [`PAGICoreModel::ipc_name`](crate::PAGICoreModel::ipc_name)).
+    pub fn bind(name: &str) -> Result<Self, String> {
+        Self::bind_inner(name, None)
+    }
+
+    /// Like [`IpcBus::bind`], but every client must open with a `Hello` whose token is
+    /// accepted by `authenticator` (typically the core's
+    /// [`IpcTokenRegistry`](super::IpcTokenRegistry)). Rejected clients receive an
+    /// `Error` and are disconnected.
+    pub fn bind_authenticated(
+        name: &str,
+        authenticator: Arc<dyn IpcAuthenticator>,
+    ) -> Result<Self, String> {
+        Self::bind_inner(name, Some(authenticator))
+    }
+
+    fn bind_inner(
+        name: &str,
+        authenticator: Option<Arc<dyn IpcAuthenticator>>,
+    ) -> Result<Self, String> {
+        let endpoint = IpcEndpoint::parse(name);
+        if endpoint.is_tcp() && authenticator.is_none() {
+            return Err(format!(
+                "Refusing to bind IPC bus ({endpoint}) without authentication; use IpcBus::bind_authenticated"
+            ));
+        }
+
+        let (listener, endpoint) = IpcListener::bind(&endpoint)?;
+
+        let (inbound, _) = broadcast::channel(CHANNEL_CAPACITY);
+        let (outbound, _) = broadcast::channel(CHANNEL_CAPACITY);
+        let shared = Arc::new(BusShared {
+            inbound,
+            outbound,
+            authenticator,
+            rpc_core: RwLock::new(None),
+        });
+        let (shutdown, shutdown_rx) = watch::channel(false);
+
+        let accept_task = tokio::spawn(accept_loop(listener, shared.clone(), shutdown_rx));
+
+        Ok(Self {
+            name: endpoint.to_string(),
+            endpoint,
+            shared,
+            shutdown,
+            accept_task: Some(accept_task),
+        })
+    }
+
+    /// The name clients should connect to. For TCP this is `tcp://` plus the bound address,
+    /// with any port `0` resolved to the actual port.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn endpoint(&self) -> &IpcEndpoint {
+        &self.endpoint
+    }
+
+    /// Subscribes to every message received from any client.
+    pub fn subscribe(&self) -> broadcast::Receiver<IpcEnvelope> {
+        self.shared.inbound.subscribe()
+    }
+
+    /// Starts answering [`IpcMessage::Request`]s by executing them against `core`.
+    ///
+    /// Calls run as the identity verified during the handshake, so this only has an effect
+    /// on buses created with [`IpcBus::bind_authenticated`]; unauthenticated connections
+    /// get an error response.
+    pub fn serve_rpc(&self, core: Arc<PAGICoreModel>) {
+        *self.shared.rpc_core.write().expect("IPC bus lock poisoned") = Some(core);
+    }
+
+    /// Feeds every message received on this bus into `core`'s liveness table and sweeps it
+    /// once per heartbeat interval, recording an `AgentLost` fact for agents that went
+    /// silent. Runs until the bus shuts down.
+    pub fn track_liveness(&self, core: Arc<PAGICoreModel>) {
+        let mut inbound = self.subscribe();
+        let mut shutdown = self.shutdown.subscribe();
+        let tracker = core.liveness_tracker();
+
+        tokio::spawn(async move {
+            let mut sweep = tokio::time::interval(tracker.config().interval);
+            loop {
+                tokio::select! {
+                    _ = shutdown.changed() => break,
+                    _ = sweep.tick() => {
+                        let core = core.clone();
+                        let _ = tokio::task::spawn_blocking(move || core.sweep_liveness()).await;
+                    }
+                    res = inbound.recv() => match res {
+                        Ok(env) => {
+                            let Some(agent_id) = env.agent_id else { continue };
+                            let heartbeat = match env.message {
+                                IpcMessage::Heartbeat { current_task, state, .. } => {
+                                    Some((current_task, state))
+                                }
+                                _ => None,
+                            };
+                            tracker.observe(&agent_id, heartbeat);
+                        }
+                        Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    },
+                }
+            }
+        });
+    }
+
+    /// Broadcasts `core`'s approval notifications ([`IpcMessage::ApprovalRequested`] /
+    /// [`IpcMessage::ApprovalResolved`]) to connected clients, and settles timed-out
+    /// requests once per second. Runs until the bus shuts down.
+    pub fn forward_approvals(&self, core: Arc<PAGICoreModel>) {
+        let mut notices = core.approval_notices();
+        let mut shutdown = self.shutdown.subscribe();
+        let outbound = self.shared.outbound.clone();
+
+        tokio::spawn(async move {
+            let mut sweep = tokio::time::interval(Duration::from_secs(1));
+            loop {
+                tokio::select! {
+                    _ = shutdown.changed() => break,
+                    _ = sweep.tick() => {
+                        let core = core.clone();
+                        let _ = tokio::task::spawn_blocking(move || core.sweep_approvals()).await;
+                    }
+                    res = notices.recv() => match res {
+                        Ok(msg) => {
+                            let _ = outbound.send(msg);
+                        }
+                        Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    },
+                }
+            }
+        });
+    }
+
+    /// Relays the events [`execute_streaming`](crate::execute_streaming) publishes on `events`
+    /// to connected clients, mapped with [`IpcMessage::from_agent_event`]. Runs until the bus
+    /// shuts down or every sender is dropped.
+    pub fn forward_agent_events(&self, events: &broadcast::Sender<AgentEventEnvelope>) {
+        let mut events = events.subscribe();
+        let mut shutdown = self.shutdown.subscribe();
+        let outbound = self.shared.outbound.clone();
+
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = shutdown.changed() => break,
+                    res = events.recv() => match res {
+                        Ok(envelope) => {
+                            let _ = outbound.send(IpcMessage::from_agent_event(&envelope));
+                        }
+                        Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    },
+                }
+            }
+        });
+    }
+
+    /// Sends `msg` to every currently connected (and, if required, authenticated) client.
+    /// Returns how many connections it was queued for.
+    pub fn broadcast(&self, msg: IpcMessage) -> usize {
+        self.shared.outbound.send(msg).unwrap_or(0)
+    }
+
+    /// Stops accepting connections, closes existing ones and waits for the accept loop to
+    /// exit.
+    pub async fn shutdown(mut self) {
+        let _ = self.shutdown.send(true);
+        if let Some(task) = self.accept_task.take() {
+            let _ = task.await;
+        }
+    }
+}
+
+impl Drop for IpcBus {
+    fn drop(&mut self) {
+        let _ = self.shutdown.send(true);
+
+        #[cfg(unix)]
+        if let IpcEndpoint::LocalSocket(ref path) = self.endpoint {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+async fn accept_loop(
+    listener: IpcListener,
+    shared: Arc<BusShared>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let next_id = AtomicU64::new(1);
+
+    loop {
+        tokio::select! {
+            _ = shutdown.changed() => break,
+            res = listener.accept() => match res {
+                Ok((reader, writer, peer)) => {
+                    let connection_id = next_id.fetch_add(1, Ordering::Relaxed);
+                    event!(Level::DEBUG, connection_id, peer = ?peer, "IPC client connected");
+
+                    let (reply_tx, reply_rx) = mpsc::channel(REPLY_CAPACITY);
+                    tokio::spawn(write_loop(writer, reply_rx, shutdown.clone()));
+                    tokio::spawn(read_loop(
+                        connection_id,
+                        peer,
+                        reader,
+                        reply_tx,
+                        shared.clone(),
+                        shutdown.clone(),
+                    ));
+                }
+                Err(e) => {
+                    event!(Level::WARN, error = %e, "IPC accept failed");
+                }
+            },
+        }
+    }
+}
+
+/// Forwards bus-wide broadcasts into one connection's reply queue.
+fn spawn_broadcast_forwarder(
+    mut outbound: broadcast::Receiver<IpcMessage>,
+    reply_tx: mpsc::Sender<IpcMessage>,
+) {
+    tokio::spawn(async move {
+        loop {
+            match outbound.recv().await {
+                Ok(msg) => {
+                    if reply_tx.send(msg).await.is_err() {
+                        break;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    event!(
+                        Level::WARN,
+                        skipped,
+                        "IPC client lagging; dropped outbound messages"
+                    );
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+async fn read_loop(
+    connection_id: u64,
+    peer: PeerCredentials,
+    mut reader: IpcReader,
+    reply_tx: mpsc::Sender<IpcMessage>,
+    shared: Arc<BusShared>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let mut agent_id: Option<String> = None;
+    let mut identity: Option<AgentIdentity> = None;
+
+    // Unauthenticated buses push broadcasts from the start; authenticated ones only once
+    // the handshake succeeds.
+    if shared.authenticator.is_none() {
+        spawn_broadcast_forwarder(shared.outbound.subscribe(), reply_tx.clone());
+    }
+
+    loop {
+        let msg = tokio::select! {
+            _ = shutdown.changed() => break,
+            res = read_message_async(&mut reader) => match res {
+                Ok(Some(msg)) => msg,
+                Ok(None) => break,
+                Err(e) => {
+                    event!(Level::WARN, connection_id, error = %e, "IPC read failed; closing connection");
+                    break;
+                }
+            },
+        };
+
+        match (&msg, &shared.authenticator, &identity) {
+            (
+                IpcMessage::Hello {
+                    agent_id: id,
+                    token,
+                },
+                Some(auth),
+                None,
+            ) => {
+                let res = token
+                    .as_deref()
+                    .ok_or_else(|| format!("IPC authentication failed for '{id}': missing token"))
+                    .and_then(|t| auth.authenticate(id, t, &peer));
+                match res {
+                    Ok(verified) => {
+                        agent_id = Some(verified.id.clone());
+                        identity = Some(verified);
+                        spawn_broadcast_forwarder(shared.outbound.subscribe(), reply_tx.clone());
+                    }
+                    Err(e) => {
+                        event!(Level::WARN, connection_id, peer = ?peer, error = %e, "IPC handshake rejected");
+                        let _ = reply_tx.send(IpcMessage::Error { message: e }).await;
+                        break;
+                    }
+                }
+            }
+            (IpcMessage::Hello { agent_id: id, .. }, None, _) => {
+                agent_id = Some(id.clone());
+            }
+            (_, Some(_), None) => {
+                let message = "IPC handshake required: first message must be Hello".to_string();
+                event!(Level::WARN, connection_id, peer = ?peer, "{message}");
+                let _ = reply_tx.send(IpcMessage::Error { message }).await;
+                break;
+            }
+            (IpcMessage::Hello { .. }, Some(_), Some(_)) => {
+                // Re-authentication on a live connection is not supported.
+                continue;
+            }
+            (_, _, Some(verified)) => {
+                if msg.agent_id().is_some_and(|claimed| claimed != verified.id) {
+                    event!(
+                        Level::WARN,
+                        connection_id,
+                        identity_id = %verified.id,
+                        claimed = ?msg.agent_id(),
+                        "Dropping IPC message impersonating another agent"
+                    );
+                    continue;
+                }
+            }
+            (_, None, None) => {}
+        }
+
+        if let IpcMessage::Request { id, call } = msg {
+            handle_request(
+                id,
+                call,
+                identity.clone(),
+                agent_id.clone(),
+                connection_id,
+                reply_tx.clone(),
+                shared.clone(),
+            );
+            continue;
+        }
+
+        if let IpcMessage::Hello { .. } = msg {
+            // Acknowledge the handshake with the (verified, if applicable) agent id.
+            let ack = IpcMessage::Hello {
+                agent_id: agent_id.clone().unwrap_or_default(),
+                token: None,
+            };
+            if reply_tx.send(ack).await.is_err() {
+                break;
+            }
+        }
+
+        let _ = shared.inbound.send(IpcEnvelope {
+            connection_id,
+            agent_id: agent_id.clone(),
+            identity: identity.clone(),
+            message: msg,
+        });
+    }
+
+    event!(Level::DEBUG, connection_id, "IPC client disconnected");
+}
+
+/// Executes an RPC request off the read loop and queues the response on the connection.
+fn handle_request(
+    id: u64,
+    call: RpcCall,
+    identity: Option<AgentIdentity>,
+    agent_id: Option<String>,
+    connection_id: u64,
+    reply_tx: mpsc::Sender<IpcMessage>,
+    shared: Arc<BusShared>,
+) {
+    let core = shared
+        .rpc_core
+        .read()
+        .expect("IPC bus lock poisoned")
+        .clone();
+
+    tokio::spawn(async move {
+        let recorded = match &call {
+            RpcCall::RecordFact(fact) => Some((fact.fact_type.clone(), fact.timestamp)),
+            _ => None,
+        };
+
+        let result = match (core, identity.clone()) {
+            (None, _) => Err("RPC is not enabled on this IPC bus".to_string()),
+            (_, None) => Err("RPC requires an authenticated IPC connection".to_string()),
+            (Some(core), Some(identity)) => {
+                tokio::task::spawn_blocking(move || rpc::dispatch(&core, &identity, call))
+                    .await
+                    .unwrap_or_else(|e| Err(format!("RPC handler panicked: {e}")))
+            }
+        };
+
+        if let (Ok(_), Some((fact_type, timestamp)), Some(verified)) =
+            (&result, recorded, identity.as_ref())
+        {
+            let _ = shared.inbound.send(IpcEnvelope {
+                connection_id,
+                agent_id: agent_id.clone(),
+                identity: identity.clone(),
+                message: IpcMessage::FactRecorded {
+                    agent_id: verified.id.clone(),
+                    fact_type,
+                    timestamp,
+                },
+            });
+        }
+
+        let _ = reply_tx.send(IpcMessage::Response { id, result }).await;
+    });
+}
+
+async fn write_loop(
+    mut writer: IpcWriter,
+    mut replies: mpsc::Receiver<IpcMessage>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    loop {
+        let msg = tokio::select! {
+            _ = shutdown.changed() => break,
+            msg = replies.recv() => match msg {
+                Some(msg) => msg,
+                None => break,
+            },
+        };
+
+        if write_message_async(&mut writer, &msg).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Agent-side connection to an [`IpcBus`].
+pub struct IpcClient {
+    agent_id: String,
+    reader: IpcReader,
+    /// Shared with the heartbeat task, if one is running.
+    writer: Arc<AsyncMutex<IpcWriter>>,
+    /// Task and state reported in heartbeats.
+    activity: Arc<Mutex<(Option<String>, AgentRunState)>>,
+    heartbeat_task: Option<JoinHandle<()>>,
+    /// Pushed messages that arrived while waiting for an RPC response.
+    pending: VecDeque<IpcMessage>,
+    next_request_id: u64,
+}
+
+impl Drop for IpcClient {
+    fn drop(&mut self) {
+        if let Some(task) = self.heartbeat_task.take() {
+            task.abort();
+        }
+    }
+}
+
+impl std::fmt::Debug for IpcClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IpcClient")
+            .field("agent_id", &self.agent_id)
+            .finish()
+    }
+}
+
+impl IpcClient {
+    /// Connects to an unauthenticated bus at `name` and announces `agent_id`.
+    ///
+    /// `name` is a local socket name or a `tcp://host:port` URL (see [`IpcEndpoint::parse`]).
+    pub async fn connect(name: &str, agent_id: &str) -> Result<Self, String> {
+        Self::handshake(name, agent_id, None).await
+    }
+
+    /// Connects to a bus and authenticates with a token issued by the core (see
+    /// [`PAGICoreModel::issue_ipc_token`](crate::PAGICoreModel::issue_ipc_token)).
+    pub async fn connect_with_token(
+        name: &str,
+        agent_id: &str,
+        token: &str,
+    ) -> Result<Self, String> {
+        Self::handshake(name, agent_id, Some(token.to_string())).await
+    }
+
+    async fn handshake(name: &str, agent_id: &str, token: Option<String>) -> Result<Self, String> {
+        let (reader, writer) = transport::connect(&IpcEndpoint::parse(name)).await?;
+
+        let mut client = Self {
+            agent_id: agent_id.to_string(),
+            reader,
+            writer: Arc::new(AsyncMutex::new(writer)),
+            activity: Arc::new(Mutex::new((None, AgentRunState::Idle))),
+            heartbeat_task: None,
+            pending: VecDeque::new(),
+            next_request_id: 1,
+        };
+        client
+            .send(&IpcMessage::Hello {
+                agent_id: agent_id.to_string(),
+                token,
+            })
+            .await?;
+
+        match client.read_next().await? {
+            Some(IpcMessage::Hello { .. }) => Ok(client),
+            Some(IpcMessage::Error { message }) => Err(message),
+            Some(other) => Err(format!("Unexpected IPC handshake reply: {other:?}")),
+            None => Err("IPC bus closed the connection during handshake".to_string()),
+        }
+    }
+
+    pub fn agent_id(&self) -> &str {
+        &self.agent_id
+    }
+
+    /// Sends a single message to the bus.
+    pub async fn send(&mut self, msg: &IpcMessage) -> Result<(), String> {
+        let mut writer = self.writer.lock().await;
+        write_message_async(&mut *writer, msg)
+            .await
+            .map_err(|e| format!("IPC send failed: {e}"))
+    }
+
+    /// Updates the task and state reported in subsequent heartbeats.
+    pub fn set_activity(&self, current_task: Option<String>, state: AgentRunState) {
+        *self.activity.lock().expect("IPC client activity poisoned") = (current_task, state);
+    }
+
+    /// Spawns a background task that sends a heartbeat every `interval` until the client is
+    /// dropped or [`IpcClient::stop`] is called. Use the core's
+    /// [`HeartbeatConfig::interval`](super::HeartbeatConfig::interval).
+    pub fn start_heartbeats(&mut self, interval: Duration) {
+        if let Some(task) = self.heartbeat_task.take() {
+            task.abort();
+        }
+
+        let agent_id = self.agent_id.clone();
+        let writer = self.writer.clone();
+        let activity = self.activity.clone();
+        self.heartbeat_task = Some(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let (current_task, state) = activity
+                    .lock()
+                    .expect("IPC client activity poisoned")
+                    .clone();
+                let msg = IpcMessage::Heartbeat {
+                    agent_id: agent_id.clone(),
+                    current_task,
+                    state,
+                };
+                let mut w = writer.lock().await;
+                if write_message_async(&mut *w, &msg).await.is_err() {
+                    break;
+                }
+            }
+        }));
+    }
+
+    /// Stops heartbeats and tells the bus this agent is leaving on purpose, so it is not
+    /// reported as lost.
+    pub async fn stop(&mut self) -> Result<(), String> {
+        if let Some(task) = self.heartbeat_task.take() {
+            task.abort();
+        }
+        let msg = IpcMessage::Heartbeat {
+            agent_id: self.agent_id.clone(),
+            current_task: None,
+            state: AgentRunState::Stopping,
+        };
+        self.send(&msg).await
+    }
+
+    /// Convenience wrapper for [`IpcMessage::Status`].
+    pub async fn status(&mut self, message: &str) -> Result<(), String> {
+        let msg = IpcMessage::Status {
+            agent_id: self.agent_id.clone(),
+            message: message.to_string(),
+        };
+        self.send(&msg).await
+    }
+
+    /// Waits for the next message pushed by the bus. Returns `Ok(None)` once the bus closes
+    /// the connection.
+    pub async fn recv(&mut self) -> Result<Option<IpcMessage>, String> {
+        if let Some(msg) = self.pending.pop_front() {
+            return Ok(Some(msg));
+        }
+        self.read_next().await
+    }
+
+    async fn read_next(&mut self) -> Result<Option<IpcMessage>, String> {
+        read_message_async(&mut self.reader)
+            .await
+            .map_err(|e| format!("IPC receive failed: {e}"))
+    }
+
+    /// Invokes `call` on the orchestrator's core and waits for the response.
+    ///
+    /// Messages pushed by the bus in the meantime are kept and returned by later
+    /// [`IpcClient::recv`] calls.
+    pub async fn call(&mut self, call: RpcCall) -> Result<RpcReply, String> {
+        let id = self.next_request_id;
+        self.next_request_id += 1;
+        self.send(&IpcMessage::Request { id, call }).await?;
+
+        loop {
+            match self.read_next().await? {
+                Some(IpcMessage::Response { id: rid, result }) if rid == id => return result,
+                Some(other) => self.pending.push_back(other),
+                None => return Err("IPC bus closed the connection before responding".to_string()),
+            }
+        }
+    }
+
+    /// Remote [`PAGICoreModel::record_fact`].
+    pub async fn record_fact(&mut self, fact: AgentFact) -> Result<(), String> {
+        match self.call(RpcCall::RecordFact(fact)).await? {
+            RpcReply::Ack => Ok(()),
+            other => Err(format!("Unexpected RPC reply: {other:?}")),
+        }
+    }
+
+    /// Remote [`PAGICoreModel::retrieve_facts_by_timestamp`].
+    pub async fn retrieve_facts_by_timestamp(
+        &mut self,
+        start_ts: u64,
+    ) -> Result<Vec<AgentFact>, String> {
+        match self
+            .call(RpcCall::RetrieveFactsByTimestamp { start_ts })
+            .await?
+        {
+            RpcReply::Facts(facts) => Ok(facts),
+            other => Err(format!("Unexpected RPC reply: {other:?}")),
+        }
+    }
+
+    /// Remote [`PAGICoreModel::apply_rules_to_facts`].
+    pub async fn apply_rules_to_facts(
+        &mut self,
+        facts: Vec<AgentFact>,
+    ) -> Result<Vec<String>, String> {
+        match self.call(RpcCall::ApplyRules { facts }).await? {
+            RpcReply::Directives(d) => Ok(d),
+            other => Err(format!("Unexpected RPC reply: {other:?}")),
+        }
+    }
+
+    /// Remote [`PAGICoreModel::symbolic_directives`].
+    pub async fn symbolic_directives(&mut self) -> Result<Vec<String>, String> {
+        match self.call(RpcCall::SymbolicDirectives).await? {
+            RpcReply::Directives(d) => Ok(d),
+            other => Err(format!("Unexpected RPC reply: {other:?}")),
+        }
+    }
+
+    /// Remote [`PAGICoreModel::approve_action`].
+    pub async fn approve_action(&mut self, request_id: &str) -> Result<(), String> {
+        let call = RpcCall::ApproveAction {
+            request_id: request_id.to_string(),
+        };
+        match self.call(call).await? {
+            RpcReply::Ack => Ok(()),
+            other => Err(format!("Unexpected RPC reply: {other:?}")),
+        }
+    }
+
+    /// Remote [`PAGICoreModel::reject_action`].
+    pub async fn reject_action(&mut self, request_id: &str, reason: &str) -> Result<(), String> {
+        let call = RpcCall::RejectAction {
+            request_id: request_id.to_string(),
+            reason: reason.to_string(),
+        };
+        match self.call(call).await? {
+            RpcReply::Ack => Ok(()),
+            other => Err(format!("Unexpected RPC reply: {other:?}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipc::IpcTokenRegistry;
+    use crate::{AgentEvent, AuthScope};
+
+    #[tokio::test]
+    async fn bus_publishes_client_messages_and_broadcasts_back() {
+        let name = format!("/tmp/pagi_ipc_bus_test_{}", std::process::id());
+        let bus = IpcBus::bind(&name).expect("bind bus");
+        let mut inbound = bus.subscribe();
+
+        let mut client = IpcClient::connect(&name, "SearchAgent")
+            .await
+            .expect("connect");
+        client.status("I'm starting…").await.expect("send");
+
+        let hello = inbound.recv().await.expect("hello");
+        assert!(matches!(hello.message, IpcMessage::Hello { .. }));
+        let status = inbound.recv().await.expect("status");
+        assert_eq!(status.agent_id.as_deref(), Some("SearchAgent"));
+        assert_eq!(status.connection_id, hello.connection_id);
+
+        // The broadcast forwarder subscribes on accept, which has happened by the time we
+        // saw Hello.
+        assert_eq!(
+            bus.broadcast(IpcMessage::Error {
+                message: "stop".to_string(),
+            }),
+            1
+        );
+        let pushed = tokio::time::timeout(Duration::from_secs(5), client.recv())
+            .await
+            .expect("timely push")
+            .expect("recv");
+        assert!(matches!(pushed, Some(IpcMessage::Error { .. })));
+
+        bus.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn bus_relays_streamed_agent_events() {
+        let name = format!("/tmp/pagi_ipc_stream_test_{}", std::process::id());
+        let bus = IpcBus::bind(&name).expect("bind bus");
+        let mut inbound = bus.subscribe();
+        let (events, _) = broadcast::channel(16);
+        bus.forward_agent_events(&events);
+
+        let mut client = IpcClient::connect(&name, "Observer")
+            .await
+            .expect("connect");
+        client.status("watching").await.expect("send");
+        inbound.recv().await.expect("hello");
+
+        for event in [
+            AgentEvent::PartialResult("hello".to_string()),
+            AgentEvent::Error("write denied".to_string()),
+        ] {
+            events
+                .send(AgentEventEnvelope {
+                    agent_id: "SearchAgent".to_string(),
+                    event,
+                })
+                .expect("forwarder subscribed");
+        }
+        let mut pushed = Vec::new();
+        while pushed.len() < 2 {
+            let msg = tokio::time::timeout(Duration::from_secs(5), client.recv())
+                .await
+                .expect("timely push")
+                .expect("recv")
+                .expect("open");
+            pushed.push(msg);
+        }
+        assert_eq!(
+            pushed[0],
+            IpcMessage::PartialResult {
+                agent_id: "SearchAgent".to_string(),
+                chunk: "hello".to_string(),
+            }
+        );
+        assert!(
+            matches!(&pushed[1], IpcMessage::Error { message } if message.contains("write denied"))
+        );
+
+        bus.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn authenticated_bus_rejects_bad_tokens_and_tags_verified_identity() {
+        let name = format!("/tmp/pagi_ipc_auth_test_{}", std::process::id());
+        let registry = IpcTokenRegistry::new();
+        let identity = AgentIdentity::new("CybersecurityAgent", vec![AuthScope::ReadFacts]);
+        let token = registry.issue(&identity).expect("issue");
+        let bus = IpcBus::bind_authenticated(&name, Arc::new(registry)).expect("bind bus");
+        let mut inbound = bus.subscribe();
+
+        let rejected = IpcClient::connect_with_token(&name, "CybersecurityAgent", "nope").await;
+        assert!(rejected.is_err());
+        assert!(IpcClient::connect(&name, "CybersecurityAgent")
+            .await
+            .is_err());
+
+        let mut client = IpcClient::connect_with_token(&name, "CybersecurityAgent", &token)
+            .await
+            .expect("authenticated");
+        client.status("triaging").await.expect("send");
+        // Impersonation attempts on an authenticated connection are dropped.
+        client
+            .send(&IpcMessage::Status {
+                agent_id: "SearchAgent".to_string(),
+                message: "spoofed".to_string(),
+            })
+            .await
+            .expect("send");
+        client.status("done").await.expect("send");
+
+        let mut seen = Vec::new();
+        while seen.len() < 3 {
+            let env = inbound.recv().await.expect("envelope");
+            assert_eq!(
+                env.identity.as_ref().map(|i| i.id.as_str()),
+                Some("CybersecurityAgent")
+            );
+            seen.push(env.message);
+        }
+        assert!(matches!(seen[0], IpcMessage::Hello { .. }));
+        assert!(matches!(&seen[1], IpcMessage::Status { message, .. } if message == "triaging"));
+        assert!(matches!(&seen[2], IpcMessage::Status { message, .. } if message == "done"));
+
+        bus.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn rpc_executes_against_core_with_verified_identity() {
+        let name = format!("/tmp/pagi_ipc_rpc_test_{}", std::process::id());
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("failed to open temporary sled db");
+        let core = Arc::new(PAGICoreModel::from_db(db).require_credentials(false));
+
+        let writer = AgentIdentity::new("SearchAgent", vec![AuthScope::WriteFacts]);
+        let reader = AgentIdentity::new("ReflectiveAgent", vec![AuthScope::ReadFacts]);
+        let writer_token = core.issue_ipc_token(&writer).expect("issue");
+        let reader_token = core.issue_ipc_token(&reader).expect("issue");
+
+        let bus = IpcBus::bind_authenticated(&name, Arc::new(core.ipc_token_registry()))
+            .expect("bind bus");
+        bus.serve_rpc(core.clone());
+
+        let mut search = IpcClient::connect_with_token(&name, "SearchAgent", &writer_token)
+            .await
+            .expect("connect");
+        search
+            .record_fact(AgentFact {
+                agent_id: "SearchAgent".to_string(),
+                timestamp: 42,
+                fact_type: "AnalysisResult".to_string(),
+                content: "Failure: SearchAgent timeout".to_string(),
+            })
+            .await
+            .expect("write allowed");
+        // Claims another agent's name; recorded under the verified identity instead.
+        search
+            .record_fact(AgentFact {
+                agent_id: "CybersecurityAgent".to_string(),
+                timestamp: 43,
+                fact_type: "AnalysisResult".to_string(),
+                content: "All clear".to_string(),
+            })
+            .await
+            .expect("write allowed");
+        // Server-side authorization: the writer lacks ReadFacts.
+        assert!(search.retrieve_facts_by_timestamp(0).await.is_err());
+
+        let mut reflective = IpcClient::connect_with_token(&name, "ReflectiveAgent", &reader_token)
+            .await
+            .expect("connect");
+        let facts = reflective
+            .retrieve_facts_by_timestamp(0)
+            .await
+            .expect("read allowed");
+        assert_eq!(facts.len(), 2);
+        assert!(facts.iter().all(|f| f.agent_id == "SearchAgent"));
+        let directives = reflective.symbolic_directives().await.expect("rules");
+        assert!(directives.iter().any(|d| d.contains("Deep Search")));
+
+        bus.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn tcp_transport_shares_handshake_and_rpc_authorization() {
+        assert!(IpcBus::bind("tcp://127.0.0.1:0").is_err());
+
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("failed to open temporary sled db");
+        let core = Arc::new(PAGICoreModel::from_db(db).require_credentials(false));
+        let identity = AgentIdentity::new("SearchAgent", vec![AuthScope::WriteFacts]);
+        let token = core.issue_ipc_token(&identity).expect("issue");
+
+        let bus =
+            IpcBus::bind_authenticated("tcp://127.0.0.1:0", Arc::new(core.ipc_token_registry()))
+                .expect("bind tcp bus");
+        bus.serve_rpc(core.clone());
+        assert!(bus.endpoint().is_tcp());
+        assert!(!bus.name().ends_with(":0"));
+
+        assert!(IpcClient::connect(bus.name(), "SearchAgent").await.is_err());
+
+        let mut client = IpcClient::connect_with_token(bus.name(), "SearchAgent", &token)
+            .await
+            .expect("connect over tcp");
+        client
+            .record_fact(AgentFact {
+                agent_id: "SearchAgent".to_string(),
+                timestamp: 7,
+                fact_type: "AnalysisResult".to_string(),
+                content: "Success: found 3 sources".to_string(),
+            })
+            .await
+            .expect("write allowed");
+        assert!(client.retrieve_facts_by_timestamp(0).await.is_err());
+
+        bus.shutdown().await;
+    }
+}
@@ -0,0 +1,341 @@
+//! IPC socket naming and lifecycle.
+//!
+//! Socket names come from [`IpcConfig`] instead of a single hard-coded path, so several
+//! orchestrators can share a host. On Unix the default is
+//! `$XDG_RUNTIME_DIR/pagi_shmem_pipe.<instance>` (falling back to a private
+//! `/tmp/pagi-<uid>/` directory), where `<instance>` defaults to the process id.
+//!
+//! Binding goes through [`prepare_socket_path`], which checks that the socket directory is
+//! private to this user and only unlinks a leftover socket if no live server answers on it.
+//! The bind itself runs under [`with_private_umask`], so the socket is never accessible to
+//! other users, and [`restrict_socket_permissions`] then sets its mode explicitly.
+//!
+//! Setting `PAGI_IPC_TRANSPORT=tcp` switches to a TCP endpoint (`PAGI_IPC_TCP_ADDR`, default
+//! `127.0.0.1:7878`) for agents that don't share a filesystem with the orchestrator; see
+//! [`IpcEndpoint`].
+
+use std::path::PathBuf;
+
+use super::transport::IpcEndpoint;
+
+/// Base file name of the IPC socket; the instance suffix is appended to it.
+pub const PAGI_IPC_BASENAME: &str = "pagi_shmem_pipe";
+
+/// Environment variable overriding the full socket name.
+pub const PAGI_IPC_NAME_ENV: &str = "PAGI_IPC_NAME";
+
+/// Environment variable overriding the socket directory (Unix).
+pub const PAGI_IPC_DIR_ENV: &str = "PAGI_IPC_DIR";
+
+/// Environment variable selecting the instance suffix.
+pub const PAGI_INSTANCE_ENV: &str = "PAGI_INSTANCE";
+
+/// Environment variable selecting the transport (`local` or `tcp`).
+pub const PAGI_IPC_TRANSPORT_ENV: &str = "PAGI_IPC_TRANSPORT";
+
+/// Environment variable holding the `host:port` the TCP transport binds.
+pub const PAGI_IPC_TCP_ADDR_ENV: &str = "PAGI_IPC_TCP_ADDR";
+
+/// Address the TCP transport binds when `PAGI_IPC_TCP_ADDR` is unset.
+pub const DEFAULT_TCP_ADDR: &str = "127.0.0.1:7878";
+
+/// Which transport the IPC bus runs over.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IpcTransport {
+    /// Local socket: Unix domain socket or Windows named pipe.
+    #[default]
+    LocalSocket,
+    /// TCP, for agents in another container or network namespace. Requires token auth.
+    Tcp,
+}
+
+/// Where the IPC socket lives.
+///
+/// Agent processes should not resolve their own config: with the default per-process
+/// instance suffix they would compute a different name. The orchestrator passes
+/// [`PAGICoreModel::ipc_name`](crate::PAGICoreModel::ipc_name) down instead (for example via
+/// `PAGI_IPC_NAME`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IpcConfig {
+    /// Full socket name. Overrides `runtime_dir` and `instance` when set.
+    pub name: Option<String>,
+    /// Directory holding the socket (Unix only).
+    pub runtime_dir: Option<PathBuf>,
+    /// Per-instance suffix; defaults to the current process id.
+    pub instance: Option<String>,
+    pub transport: IpcTransport,
+    /// `host:port` for [`IpcTransport::Tcp`]; defaults to [`DEFAULT_TCP_ADDR`].
+    pub tcp_addr: Option<String>,
+}
+
+impl IpcConfig {
+    /// Reads `PAGI_IPC_NAME`, `PAGI_IPC_DIR`, `PAGI_INSTANCE`, `PAGI_IPC_TRANSPORT` and
+    /// `PAGI_IPC_TCP_ADDR`.
+    pub fn from_env() -> Self {
+        let var = |key: &str| std::env::var(key).ok().filter(|v| !v.trim().is_empty());
+        let transport = match var(PAGI_IPC_TRANSPORT_ENV) {
+            Some(t) if t.trim().eq_ignore_ascii_case("tcp") => IpcTransport::Tcp,
+            _ => IpcTransport::LocalSocket,
+        };
+        Self {
+            name: var(PAGI_IPC_NAME_ENV),
+            runtime_dir: var(PAGI_IPC_DIR_ENV).map(PathBuf::from),
+            instance: var(PAGI_INSTANCE_ENV),
+            transport,
+            tcp_addr: var(PAGI_IPC_TCP_ADDR_ENV),
+        }
+    }
+
+    /// Resolves the endpoint to bind / connect to for the configured transport.
+    ///
+    /// An explicit `name` wins, so `PAGI_IPC_NAME=tcp://host:port` also selects TCP.
+    pub fn endpoint(&self) -> IpcEndpoint {
+        match (&self.name, self.transport) {
+            (Some(name), _) => IpcEndpoint::parse(name),
+            (None, IpcTransport::Tcp) => IpcEndpoint::Tcp(
+                self.tcp_addr
+                    .clone()
+                    .unwrap_or_else(|| DEFAULT_TCP_ADDR.to_string()),
+            ),
+            (None, IpcTransport::LocalSocket) => IpcEndpoint::LocalSocket(self.resolve_name()),
+        }
+    }
+
+    /// Returns the instance suffix in effect.
+    pub fn instance(&self) -> String {
+        self.instance
+            .clone()
+            .unwrap_or_else(|| std::process::id().to_string())
+    }
+
+    /// Resolves the local socket name to bind / connect to.
+    pub fn resolve_name(&self) -> String {
+        if let Some(ref name) = self.name {
+            return name.clone();
+        }
+
+        let file_name = format!("{PAGI_IPC_BASENAME}.{}", self.instance());
+
+        #[cfg(unix)]
+        {
+            self.runtime_dir()
+                .join(file_name)
+                .to_string_lossy()
+                .into_owned()
+        }
+
+        #[cfg(not(unix))]
+        {
+            file_name
+        }
+    }
+
+    /// The directory the socket is placed in (Unix).
+    #[cfg(unix)]
+    pub fn runtime_dir(&self) -> PathBuf {
+        if let Some(ref dir) = self.runtime_dir {
+            return dir.clone();
+        }
+        match std::env::var_os("XDG_RUNTIME_DIR") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            // SAFETY: geteuid has no preconditions and cannot fail.
+            _ => std::env::temp_dir().join(format!("pagi-{}", unsafe { libc::geteuid() })),
+        }
+    }
+}
+
+/// Makes `name` available for binding.
+///
+/// On Unix, if something already exists at the path:
+/// - a live server answering on it is an error (we refuse to clobber another instance);
+/// - a socket nobody answers on is a leftover from a crashed run and is unlinked;
+/// - anything that isn't a socket is left alone and reported as an error.
+///
+/// Missing parent directories are created with mode `0700`. An existing parent must be owned
+/// by the effective user with no group or other access, or be a root-owned sticky directory
+/// such as `/tmp`; anything else (e.g. a `/tmp/pagi-<uid>` pre-created by another user) is an
+/// error.
+pub fn prepare_socket_path(name: &str) -> Result<(), String> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{DirBuilderExt, FileTypeExt};
+        use std::path::Path;
+
+        let path = Path::new(name);
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            if !parent.exists() {
+                std::fs::DirBuilder::new()
+                    .recursive(true)
+                    .mode(0o700)
+                    .create(parent)
+                    .map_err(|e| {
+                        format!("Failed to create IPC directory {}: {e}", parent.display())
+                    })?;
+            }
+            check_socket_dir(parent)?;
+        }
+
+        let Ok(meta) = std::fs::symlink_metadata(path) else {
+            return Ok(());
+        };
+        if !meta.file_type().is_socket() {
+            return Err(format!(
+                "Refusing to replace {name}: it exists and is not a socket"
+            ));
+        }
+        if interprocess::local_socket::LocalSocketStream::connect(name).is_ok() {
+            return Err(format!(
+                "IPC name {name} is already in use by a live server"
+            ));
+        }
+        std::fs::remove_file(path)
+            .map_err(|e| format!("Failed to remove stale IPC socket {name}: {e}"))?;
+    }
+
+    #[cfg(not(unix))]
+    let _ = name;
+
+    Ok(())
+}
+
+/// Refuses socket directories other users could tamper with.
+#[cfg(unix)]
+fn check_socket_dir(dir: &std::path::Path) -> Result<(), String> {
+    use std::os::unix::fs::MetadataExt;
+
+    let meta = std::fs::symlink_metadata(dir)
+        .map_err(|e| format!("Failed to inspect IPC directory {}: {e}", dir.display()))?;
+    if !meta.is_dir() {
+        return Err(format!(
+            "IPC directory {} is not a directory (symlinks are refused)",
+            dir.display()
+        ));
+    }
+    // SAFETY: geteuid has no preconditions and cannot fail.
+    let euid = unsafe { libc::geteuid() };
+    let mode = meta.mode();
+    let private = meta.uid() == euid && mode & 0o077 == 0;
+    let shared_sticky = meta.uid() == 0 && mode & 0o1000 != 0;
+    if private || shared_sticky {
+        return Ok(());
+    }
+    Err(format!(
+        "Refusing IPC directory {}: it must be owned by uid {euid} with mode 0700 \
+         (found uid {}, mode {:o})",
+        dir.display(),
+        meta.uid(),
+        mode & 0o7777
+    ))
+}
+
+/// Runs `bind` with the process umask set to `0177`, so a socket it creates starts out
+/// owner-only instead of being briefly accessible until [`restrict_socket_permissions`].
+///
+/// The umask is process-wide; files other threads create meanwhile are also owner-only.
+pub fn with_private_umask<T>(bind: impl FnOnce() -> T) -> T {
+    #[cfg(unix)]
+    {
+        // SAFETY: umask has no preconditions and cannot fail.
+        let previous = unsafe { libc::umask(0o177) };
+        let out = bind();
+        // SAFETY: as above.
+        unsafe { libc::umask(previous) };
+        out
+    }
+
+    #[cfg(not(unix))]
+    bind()
+}
+
+/// Restricts a freshly bound socket to its owner (`0600`). No-op on non-Unix platforms.
+pub fn restrict_socket_permissions(name: &str) -> Result<(), String> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(name, std::fs::Permissions::from_mode(0o600))
+            .map_err(|e| format!("Failed to restrict permissions on IPC socket {name}: {e}"))?;
+    }
+
+    #[cfg(not(unix))]
+    let _ = name;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_name_uses_instance_suffix_and_explicit_override() {
+        let config = IpcConfig {
+            name: None,
+            runtime_dir: Some(PathBuf::from("/run/user/1000")),
+            instance: Some("alpha".to_string()),
+            ..Default::default()
+        };
+        #[cfg(unix)]
+        assert_eq!(
+            config.resolve_name(),
+            "/run/user/1000/pagi_shmem_pipe.alpha"
+        );
+
+        let explicit = IpcConfig {
+            name: Some("/tmp/custom.sock".to_string()),
+            ..config
+        };
+        assert_eq!(explicit.resolve_name(), "/tmp/custom.sock");
+
+        let tcp = IpcConfig {
+            transport: IpcTransport::Tcp,
+            tcp_addr: Some("0.0.0.0:9000".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(tcp.endpoint().to_string(), "tcp://0.0.0.0:9000");
+        let tcp_by_name = IpcConfig {
+            name: Some("tcp://10.0.0.5:7878".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            tcp_by_name.endpoint(),
+            IpcEndpoint::Tcp("10.0.0.5:7878".to_string())
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn prepare_socket_path_unlinks_only_dead_sockets() {
+        use std::os::unix::fs::PermissionsExt;
+        use std::os::unix::net::UnixListener;
+
+        let dir = std::env::temp_dir().join(format!("pagi_ipc_cfg_test_{}", std::process::id()));
+        let name = dir.join("sock").to_string_lossy().into_owned();
+        prepare_socket_path(&name).expect("creates parent dir");
+        let dir_mode = std::fs::metadata(&dir).expect("dir").permissions().mode();
+        assert_eq!(dir_mode & 0o777, 0o700);
+
+        // Live server: refuse.
+        let live = UnixListener::bind(&name).expect("bind");
+        assert!(prepare_socket_path(&name).is_err());
+
+        // Server gone, file left behind: reclaim.
+        drop(live);
+        assert!(std::path::Path::new(&name).exists());
+        prepare_socket_path(&name).expect("stale socket removed");
+        assert!(!std::path::Path::new(&name).exists());
+
+        // Not a socket: leave it alone.
+        std::fs::write(&name, b"precious").expect("write");
+        assert!(prepare_socket_path(&name).is_err());
+        assert!(std::path::Path::new(&name).exists());
+
+        // A directory other users can write to is refused.
+        std::fs::remove_file(&name).expect("remove");
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o777)).expect("chmod");
+        assert!(prepare_socket_path(&name)
+            .expect_err("shared dir")
+            .contains("must be owned"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,239 @@
+//! Liveness tracking for agents connected over IPC.
+//!
+//! Agents send periodic [`IpcMessage::Heartbeat`](super::IpcMessage::Heartbeat)s (see
+//! [`IpcClient::start_heartbeats`](super::IpcClient::start_heartbeats)); any message from an
+//! agent counts as a sign of life. An agent that stays silent for more than
+//! `missed_threshold` heartbeat intervals is reported lost exactly once, and the core turns
+//! that into an `AgentLost` fact (see
+//! [`PAGICoreModel::sweep_liveness`](crate::PAGICoreModel::sweep_liveness)).
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::unix_now;
+
+/// Fact type recorded when an agent misses too many heartbeats.
+pub const AGENT_LOST_FACT_TYPE: &str = "AgentLost";
+
+/// What an agent reports it is doing in its heartbeat.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AgentRunState {
+    #[default]
+    Idle,
+    Busy,
+    /// The agent is exiting on purpose; it is dropped from the table instead of being
+    /// reported lost.
+    Stopping,
+}
+
+/// Whether the core still considers an agent alive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Liveness {
+    Alive,
+    Lost,
+}
+
+/// Heartbeat cadence and tolerance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeartbeatConfig {
+    /// How often agents are expected to send a heartbeat.
+    pub interval: Duration,
+    /// How many consecutive intervals may pass without a message before the agent is lost.
+    pub missed_threshold: u32,
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(5),
+            missed_threshold: 3,
+        }
+    }
+}
+
+impl HeartbeatConfig {
+    /// Silence longer than this marks an agent as lost.
+    pub fn timeout(&self) -> Duration {
+        self.interval * self.missed_threshold.max(1)
+    }
+}
+
+/// One row of the liveness table.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AgentLiveness {
+    pub agent_id: String,
+    /// Unix timestamp (seconds) of the last message received from the agent.
+    pub last_seen: u64,
+    pub current_task: Option<String>,
+    pub state: AgentRunState,
+    pub liveness: Liveness,
+    #[serde(skip)]
+    last_seen_at: Instant,
+}
+
+#[derive(Debug)]
+struct TrackerInner {
+    config: HeartbeatConfig,
+    agents: HashMap<String, AgentLiveness>,
+}
+
+/// Shared liveness table. Clones share the same state.
+#[derive(Debug, Clone)]
+pub struct LivenessTracker {
+    inner: Arc<Mutex<TrackerInner>>,
+}
+
+impl Default for LivenessTracker {
+    fn default() -> Self {
+        Self::new(HeartbeatConfig::default())
+    }
+}
+
+impl LivenessTracker {
+    pub fn new(config: HeartbeatConfig) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(TrackerInner {
+                config,
+                agents: HashMap::new(),
+            })),
+        }
+    }
+
+    pub fn config(&self) -> HeartbeatConfig {
+        self.lock().config
+    }
+
+    pub fn set_config(&self, config: HeartbeatConfig) {
+        self.lock().config = config;
+    }
+
+    /// Records a sign of life from `agent_id`.
+    ///
+    /// `heartbeat` carries the task/state fields of a heartbeat; other messages pass `None`
+    /// and only refresh `last_seen`.
+    pub fn observe(&self, agent_id: &str, heartbeat: Option<(Option<String>, AgentRunState)>) {
+        self.observe_at(agent_id, heartbeat, Instant::now());
+    }
+
+    pub fn observe_at(
+        &self,
+        agent_id: &str,
+        heartbeat: Option<(Option<String>, AgentRunState)>,
+        now: Instant,
+    ) {
+        let mut inner = self.lock();
+
+        if let Some((_, AgentRunState::Stopping)) = heartbeat {
+            inner.agents.remove(agent_id);
+            return;
+        }
+
+        let entry = inner
+            .agents
+            .entry(agent_id.to_string())
+            .or_insert_with(|| AgentLiveness {
+                agent_id: agent_id.to_string(),
+                last_seen: 0,
+                current_task: None,
+                state: AgentRunState::Idle,
+                liveness: Liveness::Alive,
+                last_seen_at: now,
+            });
+
+        entry.last_seen = unix_now();
+        entry.last_seen_at = now;
+        entry.liveness = Liveness::Alive;
+        if let Some((task, state)) = heartbeat {
+            entry.current_task = task;
+            entry.state = state;
+        }
+    }
+
+    /// Returns a copy of the whole table, sorted by agent id.
+    pub fn snapshot(&self) -> Vec<AgentLiveness> {
+        let mut rows: Vec<_> = self.lock().agents.values().cloned().collect();
+        rows.sort_by(|a, b| a.agent_id.cmp(&b.agent_id));
+        rows
+    }
+
+    pub fn get(&self, agent_id: &str) -> Option<AgentLiveness> {
+        self.lock().agents.get(agent_id).cloned()
+    }
+
+    /// Marks agents silent for longer than the configured timeout as lost and returns the
+    /// ones that transitioned on this call.
+    pub fn sweep(&self) -> Vec<AgentLiveness> {
+        self.sweep_at(Instant::now())
+    }
+
+    pub fn sweep_at(&self, now: Instant) -> Vec<AgentLiveness> {
+        let mut inner = self.lock();
+        let timeout = inner.config.timeout();
+
+        let mut lost = Vec::new();
+        for entry in inner.agents.values_mut() {
+            if entry.liveness == Liveness::Alive
+                && now.saturating_duration_since(entry.last_seen_at) > timeout
+            {
+                entry.liveness = Liveness::Lost;
+                lost.push(entry.clone());
+            }
+        }
+        lost.sort_by(|a, b| a.agent_id.cmp(&b.agent_id));
+        lost
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, TrackerInner> {
+        self.inner.lock().expect("liveness tracker poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sweep_reports_silent_agents_once_and_recovers_on_heartbeat() {
+        let tracker = LivenessTracker::new(HeartbeatConfig {
+            interval: Duration::from_secs(1),
+            missed_threshold: 3,
+        });
+        let t0 = Instant::now();
+        tracker.observe_at(
+            "SearchAgent",
+            Some((
+                Some("top anti-aging compounds".to_string()),
+                AgentRunState::Busy,
+            )),
+            t0,
+        );
+        tracker.observe_at("CalendarAgent", None, t0 + Duration::from_secs(2));
+
+        assert!(tracker.sweep_at(t0 + Duration::from_secs(3)).is_empty());
+
+        let lost = tracker.sweep_at(t0 + Duration::from_secs(4));
+        assert_eq!(lost.len(), 1);
+        assert_eq!(lost[0].agent_id, "SearchAgent");
+        assert_eq!(
+            lost[0].current_task.as_deref(),
+            Some("top anti-aging compounds")
+        );
+        assert!(tracker.sweep_at(t0 + Duration::from_secs(4)).is_empty());
+
+        tracker.observe_at("SearchAgent", None, t0 + Duration::from_secs(5));
+        assert_eq!(
+            tracker.get("SearchAgent").map(|a| a.liveness),
+            Some(Liveness::Alive)
+        );
+
+        tracker.observe_at(
+            "CalendarAgent",
+            Some((None, AgentRunState::Stopping)),
+            t0 + Duration::from_secs(5),
+        );
+        assert!(tracker.get("CalendarAgent").is_none());
+    }
+}
@@ -0,0 +1,35 @@
+//! Inter-process communication between the orchestrator core and agent processes.
+//!
+//! - [`protocol`]: the typed [`IpcMessage`] enum and its length-prefixed JSON framing.
+//! - [`bus`]: the tokio-based [`IpcBus`] server and the agent-side [`IpcClient`].
+//! - [`auth`]: token-based handshake authentication bound to an
+//!   [`AgentIdentity`](crate::AgentIdentity).
+//! - [`config`]: configurable, per-instance socket names and safe socket setup.
+//! - [`rpc`]: knowledge-base calls executed by the orchestrator on behalf of remote agents.
+//! - [`liveness`]: heartbeat-driven liveness table for connected agents.
+//! - [`transport`]: local-socket and TCP transports behind a common [`IpcEndpoint`].
+
+pub mod auth;
+pub mod bus;
+pub mod config;
+pub mod liveness;
+pub mod protocol;
+pub mod rpc;
+pub mod transport;
+
+pub use auth::{IpcAuthenticator, IpcTokenRegistry, PeerCredentials};
+pub use bus::{IpcBus, IpcClient, IpcEnvelope};
+pub use config::{
+    prepare_socket_path, restrict_socket_permissions, with_private_umask, IpcConfig, IpcTransport,
+};
+
+pub use liveness::{
+    AgentLiveness, AgentRunState, HeartbeatConfig, Liveness, LivenessTracker, AGENT_LOST_FACT_TYPE,
+};
+pub use rpc::{RpcCall, RpcReply};
+pub use transport::{IpcEndpoint, IpcReader, IpcWriter};
+
+pub use protocol::{
+    read_message, read_message_async, write_message, write_message_async, FrameDecoder, IpcMessage,
+    MAX_FRAME_LEN,
+};
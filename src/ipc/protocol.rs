@@ -0,0 +1,321 @@
+//! Typed IPC message protocol.
+//!
+//! Every message is a single [`IpcMessage`] serialized as JSON and prefixed with its length
+//! as a big-endian `u32`:
+//!
+//! ```text
+//! +----------------+---------------------------+
+//! | len: u32 (BE)  | serde_json(IpcMessage)    |
+//! +----------------+---------------------------+
+//! ```
+//!
+//! The same helpers are used on both ends of the socket: blocking `std::io` streams (e.g.
+//! `interprocess::local_socket::LocalSocketStream`) use [`read_message`] / [`write_message`],
+//! async streams use the `_async` variants, and callers that receive bytes in arbitrary
+//! chunks can feed a [`FrameDecoder`].
+
+use std::io::{self, Read, Write};
+
+use futures::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use serde::{Deserialize, Serialize};
+
+use super::liveness::AgentRunState;
+use super::rpc::{RpcCall, RpcReply};
+use crate::approval::ApprovalStatus;
+use crate::auth::AuthScope;
+use crate::stream::{AgentEvent, AgentEventEnvelope};
+
+/// Upper bound on a single frame's payload, to keep a corrupt or hostile peer from making
+/// us allocate unbounded buffers.
+pub const MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+
+const LEN_PREFIX: usize = 4;
+
+/// A message exchanged over the IPC channel.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "payload")]
+pub enum IpcMessage {
+    /// First message sent by a client after connecting.
+    Hello {
+        agent_id: String,
+        /// Authentication token issued by the core, if the server requires one.
+        token: Option<String>,
+    },
+    /// Free-form, human-readable status text (e.g. "I'm starting…").
+    Status { agent_id: String, message: String },
+    /// A chunk of an agent's streamed result (see [`AgentEvent::PartialResult`]).
+    PartialResult { agent_id: String, chunk: String },
+    /// Progress report; `percent` is optional for agents that can't estimate it.
+    Progress {
+        agent_id: String,
+        percent: Option<u8>,
+        message: String,
+    },
+    /// Notification that a fact was written to the knowledge base.
+    FactRecorded {
+        agent_id: String,
+        fact_type: String,
+        timestamp: u64,
+    },
+    /// Liveness signal, sent periodically by connected agents.
+    Heartbeat {
+        agent_id: String,
+        #[serde(default)]
+        current_task: Option<String>,
+        #[serde(default)]
+        state: AgentRunState,
+    },
+    /// An error reported by either side.
+    Error { message: String },
+    /// Final result of a task.
+    Done { agent_id: String, result: String },
+    /// RPC call from an agent to the core (see [`super::rpc`]).
+    Request { id: u64, call: RpcCall },
+    /// The core's answer to the [`IpcMessage::Request`] with the same `id`.
+    Response {
+        id: u64,
+        result: Result<RpcReply, String>,
+    },
+    /// A gated action is waiting for an approver (see [`crate::approval`]).
+    ApprovalRequested {
+        request_id: String,
+        requester: String,
+        scope: AuthScope,
+        resource: String,
+        summary: String,
+        expires_at: u64,
+    },
+    /// A pending approval was approved, rejected or timed out.
+    ApprovalResolved {
+        request_id: String,
+        requester: String,
+        status: ApprovalStatus,
+    },
+}
+
+impl IpcMessage {
+    /// The agent a message claims to come from, for variants that carry one.
+    pub fn agent_id(&self) -> Option<&str> {
+        match self {
+            IpcMessage::Hello { agent_id, .. }
+            | IpcMessage::Status { agent_id, .. }
+            | IpcMessage::PartialResult { agent_id, .. }
+            | IpcMessage::Progress { agent_id, .. }
+            | IpcMessage::FactRecorded { agent_id, .. }
+            | IpcMessage::Heartbeat { agent_id, .. }
+            | IpcMessage::Done { agent_id, .. } => Some(agent_id),
+            IpcMessage::Error { .. }
+            | IpcMessage::Request { .. }
+            | IpcMessage::Response { .. }
+            | IpcMessage::ApprovalRequested { .. }
+            | IpcMessage::ApprovalResolved { .. } => None,
+        }
+    }
+
+    /// Maps a streamed agent event onto the wire protocol so executors can forward
+    /// [`AgentEvent`]s to IPC subscribers.
+    pub fn from_agent_event(envelope: &AgentEventEnvelope) -> Self {
+        let agent_id = envelope.agent_id.clone();
+        match &envelope.event {
+            AgentEvent::Progress { percent, message } => IpcMessage::Progress {
+                agent_id,
+                percent: *percent,
+                message: message.clone(),
+            },
+            AgentEvent::PartialResult(chunk) => IpcMessage::PartialResult {
+                agent_id,
+                chunk: chunk.clone(),
+            },
+            AgentEvent::FactEmitted(fact) => IpcMessage::FactRecorded {
+                agent_id,
+                fact_type: fact.fact_type.clone(),
+                timestamp: fact.timestamp,
+            },
+            AgentEvent::Log { level, message } => IpcMessage::Status {
+                agent_id,
+                message: format!("[{level:?}] {message}"),
+            },
+            AgentEvent::Error(message) => IpcMessage::Error {
+                message: format!("{agent_id}: {message}"),
+            },
+        }
+    }
+
+    /// Serializes the message into a complete frame (length prefix + JSON body).
+    pub fn encode_frame(&self) -> io::Result<Vec<u8>> {
+        let body = serde_json::to_vec(self).map_err(invalid_data)?;
+        if body.len() > MAX_FRAME_LEN {
+            return Err(invalid_data(format!(
+                "IPC frame of {} bytes exceeds limit of {MAX_FRAME_LEN}",
+                body.len()
+            )));
+        }
+
+        let mut frame = Vec::with_capacity(LEN_PREFIX + body.len());
+        frame.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&body);
+        Ok(frame)
+    }
+}
+
+fn invalid_data(e: impl std::fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+fn checked_len(prefix: [u8; LEN_PREFIX]) -> io::Result<usize> {
+    let len = u32::from_be_bytes(prefix) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(invalid_data(format!(
+            "IPC frame of {len} bytes exceeds limit of {MAX_FRAME_LEN}"
+        )));
+    }
+    Ok(len)
+}
+
+fn decode_body(body: &[u8]) -> io::Result<IpcMessage> {
+    serde_json::from_slice(body).map_err(invalid_data)
+}
+
+/// Writes one framed message to a blocking writer.
+pub fn write_message<W: Write>(writer: &mut W, msg: &IpcMessage) -> io::Result<()> {
+    writer.write_all(&msg.encode_frame()?)?;
+    writer.flush()
+}
+
+/// Reads one framed message from a blocking reader.
+///
+/// Returns `Ok(None)` on a clean end-of-stream (the peer closed between frames).
+pub fn read_message<R: Read>(reader: &mut R) -> io::Result<Option<IpcMessage>> {
+    let mut prefix = [0u8; LEN_PREFIX];
+    match reader.read_exact(&mut prefix) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+
+    let mut body = vec![0u8; checked_len(prefix)?];
+    reader.read_exact(&mut body)?;
+    decode_body(&body).map(Some)
+}
+
+/// Async counterpart of [`write_message`].
+pub async fn write_message_async<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    msg: &IpcMessage,
+) -> io::Result<()> {
+    writer.write_all(&msg.encode_frame()?).await?;
+    writer.flush().await
+}
+
+/// Async counterpart of [`read_message`].
+pub async fn read_message_async<R: AsyncRead + Unpin>(
+    reader: &mut R,
+) -> io::Result<Option<IpcMessage>> {
+    let mut prefix = [0u8; LEN_PREFIX];
+    match reader.read_exact(&mut prefix).await {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+
+    let mut body = vec![0u8; checked_len(prefix)?];
+    reader.read_exact(&mut body).await?;
+    decode_body(&body).map(Some)
+}
+
+/// Incremental decoder for callers that receive bytes in arbitrary chunks.
+#[derive(Debug, Default)]
+pub struct FrameDecoder {
+    buf: Vec<u8>,
+}
+
+impl FrameDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends raw bytes received from the peer.
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Pops the next complete message, or `Ok(None)` if more bytes are needed.
+    pub fn next_message(&mut self) -> io::Result<Option<IpcMessage>> {
+        if self.buf.len() < LEN_PREFIX {
+            return Ok(None);
+        }
+
+        let mut prefix = [0u8; LEN_PREFIX];
+        prefix.copy_from_slice(&self.buf[..LEN_PREFIX]);
+        let len = checked_len(prefix)?;
+        if self.buf.len() < LEN_PREFIX + len {
+            return Ok(None);
+        }
+
+        let frame: Vec<u8> = self.buf.drain(..LEN_PREFIX + len).collect();
+        decode_body(&frame[LEN_PREFIX..]).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocking_roundtrip_preserves_messages() {
+        let msgs = vec![
+            IpcMessage::Hello {
+                agent_id: "SearchAgent".to_string(),
+                token: None,
+            },
+            IpcMessage::Status {
+                agent_id: "SearchAgent".to_string(),
+                message: "I'm starting…".to_string(),
+            },
+            IpcMessage::PartialResult {
+                agent_id: "SearchAgent".to_string(),
+                chunk: "hello ".to_string(),
+            },
+            IpcMessage::Done {
+                agent_id: "SearchAgent".to_string(),
+                result: "ok".to_string(),
+            },
+        ];
+
+        let mut wire = Vec::new();
+        for m in &msgs {
+            write_message(&mut wire, m).expect("write");
+        }
+
+        let mut cursor = io::Cursor::new(wire);
+        let mut decoded = Vec::new();
+        while let Some(m) = read_message(&mut cursor).expect("read") {
+            decoded.push(m);
+        }
+        assert_eq!(decoded, msgs);
+    }
+
+    #[test]
+    fn frame_decoder_handles_split_frames_and_rejects_oversized_ones() {
+        let frame = IpcMessage::Heartbeat {
+            agent_id: "CalendarAgent".to_string(),
+            current_task: None,
+            state: AgentRunState::Idle,
+        }
+        .encode_frame()
+        .expect("encode");
+
+        let mut decoder = FrameDecoder::new();
+        decoder.push(&frame[..3]);
+        assert!(decoder.next_message().expect("partial").is_none());
+        decoder.push(&frame[3..]);
+        assert!(matches!(
+            decoder.next_message().expect("complete"),
+            Some(IpcMessage::Heartbeat { .. })
+        ));
+
+        let mut hostile = FrameDecoder::new();
+        hostile.push(&u32::MAX.to_be_bytes());
+        assert!(hostile.next_message().is_err());
+    }
+}
@@ -0,0 +1,72 @@
+//! Request/response RPC over the IPC channel.
+//!
+//! Sled only lets one process hold the knowledge base, so agents running out of process
+//! can't reopen it with [`PAGICoreModel::from_db`]. Instead they send an
+//! [`IpcMessage::Request`](super::IpcMessage::Request) to the orchestrator's
+//! [`IpcBus`](super::IpcBus), which executes the call against its own core on behalf of the
+//! identity verified during the handshake and answers with a matching
+//! [`IpcMessage::Response`](super::IpcMessage::Response). Authorization is enforced by the
+//! core exactly as for in-process callers.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{AgentFact, AgentIdentity, PAGICoreModel};
+
+/// A knowledge-base operation an agent can invoke remotely.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "method", content = "params")]
+pub enum RpcCall {
+    /// [`PAGICoreModel::record_fact`]. The fact is recorded under the caller's verified id,
+    /// whatever `agent_id` it carries.
+    RecordFact(AgentFact),
+    /// [`PAGICoreModel::retrieve_facts_by_timestamp`].
+    RetrieveFactsByTimestamp { start_ts: u64 },
+    /// [`PAGICoreModel::apply_rules_to_facts`] over caller-supplied facts.
+    ApplyRules { facts: Vec<AgentFact> },
+    /// [`PAGICoreModel::symbolic_directives`]: rules applied to the whole knowledge base.
+    SymbolicDirectives,
+    /// [`PAGICoreModel::approve_action`].
+    ApproveAction { request_id: String },
+    /// [`PAGICoreModel::reject_action`].
+    RejectAction { request_id: String, reason: String },
+}
+
+/// Successful result of an [`RpcCall`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "value")]
+pub enum RpcReply {
+    Ack,
+    Facts(Vec<AgentFact>),
+    Directives(Vec<String>),
+}
+
+/// Executes `call` against `core` as `identity`.
+pub fn dispatch(
+    core: &PAGICoreModel,
+    identity: &AgentIdentity,
+    call: RpcCall,
+) -> Result<RpcReply, String> {
+    match call {
+        RpcCall::RecordFact(fact) => {
+            // Remote agents can't attribute facts to someone else.
+            let fact = AgentFact {
+                agent_id: identity.id.clone(),
+                ..fact
+            };
+            core.record_fact(identity, fact).map(|()| RpcReply::Ack)
+        }
+        RpcCall::RetrieveFactsByTimestamp { start_ts } => core
+            .retrieve_facts_by_timestamp(identity, u128::from(start_ts))
+            .map(RpcReply::Facts),
+        RpcCall::ApplyRules { facts } => core
+            .authorized_directives(identity, core.apply_rules_to_facts(facts))
+            .map(RpcReply::Directives),
+        RpcCall::SymbolicDirectives => core.symbolic_directives(identity).map(RpcReply::Directives),
+        RpcCall::ApproveAction { request_id } => core
+            .approve_action(identity, &request_id)
+            .map(|_| RpcReply::Ack),
+        RpcCall::RejectAction { request_id, reason } => core
+            .reject_action(identity, &request_id, &reason)
+            .map(|_| RpcReply::Ack),
+    }
+}
@@ -0,0 +1,150 @@
+//! Byte-stream transports the IPC protocol can run over.
+//!
+//! [`IpcEndpoint`] names where a bus listens: a local socket (the default, same-host only)
+//! or a TCP address for agents in another container of the same pod. Endpoints are written
+//! as plain strings so they can be handed to agents the same way a socket path is: anything
+//! starting with `tcp://` is TCP, everything else is a local socket name.
+//!
+//! Both transports yield the same boxed reader/writer halves, so framing, the `Hello`
+//! handshake and RPC authorization in [`bus`](super::bus) are shared. TCP peers carry no OS
+//! credentials, so a TCP bus always requires token authentication.
+
+use std::fmt;
+use std::io;
+
+use futures::io::{AsyncRead, AsyncWrite};
+use interprocess::local_socket::tokio::{
+    LocalSocketListener as AsyncLocalSocketListener, LocalSocketStream as AsyncLocalSocketStream,
+};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::compat::{TokioAsyncReadCompatExt, TokioAsyncWriteCompatExt};
+
+use super::auth::PeerCredentials;
+use super::config::{prepare_socket_path, restrict_socket_permissions, with_private_umask};
+
+/// Scheme prefix marking a TCP endpoint.
+pub const TCP_SCHEME: &str = "tcp://";
+
+/// Read half of a transport connection.
+pub type IpcReader = Box<dyn AsyncRead + Send + Unpin>;
+
+/// Write half of a transport connection.
+pub type IpcWriter = Box<dyn AsyncWrite + Send + Unpin>;
+
+/// Where an IPC bus listens / a client connects.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IpcEndpoint {
+    /// Local socket name (Unix domain socket path or Windows named pipe).
+    LocalSocket(String),
+    /// TCP `host:port`.
+    Tcp(String),
+}
+
+impl IpcEndpoint {
+    /// Parses `tcp://host:port` as TCP and anything else as a local socket name.
+    pub fn parse(s: &str) -> Self {
+        match s.strip_prefix(TCP_SCHEME) {
+            Some(addr) => IpcEndpoint::Tcp(addr.to_string()),
+            None => IpcEndpoint::LocalSocket(s.to_string()),
+        }
+    }
+
+    pub fn is_tcp(&self) -> bool {
+        matches!(self, IpcEndpoint::Tcp(_))
+    }
+}
+
+impl fmt::Display for IpcEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IpcEndpoint::LocalSocket(name) => f.write_str(name),
+            IpcEndpoint::Tcp(addr) => write!(f, "{TCP_SCHEME}{addr}"),
+        }
+    }
+}
+
+/// A bound listener for either transport.
+pub(crate) enum IpcListener {
+    LocalSocket(AsyncLocalSocketListener),
+    Tcp(TcpListener),
+}
+
+impl IpcListener {
+    /// Binds `endpoint`, returning the listener and the endpoint actually bound (a TCP
+    /// port of `0` is replaced with the port the OS picked). Must be called from within a
+    /// tokio runtime.
+    pub(crate) fn bind(endpoint: &IpcEndpoint) -> Result<(Self, IpcEndpoint), String> {
+        match endpoint {
+            IpcEndpoint::LocalSocket(name) => {
+                prepare_socket_path(name)?;
+                let listener = with_private_umask(|| AsyncLocalSocketListener::bind(name.as_str()))
+                    .map_err(|e| format!("Failed to bind IPC bus ({name}): {e}"))?;
+                restrict_socket_permissions(name)?;
+                Ok((IpcListener::LocalSocket(listener), endpoint.clone()))
+            }
+            IpcEndpoint::Tcp(addr) => {
+                let bind_err = |e: io::Error| format!("Failed to bind IPC bus ({endpoint}): {e}");
+                let std_listener = std::net::TcpListener::bind(addr.as_str()).map_err(bind_err)?;
+                std_listener.set_nonblocking(true).map_err(bind_err)?;
+                let local = std_listener.local_addr().map_err(bind_err)?;
+                let listener = TcpListener::from_std(std_listener).map_err(bind_err)?;
+                Ok((
+                    IpcListener::Tcp(listener),
+                    IpcEndpoint::Tcp(local.to_string()),
+                ))
+            }
+        }
+    }
+
+    /// Accepts one connection and splits it into boxed halves.
+    pub(crate) async fn accept(&self) -> io::Result<(IpcReader, IpcWriter, PeerCredentials)> {
+        match self {
+            IpcListener::LocalSocket(listener) => {
+                let stream = listener.accept().await?;
+                #[cfg(unix)]
+                let peer = {
+                    use std::os::unix::io::AsRawFd;
+                    PeerCredentials::from_fd(stream.as_raw_fd())
+                };
+                #[cfg(not(unix))]
+                let peer = PeerCredentials::default();
+
+                let (r, w) = stream.into_split();
+                Ok((Box::new(r), Box::new(w), peer))
+            }
+            IpcListener::Tcp(listener) => {
+                let (stream, _addr) = listener.accept().await?;
+                stream.set_nodelay(true)?;
+                let (r, w) = stream.into_split();
+                Ok((
+                    Box::new(r.compat()),
+                    Box::new(w.compat_write()),
+                    PeerCredentials::default(),
+                ))
+            }
+        }
+    }
+}
+
+/// Opens a client connection to `endpoint`.
+pub(crate) async fn connect(endpoint: &IpcEndpoint) -> Result<(IpcReader, IpcWriter), String> {
+    match endpoint {
+        IpcEndpoint::LocalSocket(name) => {
+            let stream = AsyncLocalSocketStream::connect(name.as_str())
+                .await
+                .map_err(|e| format!("Failed to connect to IPC bus ({endpoint}): {e}"))?;
+            let (r, w) = stream.into_split();
+            Ok((Box::new(r), Box::new(w)))
+        }
+        IpcEndpoint::Tcp(addr) => {
+            let stream = TcpStream::connect(addr.as_str())
+                .await
+                .map_err(|e| format!("Failed to connect to IPC bus ({endpoint}): {e}"))?;
+            stream
+                .set_nodelay(true)
+                .map_err(|e| format!("Failed to configure IPC connection ({endpoint}): {e}"))?;
+            let (r, w) = stream.into_split();
+            Ok((Box::new(r.compat()), Box::new(w.compat_write())))
+        }
+    }
+}
@@ -4,47 +4,89 @@
 //! - [`Task`]: a minimal task envelope used by the planner to dispatch work to agents.
 //! - [`BaseAgent`]: the async contract all agents must implement.
 //! - [`PAGICoreModel`]: a stub planner that turns a user prompt into a task list.
+//! - [`AuthScope`] / [`AuthorizationGatekeeper`]: least-privilege, optionally resource-scoped
+//!   authorization.
+//! - [`StreamingAgent`]: an optional agent contract that streams [`AgentEvent`]s.
+//! - [`IpcMessage`]: the typed, length-prefixed IPC protocol spoken over the socket named by
+//!   [`IpcConfig`].
 
 use async_trait::async_trait;
 use interprocess::local_socket::LocalSocketListener;
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::borrow::Cow;
+use std::sync::{Arc, RwLock};
 use tracing::{event, Level};
 
+pub mod auth;
+pub use auth::{AgentIdentity, AuthScope, AuthorizationGatekeeper, Resource, ResourcePattern};
+
+pub mod approval;
+pub use approval::{ApprovalConfig, ApprovalQueue, ApprovalRequest, ApprovalStatus, TimeoutPolicy};
+
+pub mod audit;
+pub use audit::{AuditEvent, AuditHead, AuditLog, AuditOutcome, AuditRecord, AuditViolation};
+
+pub mod credentials;
+pub use credentials::{CredentialAuthority, CredentialClaims};
+
+pub mod delegation;
+pub use delegation::{
+    Delegation, DelegationOrigin, DelegationStore, ElevationRequest, ElevationStatus,
+};
+
+pub mod embedding;
+pub use embedding::{Embedder, FactFilter, HashingEmbedder, ScoredFact, VectorIndex};
+
 pub mod facts;
+pub mod fulltext;
+pub use fulltext::{FullTextIndex, FullTextQuery};
+
+pub mod graph;
+pub use graph::{Direction, Entity, GraphEdge, GraphNode, KnowledgeGraph, Subgraph, Triple};
+pub mod policy;
+pub use policy::{AuthDecision, DecisionReason, Effect, PolicyCondition, PolicyEngine, PolicyStatement};
 pub use facts::{FactType as Fact, FactType, MultimodalFact, RoboticsAction, Vector3D};
 
-// === Authorization / Identity (PoLP) ===
+pub mod quota;
+pub use quota::{
+    QuotaCost, QuotaExceeded, QuotaLimit, QuotaManager, QuotaRule, QuotaUnit, QuotaUsage,
+};
 
-#[derive(Debug, PartialEq, Eq, Clone)]
-pub enum AuthScope {
-    ReadFacts,
-    WriteFacts,
-    WritePolicy,
-    ExternalAPI,
-    RoboticsAction,
-}
+pub mod ipc;
+pub use ipc::{
+    AgentLiveness, HeartbeatConfig, IpcBus, IpcClient, IpcConfig, IpcEndpoint, IpcEnvelope,
+    IpcMessage, IpcTokenRegistry, LivenessTracker,
+};
 
-#[derive(Debug, Clone)]
-pub struct AgentIdentity {
-    pub id: String,
-    pub scopes: Vec<AuthScope>,
-}
+pub mod reflection;
+pub use reflection::{
+    ExecutionResult, ExecutionStatus, Experiment, ExperimentArm, ExperimentConfig,
+    ExperimentDecision, ExperimentReport, ExperimentStore, ExperimentTag, GeneratorConfig,
+    Reflection, ReflectionGenerator, ReflectionOutcome, ReflectionStatus, ReflectionStore,
+};
 
-pub struct AuthorizationGatekeeper;
+pub mod retention;
+pub use retention::{
+    CompactionReport, ExpiryAction, RetentionPolicy, RetentionStats, RetentionStore,
+};
 
-impl AuthorizationGatekeeper {
-    pub fn can_access(identity: &AgentIdentity, required: AuthScope) -> Result<(), String> {
-        if identity.scopes.contains(&required) {
-            Ok(())
-        } else {
-            Err(format!(
-                "Permission denied: agent '{}' missing required scope {:?}",
-                identity.id, required
-            ))
-        }
-    }
-}
+pub mod retrieval;
+pub use retrieval::{PlanningContext, RetrievalQuery, RetrievalWeights, RetrievedFact};
+
+pub mod scheduler;
+pub use scheduler::{
+    reflection_job, retention_job, rule_evaluation_job, CronSchedule, JobContext, JobReport,
+    Schedule, ScheduledJob, Scheduler, SchedulerHandle,
+};
+
+pub mod transform;
+pub use transform::{apply_transforms, PlanTransform};
+
+pub mod stream;
+pub use stream::{
+    execute_streaming, AgentEvent, AgentEventEnvelope, AgentEventStream, AgentOutcome, LogLevel,
+    StreamingAdapter, StreamingAgent,
+};
 
 // Re-export for downstream crates so agents can reopen the DB without declaring a direct
 // dependency on `sled`.
@@ -55,6 +97,29 @@ pub const KNOWLEDGE_BASE_PATH: &str = "pagi_knowledge_base";
 
 const FACTS_TREE: &str = "facts";
 
+/// Lowercase hex encoding of `bytes`.
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+pub(crate) fn from_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Current unix time in seconds, the unit used by [`AgentFact::timestamp`].
+pub(crate) fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
 /// A unit of work created by the core planning model.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Task {
@@ -68,7 +133,7 @@ pub struct Task {
 pub type Plan = Vec<Task>;
 
 /// A persistent, structured fact produced by an agent.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AgentFact {
     pub agent_id: String,
     pub timestamp: u64,
@@ -77,7 +142,10 @@ pub struct AgentFact {
 }
 
 /// A reflective, self-improvement directive produced by the system.
-#[derive(Debug, Serialize, Deserialize)]
+///
+/// Legacy reflections are stored as JSON in an [`AgentFact`] of type `ReflectionFact`; new
+/// ones go through the [`reflection`] store.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ReflectionFact {
     pub target_agent: String,
     pub critique: String,
@@ -90,9 +158,64 @@ pub struct PAGIRule {
     pub id: String,
     pub condition_fact_type: String,
     pub condition_keyword: String,
+    /// Optional [`FullTextQuery`] the fact content must also match, e.g.
+    /// `"phishing" AND NOT drill`. When the core applies its rules to the whole knowledge base,
+    /// such rules only look at the facts the full-text index matches; `apply_rules_to_facts`
+    /// checks the facts it is given directly.
+    #[serde(default)]
+    pub condition_query: Option<String>,
     pub action_directive: String,
 }
 
+impl PAGIRule {
+    pub fn new(
+        id: impl Into<String>,
+        condition_fact_type: impl Into<String>,
+        condition_keyword: impl Into<String>,
+        action_directive: impl Into<String>,
+    ) -> Self {
+        Self {
+            id: id.into(),
+            condition_fact_type: condition_fact_type.into(),
+            condition_keyword: condition_keyword.into(),
+            condition_query: None,
+            action_directive: action_directive.into(),
+        }
+    }
+
+    /// Also requires the fact content to match `query` (see [`FullTextQuery::parse`]); it is
+    /// validated by [`PAGICoreModel::add_rule`].
+    pub fn with_query(mut self, query: impl Into<String>) -> Self {
+        self.condition_query = Some(query.into());
+        self
+    }
+
+    /// `condition_query`, parsed.
+    fn parse_query(&self) -> Result<Option<FullTextQuery>, String> {
+        self.condition_query
+            .as_deref()
+            .map(FullTextQuery::parse)
+            .transpose()
+            .map_err(|e| format!("Rule '{}' has an invalid condition_query: {e}", self.id))
+    }
+
+    /// Whether `fact` satisfies the rule's condition. Parses `condition_query` on every call;
+    /// the core keeps its rules' queries parsed instead.
+    pub fn matches(&self, fact: &AgentFact) -> bool {
+        match self.parse_query() {
+            Ok(query) => self.matches_with(fact, query.as_ref()),
+            Err(_) => false,
+        }
+    }
+
+    /// [`PAGIRule::matches`] with `condition_query` already parsed as `query`.
+    fn matches_with(&self, fact: &AgentFact, query: Option<&FullTextQuery>) -> bool {
+        fact.fact_type == self.condition_fact_type
+            && fact.content.contains(&self.condition_keyword)
+            && query.is_none_or(|q| q.matches_text(&fact.content))
+    }
+}
+
 /// The base contract for all PAGI agents.
 ///
 /// Agents accept an input payload (commonly JSON) and return a structured output string
@@ -104,16 +227,15 @@ pub trait BaseAgent: Send + Sync {
         -> String;
 }
 
-/// Default IPC channel name (local socket / pipe).
+/// Legacy fixed IPC channel name (local socket / pipe).
 ///
-/// The orchestrator initializes a local socket listener using this name, and agents connect
-/// to it to send real-time status updates during execution.
-///
-/// On Unix we use a filesystem-backed socket in `/tmp` so separate processes can discover it.
+/// The core no longer binds this path by default: names now come from [`IpcConfig`] so
+/// several orchestrators can share a host. Set `PAGI_IPC_NAME` to this value to restore the
+/// old behaviour.
 #[cfg(unix)]
 pub const PAGI_IPC_NAME: &str = "/tmp/pagi_shmem_pipe";
 
-/// Default IPC channel name (non-Unix platforms).
+/// Legacy fixed IPC channel name (non-Unix platforms).
 #[cfg(not(unix))]
 pub const PAGI_IPC_NAME: &str = "pagi_shmem_pipe";
 
@@ -142,6 +264,60 @@ pub struct PAGICoreModel {
 
     /// Symbolic rule set used by the inference engine.
     rules: Vec<PAGIRule>,
+
+    /// Parsed `condition_query` of each rule that has one, by rule id.
+    rule_queries: std::collections::HashMap<String, FullTextQuery>,
+
+    /// Tokens issued to agents for authenticating IPC connections.
+    ipc_tokens: IpcTokenRegistry,
+
+    /// Heartbeat-driven liveness table for agents connected over IPC.
+    liveness: LivenessTracker,
+
+    /// Scope implications applied by [`PAGICoreModel::check_authorization`].
+    gatekeeper: RwLock<AuthorizationGatekeeper>,
+
+    /// Allow/deny statements stored in the `policies` tree.
+    policies: PolicyEngine,
+
+    /// Mints and verifies signed agent credentials.
+    credentials: CredentialAuthority,
+
+    /// Whether gated calls reject identities without a valid credential.
+    require_credentials: bool,
+
+    /// Hash-chained record of authorization decisions and KB mutations.
+    audit: AuditLog,
+
+    /// Scopes lent between identities, and elevation requests.
+    delegations: DelegationStore,
+
+    /// Token-bucket limits charged after authorization succeeds.
+    quotas: QuotaManager,
+
+    /// Human-in-the-loop approvals for gated scopes and fact types (off by default).
+    approvals: ApprovalQueue,
+
+    /// Typed reflections and their lifecycle.
+    reflections: ReflectionStore,
+
+    /// A/B experiments on proposed reflections.
+    experiments: ExperimentStore,
+
+    /// Embeds facts as they are written.
+    embedder: RwLock<Arc<dyn Embedder>>,
+
+    /// Fact vectors and their ANN graph.
+    vectors: VectorIndex,
+
+    /// Inverted index over fact content.
+    fulltext: FullTextIndex,
+
+    /// Entities and relations extracted from facts.
+    graph: KnowledgeGraph,
+
+    /// Per-fact-type retention policies and compaction totals.
+    retention: RetentionStore,
 }
 
 impl Drop for PAGICoreModel {
@@ -187,179 +363,1327 @@ impl PAGICoreModel {
         identity: &AgentIdentity,
         scope: AuthScope,
     ) -> Result<(), String> {
-        let res = AuthorizationGatekeeper::can_access(identity, scope.clone());
+        self.check_authorization_on(identity, scope, &Resource::default())
+    }
 
-        if let Err(ref e) = res {
-            event!(
-                Level::WARN,
-                identity_id = %identity.id,
-                required_scope = ?scope,
-                error = %e,
-                "Authorization denied"
-            );
-        }
+    /// Checks `scope` against a specific resource, honouring resource-scoped grants and the
+    /// stored policies (explicit deny wins; see [`policy`]).
+    ///
+    /// If neither allows the request, the identity's active delegations are tried one at a
+    /// time and the first that does is charged a use (see [`delegation`]).
+    #[tracing::instrument(
+        level = "trace",
+        skip(self, identity, resource),
+        fields(identity_id = %identity.id, required_scope = ?scope, resource = %resource)
+    )]
+    pub fn check_authorization_on(
+        &self,
+        identity: &AgentIdentity,
+        scope: AuthScope,
+        resource: &Resource,
+    ) -> Result<(), String> {
+        self.check_authorization_metered(identity, scope, resource, QuotaCost::request())
+    }
 
+    /// [`PAGICoreModel::check_authorization_on`] for a request costing `cost` against the
+    /// configured quotas (e.g. the size of a KB write). Quota failures are errors starting
+    /// with [`quota::QUOTA_EXCEEDED_PREFIX`].
+    ///
+    /// Scopes gated by the [`ApprovalConfig`] additionally need a signed-off approval
+    /// request; until then the error starts with [`approval::APPROVAL_PENDING_PREFIX`].
+    pub fn check_authorization_metered(
+        &self,
+        identity: &AgentIdentity,
+        scope: AuthScope,
+        resource: &Resource,
+        cost: QuotaCost,
+    ) -> Result<(), String> {
+        let now = unix_now();
+        let mut used = None;
+        let res = self.verified_identity(identity).and_then(|identity| {
+            let gatekeeper = self.gatekeeper();
+            let mut decision =
+                self.policies
+                    .evaluate(&gatekeeper, &identity, &scope, resource, now, true);
+            if decision.reason == DecisionReason::NoMatch {
+                let delegated = self.use_delegation(&identity, now, |elevated| {
+                    let decision =
+                        self.policies
+                            .evaluate(&gatekeeper, elevated, &scope, resource, now, true);
+                    decision.allowed.then_some(decision)
+                });
+                if let Some((d, delegated)) = delegated {
+                    decision = delegated;
+                    used = Some(d);
+                }
+            }
+            Self::decision_to_result(&gatekeeper, &identity, &scope, resource, &decision)?;
+            // Requests still waiting for approval don't use up quota.
+            self.approval_gate(&identity.id, &scope, resource)?;
+            self.quotas
+                .charge(&identity.id, &scope, cost)
+                .map_err(|e| e.to_string())
+        });
+        Self::log_denial(identity, &scope, &res);
+        self.audit_decision(identity, &scope, resource, &res);
+        if let Some(d) = used {
+            self.audit_delegation_use(&identity.id, scope, resource.clone(), &d);
+        }
         res
     }
 
-    fn default_rules() -> Vec<PAGIRule> {
-        vec![
-            PAGIRule {
-                id: "rule_failure_rerun_deep".to_string(),
-                condition_fact_type: "AnalysisResult".to_string(),
-                condition_keyword: "Failure".to_string(),
-                action_directive: "Rerun: Deep Search".to_string(),
-            },
-            PAGIRule {
-                id: "rule_cyber_alert_triage".to_string(),
-                condition_fact_type: "AnalysisResult".to_string(),
-                condition_keyword: "CYBER_ALERT".to_string(),
-                action_directive: "TASK: CybersecurityAgent, INPUT: Triage alert".to_string(),
-            },
-        ]
+    fn audit_delegation_use(
+        &self,
+        identity_id: &str,
+        scope: AuthScope,
+        resource: Resource,
+        d: &Delegation,
+    ) {
+        self.append_audit(AuditEvent {
+            identity_id: identity_id.to_string(),
+            action: "use_delegation".to_string(),
+            scope: Some(scope),
+            resource,
+            outcome: AuditOutcome::Succeeded,
+            detail: Some(format!(
+                "delegation '{}' from '{}' ({} use(s))",
+                d.id, d.delegator, d.uses
+            )),
+        });
     }
 
-    /// Constructs the core model and opens/creates the persistent knowledge base.
+    /// Checks that `identity` holds `scope` on at least some resource, through a scope, an
+    /// active delegation or an `Allow` policy. Results must then be filtered per resource
+    /// with [`PAGICoreModel::is_authorized_on`].
     ///
-    /// Note: this follows the prompt's "conceptual stand-in" approach and uses a simple
-    /// `unwrap`-style initialization.
-    pub fn new() -> Self {
-        let db = sled::open(KNOWLEDGE_BASE_PATH).expect("failed to open sled knowledge base");
-        Self {
-            ipc_listener: None,
-            ipc_name: PAGI_IPC_NAME.to_string(),
-            knowledge_base: db,
-            ipc_initialized: false,
-            rules: Self::default_rules(),
-        }
+    /// A use-limited delegation is charged one use if only it allows `scope`.
+    pub fn check_any_authorization(
+        &self,
+        identity: &AgentIdentity,
+        scope: AuthScope,
+    ) -> Result<(), String> {
+        self.authorize_read(identity, scope).map(|_| ())
     }
 
-    /// Creates a core model from an already-open Sled DB handle.
-    ///
-    /// Useful for agents that reopen the DB independently (simulating separate processes).
-    pub fn from_db(db: sled::Db) -> Self {
-        Self {
-            ipc_listener: None,
-            ipc_name: PAGI_IPC_NAME.to_string(),
-            knowledge_base: db,
-            ipc_initialized: false,
-            rules: Self::default_rules(),
+    /// [`PAGICoreModel::check_any_authorization`], returning the identity to filter the
+    /// call's results with: the verified identity, its unlimited delegations and, if one
+    /// was charged, the use-limited delegation that allowed the call.
+    fn authorize_read(
+        &self,
+        identity: &AgentIdentity,
+        scope: AuthScope,
+    ) -> Result<AgentIdentity, String> {
+        let now = unix_now();
+        let mut used = None;
+        let res = self.delegated_identity(identity).and_then(|identity| {
+            let gatekeeper = self.gatekeeper();
+            let allows = |identity: &AgentIdentity| {
+                gatekeeper.authorize_any(identity, &scope).is_ok()
+                    || self
+                        .policies
+                        .may_allow_any(&gatekeeper, identity, &scope, now)
+            };
+            if allows(&identity) {
+                return Ok(identity.into_owned());
+            }
+            let denied = gatekeeper
+                .authorize_any(&identity, &scope)
+                .err()
+                .unwrap_or_default();
+            let (d, elevated) = self
+                .use_delegation(&identity, now, |elevated| {
+                    allows(elevated).then(|| elevated.clone())
+                })
+                .ok_or(denied)?;
+            used = Some(d);
+            Ok(elevated)
+        });
+        let checked = res.as_ref().map(|_| ()).map_err(Clone::clone);
+        Self::log_denial(identity, &scope, &checked);
+        self.audit_decision(identity, &scope, &Resource::default(), &checked);
+        if let Some(d) = used {
+            self.audit_delegation_use(&identity.id, scope, Resource::default(), &d);
         }
+        res
     }
 
-    fn parse_llm_plan(&self, raw: &str) -> Result<Vec<Task>, String> {
-        let v: serde_json::Value = serde_json::from_str(raw)
-            .map_err(|e| format!("LLM returned non-JSON plan: {e}. Raw: {raw}"))?;
-        let arr = v
-            .as_array()
-            .ok_or_else(|| "LLM plan must be a JSON array".to_string())?;
-
-        let mut tasks = Vec::new();
-        for item in arr {
-            let agent_type = item
-                .get("agent_type")
-                .and_then(|v| v.as_str())
-                .ok_or_else(|| "Task missing 'agent_type'".to_string())?;
-
-            let input_val = item
-                .get("input_data")
-                .cloned()
-                .unwrap_or_else(|| serde_json::json!({}));
+    /// Like [`PAGICoreModel::check_authorization_on`], without logging denials or consuming
+    /// policy rate limits or delegation uses. Used to filter results.
+    pub fn is_authorized_on(
+        &self,
+        identity: &AgentIdentity,
+        scope: &AuthScope,
+        resource: &Resource,
+    ) -> bool {
+        let Ok(identity) = self.delegated_identity(identity) else {
+            return false;
+        };
+        self.policies
+            .evaluate(&self.gatekeeper(), &identity, scope, resource, unix_now(), false)
+            .allowed
+    }
 
-            let input_data = match input_val {
-                serde_json::Value::String(s) => s,
-                other => other.to_string(),
+    /// Evaluates a request against scopes, active delegations and policies without side
+    /// effects (no logging, no rate-limit or delegation-use consumption).
+    pub fn simulate(
+        &self,
+        identity: &AgentIdentity,
+        action: AuthScope,
+        resource: &Resource,
+    ) -> AuthDecision {
+        let Ok(identity) = self.delegated_identity(identity) else {
+            return AuthDecision {
+                allowed: false,
+                reason: DecisionReason::NoMatch,
             };
+        };
+        self.policies.evaluate(
+            &self.gatekeeper(),
+            &identity,
+            &action,
+            resource,
+            unix_now(),
+            false,
+        )
+    }
 
-            tasks.push(Task {
-                agent_type: agent_type.to_string(),
-                input_data,
-            });
+    /// Returns the identity gated calls should trust.
+    ///
+    /// An identity carrying a credential is replaced by what the credential proves (an
+    /// invalid, expired or mismatched credential is an error). Without one, the
+    /// caller-built identity is accepted only if credentials were made optional (see
+    /// [`PAGICoreModel::require_credentials`]).
+    pub fn verified_identity<'a>(
+        &self,
+        identity: &'a AgentIdentity,
+    ) -> Result<Cow<'a, AgentIdentity>, String> {
+        if identity.credential.is_some() {
+            return self
+                .credentials
+                .resolve(identity)
+                .map(Cow::Owned)
+                .map_err(|e| format!("Permission denied: {e}"));
         }
-
-        Ok(tasks)
+        if self.require_credentials {
+            return Err(format!(
+                "Permission denied: agent '{}' presented no credential",
+                identity.id
+            ));
+        }
+        Ok(Cow::Borrowed(identity))
     }
 
-    /// Applies symbolic rules against observed facts and returns action directives.
-    pub fn apply_rules_to_facts(&self, facts: Vec<AgentFact>) -> Vec<String> {
-        let mut directives = Vec::new();
+    /// [`PAGICoreModel::verified_identity`] plus the scopes of its active delegations, for
+    /// checks that don't charge delegation uses. Use-limited delegations are left out: they
+    /// only apply where a use is charged.
+    fn delegated_identity<'a>(
+        &self,
+        identity: &'a AgentIdentity,
+    ) -> Result<Cow<'a, AgentIdentity>, String> {
+        let identity = self.verified_identity(identity)?;
+        let mut active = self.delegations.active_for(&identity.id, unix_now());
+        active.retain(|d| d.max_uses.is_none());
+        if active.is_empty() {
+            return Ok(identity);
+        }
+        Ok(Cow::Owned(delegation::elevate(&identity, &active)))
+    }
 
-        for fact in facts {
-            for rule in &self.rules {
-                if fact.fact_type == rule.condition_fact_type
-                    && fact.content.contains(&rule.condition_keyword)
-                {
-                    directives.push(rule.action_directive.clone());
+    /// Tries `identity`'s active delegations one at a time and charges a use to the first
+    /// one `allows` accepts. Expired delegations are revoked first.
+    fn use_delegation<T>(
+        &self,
+        identity: &AgentIdentity,
+        now: u64,
+        allows: impl Fn(&AgentIdentity) -> Option<T>,
+    ) -> Option<(Delegation, T)> {
+        self.sweep_delegations();
+        for d in self.delegations.active_for(&identity.id, now) {
+            let Some(out) = allows(&delegation::elevate(identity, std::slice::from_ref(&d)))
+            else {
+                continue;
+            };
+            match self.delegations.consume(&d.id, now) {
+                Ok(Some(d)) => return Some((d, out)),
+                Ok(None) => continue,
+                Err(e) => {
+                    event!(
+                        Level::ERROR,
+                        delegation_id = %d.id,
+                        error = %e,
+                        "Failed to record delegation use"
+                    );
                 }
             }
         }
-
-        directives.sort();
-        directives.dedup();
-        directives
+        None
     }
 
-    fn resolve_symbolic_directives(&self) -> Vec<String> {
-        // In a fuller implementation, we'd query a narrower window (e.g., since last run), or
-        // only facts produced by specific analysis agents. For now, scan all facts.
-        let facts = self.retrieve_facts_by_timestamp_unchecked(0);
-        self.apply_rules_to_facts(facts)
+    /// Turns credential enforcement on or off. It is on by default; turning it off (or
+    /// starting with `PAGI_REQUIRE_CREDENTIALS=0`) trusts caller-built identities and is
+    /// meant for tests and migrations only.
+    ///
+    /// Consumes the core, so it can only be changed before the core is shared with agents.
+    pub fn require_credentials(mut self, required: bool) -> Self {
+        self.require_credentials = required;
+        self
     }
 
-    fn apply_symbolic_directives_to_plan(&self, plan: Vec<Task>, directives: &[String]) -> Vec<Task> {
-        let wants_deep_rerun = directives
-            .iter()
-            .any(|d| d.to_lowercase().contains("deep"));
+    /// Mints the first credential (typically for an operator holding
+    /// [`AuthScope::WritePolicy`]) without an authorization check.
+    ///
+    /// Takes `&mut self`, so it is only reachable before the core is shared with agents.
+    pub fn bootstrap_credential(
+        &mut self,
+        identity: &AgentIdentity,
+        ttl: std::time::Duration,
+    ) -> Result<AgentIdentity, String> {
+        let token = self.credentials.mint(identity, ttl)?;
+        Ok(AgentIdentity::new(identity.id.clone(), identity.scopes.clone()).with_credential(&token))
+    }
 
-        if !wants_deep_rerun {
-            return plan;
-        }
+    /// Mints a signed credential for `identity`'s id and scopes, valid for `ttl`, and
+    /// returns the identity with the credential attached. Requires [`AuthScope::WritePolicy`]
+    /// on the `credentials` tree.
+    pub fn issue_credential(
+        &self,
+        issuer: &AgentIdentity,
+        identity: &AgentIdentity,
+        ttl: std::time::Duration,
+    ) -> Result<AgentIdentity, String> {
+        let resource = Resource::tree(credentials::CREDENTIALS_TREE);
+        self.check_authorization_on(issuer, AuthScope::WritePolicy, &resource)?;
+        let res = self.credentials.mint(identity, ttl).map(|token| {
+            AgentIdentity::new(identity.id.clone(), identity.scopes.clone())
+                .with_credential(&token)
+        });
+        self.audit_mutation(&issuer.id, "issue_credential", resource, &res);
+        res
+    }
 
-        let mut out = Vec::new();
+    /// Returns a handle to the credential authority (shared, not a copy), e.g. for key
+    /// rotation or as an [`IpcBus`] authenticator. Requires [`AuthScope::WritePolicy`] on the
+    /// `credentials` tree.
+    pub fn credential_authority(
+        &self,
+        identity: &AgentIdentity,
+    ) -> Result<CredentialAuthority, String> {
+        let resource = Resource::tree(credentials::CREDENTIALS_TREE);
+        self.check_authorization_on(identity, AuthScope::WritePolicy, &resource)?;
+        self.audit_mutation(&identity.id, "credential_authority", resource, &Ok(()));
+        Ok(self.credentials.clone())
+    }
 
-        for task in plan {
-            if task.agent_type != "SearchAgent" {
-                out.push(task);
-                continue;
-            }
+    /// Stores (or replaces) a policy statement. Requires [`AuthScope::WritePolicy`] on the
+    /// `policies` tree.
+    pub fn put_policy(
+        &self,
+        identity: &AgentIdentity,
+        statement: PolicyStatement,
+    ) -> Result<(), String> {
+        self.check_authorization_on(
+            identity,
+            AuthScope::WritePolicy,
+            &Resource::tree(policy::POLICIES_TREE),
+        )?;
+        let resource = Resource::tree(policy::POLICIES_TREE);
+        let res = self
+            .policies
+            .put(&statement)
+            .map_err(|e| format!("Policy write failed: {e}"));
+        self.audit_mutation(&identity.id, "put_policy", resource, &res);
+        res
+    }
 
-            // Original task remains.
-            out.push(task.clone());
+    /// Deletes a policy statement. Requires [`AuthScope::WritePolicy`] on the `policies` tree.
+    pub fn remove_policy(&self, identity: &AgentIdentity, id: &str) -> Result<bool, String> {
+        self.check_authorization_on(
+            identity,
+            AuthScope::WritePolicy,
+            &Resource::tree(policy::POLICIES_TREE),
+        )?;
+        let resource = Resource::tree(policy::POLICIES_TREE);
+        let res = self
+            .policies
+            .remove(id)
+            .map_err(|e| format!("Policy write failed: {e}"));
+        self.audit_mutation(&identity.id, "remove_policy", resource, &res);
+        res
+    }
 
-            // Rerun variants (symbolic directive applied).
-            for variant in 1..=2u8 {
-                let mut payload = serde_json::from_str::<serde_json::Value>(&task.input_data)
-                    .unwrap_or_else(|_| serde_json::json!({ "raw": task.input_data }));
+    /// Lists the stored policy statements. Requires [`AuthScope::ReadFacts`] or
+    /// [`AuthScope::WritePolicy`] on the `policies` tree.
+    pub fn policies(&self, identity: &AgentIdentity) -> Result<Vec<PolicyStatement>, String> {
+        let resource = Resource::tree(policy::POLICIES_TREE);
+        if !self.is_authorized_on(identity, &AuthScope::WritePolicy, &resource) {
+            self.check_authorization_on(identity, AuthScope::ReadFacts, &resource)?;
+        }
+        Ok(self.policies.statements())
+    }
 
-                if let serde_json::Value::Object(ref mut m) = payload {
-                    m.insert("deep".to_string(), serde_json::Value::Bool(true));
-                    m.insert(
-                        "rerun_variant".to_string(),
-                        serde_json::Value::Number(variant.into()),
-                    );
-                    m.insert(
-                        "symbolic_directives".to_string(),
-                        serde_json::Value::Array(
-                            directives
-                                .iter()
-                                .cloned()
-                                .map(serde_json::Value::String)
-                                .collect(),
-                        ),
-                    );
-                }
+    /// Lends `scopes` to the identity `delegate` for `ttl`, optionally limited to `max_uses`
+    /// authorization checks. `delegator` must hold every scope itself (directly or by
+    /// implication; delegated scopes can't be passed on).
+    pub fn delegate(
+        &self,
+        delegator: &AgentIdentity,
+        delegate: &str,
+        scopes: Vec<AuthScope>,
+        ttl: std::time::Duration,
+        max_uses: Option<u32>,
+    ) -> Result<Delegation, String> {
+        let delegator = self.verified_identity(delegator)?;
+        self.check_delegable(&delegator, &scopes)?;
+        let res = self
+            .delegations
+            .create(
+                &delegator,
+                delegate,
+                scopes,
+                ttl.as_secs(),
+                max_uses,
+                DelegationOrigin::Direct,
+                unix_now(),
+            )
+            .map_err(|e| format!("Delegation write failed: {e}"));
+        self.audit_mutation(
+            &delegator.id,
+            "delegate",
+            Resource::tree(delegation::DELEGATIONS_TREE),
+            &res,
+        );
+        res
+    }
 
-                out.push(Task {
-                    agent_type: "SearchAgent".to_string(),
-                    input_data: payload.to_string(),
-                });
-            }
+    fn check_delegable(
+        &self,
+        delegator: &AgentIdentity,
+        scopes: &[AuthScope],
+    ) -> Result<(), String> {
+        if scopes.is_empty() {
+            return Err("Delegation must name at least one scope".to_string());
+        }
+        let held = self.gatekeeper().effective_scopes(delegator);
+        match scopes.iter().find(|s| !delegation::covers(&held, s)) {
+            Some(missing) => Err(format!(
+                "Permission denied: agent '{}' cannot delegate {:?} it does not hold",
+                delegator.id, missing
+            )),
+            None => Ok(()),
         }
-
-        out
     }
 
-    /// Records a fact into the persistent knowledge base.
+    /// Revokes a delegation. Allowed for its delegator and delegate, and for holders of
+    /// [`AuthScope::WritePolicy`] on the `delegations` tree.
+    pub fn revoke_delegation(&self, identity: &AgentIdentity, id: &str) -> Result<bool, String> {
+        let Some(d) = self.delegations.get(id) else {
+            return Ok(false);
+        };
+        let verified = self.verified_identity(identity)?;
+        if verified.id != d.delegator && verified.id != d.delegate {
+            self.check_authorization_on(
+                identity,
+                AuthScope::WritePolicy,
+                &Resource::tree(delegation::DELEGATIONS_TREE),
+            )?;
+        }
+        let res = self
+            .delegations
+            .revoke(id)
+            .map_err(|e| format!("Delegation write failed: {e}"));
+        self.audit_mutation(
+            &verified.id,
+            "revoke_delegation",
+            Resource::tree(delegation::DELEGATIONS_TREE),
+            &res,
+        );
+        res
+    }
+
+    /// Active delegations `identity` has given or received.
+    pub fn active_delegations(&self, identity: &AgentIdentity) -> Result<Vec<Delegation>, String> {
+        let identity = self.verified_identity(identity)?;
+        let now = unix_now();
+        Ok(self
+            .delegations
+            .all()
+            .into_iter()
+            .filter(|d| {
+                d.is_active(now) && (d.delegate == identity.id || d.delegator == identity.id)
+            })
+            .collect())
+    }
+
+    /// Revokes every expired delegation and returns them. Also runs before delegations are
+    /// tried in [`PAGICoreModel::check_authorization_on`].
+    pub fn sweep_delegations(&self) -> Vec<Delegation> {
+        let expired = match self.delegations.revoke_expired(unix_now()) {
+            Ok(expired) => expired,
+            Err(e) => {
+                event!(Level::ERROR, error = %e, "Failed to revoke expired delegations");
+                return Vec::new();
+            }
+        };
+        for d in &expired {
+            self.append_audit(AuditEvent {
+                identity_id: "PAGICore".to_string(),
+                action: "expire_delegation".to_string(),
+                scope: None,
+                resource: Resource::tree(delegation::DELEGATIONS_TREE),
+                outcome: AuditOutcome::Succeeded,
+                detail: Some(format!("delegation '{}' to '{}'", d.id, d.delegate)),
+            });
+        }
+        expired
+    }
+
+    /// Files a just-in-time request for `scopes`, to be granted for `ttl` once approved.
+    pub fn request_elevation(
+        &self,
+        identity: &AgentIdentity,
+        scopes: Vec<AuthScope>,
+        ttl: std::time::Duration,
+        max_uses: Option<u32>,
+        justification: &str,
+    ) -> Result<ElevationRequest, String> {
+        let identity = self.verified_identity(identity)?;
+        if scopes.is_empty() {
+            return Err("Elevation request must name at least one scope".to_string());
+        }
+        let res = self
+            .delegations
+            .create_request(
+                &identity.id,
+                scopes,
+                ttl.as_secs(),
+                max_uses,
+                justification,
+                unix_now(),
+            )
+            .map_err(|e| format!("Elevation request write failed: {e}"));
+        self.audit_mutation(
+            &identity.id,
+            "request_elevation",
+            Resource::tree(delegation::ELEVATION_REQUESTS_TREE),
+            &res,
+        );
+        res
+    }
+
+    /// Approves a pending elevation request, delegating the requested scopes from
+    /// `approver`. Requires [`AuthScope::WritePolicy`] on the `elevation_requests` tree,
+    /// and the approver must hold the scopes and not be the requester.
+    pub fn approve_elevation(
+        &self,
+        approver: &AgentIdentity,
+        request_id: &str,
+    ) -> Result<Delegation, String> {
+        let (approver, mut request) = self.pending_elevation(approver, request_id)?;
+        self.check_delegable(&approver, &request.scopes)?;
+
+        let now = unix_now();
+        let res = self
+            .delegations
+            .create(
+                &approver,
+                &request.requester,
+                request.scopes.clone(),
+                request.ttl_secs,
+                request.max_uses,
+                DelegationOrigin::Elevation {
+                    request_id: request.id.clone(),
+                },
+                now,
+            )
+            .and_then(|d| {
+                request.status = ElevationStatus::Approved {
+                    approver: approver.id.clone(),
+                    delegation_id: d.id.clone(),
+                    at: now,
+                };
+                self.delegations.put_request(&request)?;
+                Ok(d)
+            })
+            .map_err(|e| format!("Elevation approval failed: {e}"));
+        self.audit_mutation(
+            &approver.id,
+            "approve_elevation",
+            Resource::tree(delegation::ELEVATION_REQUESTS_TREE),
+            &res,
+        );
+        res
+    }
+
+    /// Rejects a pending elevation request. Same authorization as
+    /// [`PAGICoreModel::approve_elevation`].
+    pub fn deny_elevation(
+        &self,
+        approver: &AgentIdentity,
+        request_id: &str,
+        reason: &str,
+    ) -> Result<(), String> {
+        let (approver, mut request) = self.pending_elevation(approver, request_id)?;
+        request.status = ElevationStatus::Denied {
+            approver: approver.id.clone(),
+            reason: reason.to_string(),
+            at: unix_now(),
+        };
+        let res = self
+            .delegations
+            .put_request(&request)
+            .map_err(|e| format!("Elevation request write failed: {e}"));
+        self.audit_mutation(
+            &approver.id,
+            "deny_elevation",
+            Resource::tree(delegation::ELEVATION_REQUESTS_TREE),
+            &res,
+        );
+        res
+    }
+
+    fn pending_elevation<'a>(
+        &self,
+        approver: &'a AgentIdentity,
+        request_id: &str,
+    ) -> Result<(Cow<'a, AgentIdentity>, ElevationRequest), String> {
+        self.check_authorization_on(
+            approver,
+            AuthScope::WritePolicy,
+            &Resource::tree(delegation::ELEVATION_REQUESTS_TREE),
+        )?;
+        let approver = self.verified_identity(approver)?;
+        let request = self
+            .delegations
+            .request(request_id)
+            .ok_or_else(|| format!("Unknown elevation request '{request_id}'"))?;
+        if request.status != ElevationStatus::Pending {
+            return Err(format!("Elevation request '{request_id}' is no longer pending"));
+        }
+        if request.requester == approver.id {
+            return Err(format!(
+                "Permission denied: agent '{}' cannot approve its own elevation request",
+                approver.id
+            ));
+        }
+        Ok((approver, request))
+    }
+
+    /// Elevation requests visible to `identity`: all of them for holders of
+    /// [`AuthScope::WritePolicy`] on the `elevation_requests` tree, otherwise its own.
+    pub fn elevation_requests(
+        &self,
+        identity: &AgentIdentity,
+    ) -> Result<Vec<ElevationRequest>, String> {
+        let approver = self.is_authorized_on(
+            identity,
+            &AuthScope::WritePolicy,
+            &Resource::tree(delegation::ELEVATION_REQUESTS_TREE),
+        );
+        let identity = self.verified_identity(identity)?;
+        Ok(self
+            .delegations
+            .requests()
+            .into_iter()
+            .filter(|r| approver || r.requester == identity.id)
+            .collect())
+    }
+
+    fn decision_to_result(
+        gatekeeper: &AuthorizationGatekeeper,
+        identity: &AgentIdentity,
+        scope: &AuthScope,
+        resource: &Resource,
+        decision: &AuthDecision,
+    ) -> Result<(), String> {
+        match &decision.reason {
+            _ if decision.allowed => Ok(()),
+            DecisionReason::ExplicitDeny(id) => Err(format!(
+                "Permission denied: agent '{}' denied {:?} on {resource} by policy '{id}'",
+                identity.id,
+                scope.base()
+            )),
+            _ => gatekeeper.authorize(identity, scope, resource),
+        }
+    }
+
+    fn log_denial(identity: &AgentIdentity, scope: &AuthScope, res: &Result<(), String>) {
+        if let Err(ref e) = res {
+            event!(
+                Level::WARN,
+                identity_id = %identity.id,
+                required_scope = ?scope,
+                error = %e,
+                "Authorization denied"
+            );
+        }
+    }
+
+    fn audit_decision(
+        &self,
+        identity: &AgentIdentity,
+        scope: &AuthScope,
+        resource: &Resource,
+        res: &Result<(), String>,
+    ) {
+        self.append_audit(AuditEvent {
+            identity_id: identity.id.clone(),
+            action: "authorize".to_string(),
+            scope: Some(scope.clone()),
+            resource: resource.clone(),
+            outcome: if res.is_ok() {
+                AuditOutcome::Allowed
+            } else {
+                AuditOutcome::Denied
+            },
+            detail: res.as_ref().err().cloned(),
+        });
+    }
+
+    fn audit_mutation<T>(
+        &self,
+        identity_id: &str,
+        action: &str,
+        resource: Resource,
+        res: &Result<T, String>,
+    ) {
+        self.append_audit(AuditEvent {
+            identity_id: identity_id.to_string(),
+            action: action.to_string(),
+            scope: None,
+            resource,
+            outcome: if res.is_ok() {
+                AuditOutcome::Succeeded
+            } else {
+                AuditOutcome::Failed
+            },
+            detail: res.as_ref().err().cloned(),
+        });
+    }
+
+    fn append_audit(&self, event: AuditEvent) {
+        if let Err(e) = self.audit.append(event) {
+            event!(Level::ERROR, error = %e, "Failed to append audit record");
+        }
+    }
+
+    /// Returns every audit record. Requires [`AuthScope::ReadFacts`] on the `audit` tree.
+    pub fn audit_records(&self, identity: &AgentIdentity) -> Result<Vec<AuditRecord>, String> {
+        self.check_authorization_on(
+            identity,
+            AuthScope::ReadFacts,
+            &Resource::tree(audit::AUDIT_TREE),
+        )?;
+        Ok(self.audit.records())
+    }
+
+    /// Writes the audit log as JSON lines. Requires [`AuthScope::ReadFacts`] on the `audit`
+    /// tree.
+    pub fn export_audit_log<W: std::io::Write>(
+        &self,
+        identity: &AgentIdentity,
+        out: W,
+    ) -> Result<usize, String> {
+        self.check_authorization_on(
+            identity,
+            AuthScope::ReadFacts,
+            &Resource::tree(audit::AUDIT_TREE),
+        )?;
+        self.audit
+            .export_jsonl(out)
+            .map_err(|e| format!("Audit export failed: {e}"))
+    }
+
+    /// Checks the audit hash chain end to end and returns its head. Keep the head somewhere
+    /// outside the KB to detect a rewritten chain later with
+    /// [`PAGICoreModel::verify_audit_log_anchor`].
+    pub fn verify_audit_log(&self) -> Result<AuditHead, AuditViolation> {
+        self.audit.verify()
+    }
+
+    /// Checks the audit hash chain and that it still contains `anchor`, a head returned by an
+    /// earlier [`PAGICoreModel::verify_audit_log`].
+    pub fn verify_audit_log_anchor(&self, anchor: &AuditHead) -> Result<AuditHead, AuditViolation> {
+        self.audit.verify_anchor(anchor)
+    }
+
+    /// Installs the approval gate, or removes it with `None` (the default unless
+    /// `PAGI_REQUIRE_APPROVALS=1`). Requires [`AuthScope::WritePolicy`] on the `approvals`
+    /// tree.
+    pub fn configure_approvals(
+        &self,
+        identity: &AgentIdentity,
+        config: Option<ApprovalConfig>,
+    ) -> Result<(), String> {
+        let resource = Resource::tree(approval::APPROVALS_TREE);
+        self.check_authorization_on(identity, AuthScope::WritePolicy, &resource)?;
+        self.approvals.configure(config);
+        self.audit_mutation(&identity.id, "configure_approvals", resource, &Ok(()));
+        Ok(())
+    }
+
+    /// Subscribes to approval notifications; [`IpcBus::forward_approvals`] relays them to
+    /// IPC clients.
+    pub fn approval_notices(&self) -> tokio::sync::broadcast::Receiver<IpcMessage> {
+        self.approvals.subscribe()
+    }
+
+    /// Lets the action behind a pending approval request proceed (queued facts are written
+    /// now). Requires [`AuthScope::WritePolicy`] on the `approvals` tree; requesters can't
+    /// approve their own requests.
+    pub fn approve_action(
+        &self,
+        approver: &AgentIdentity,
+        request_id: &str,
+    ) -> Result<ApprovalRequest, String> {
+        let approver = self.check_approver(approver, request_id)?;
+        let status = ApprovalStatus::Approved {
+            approver: approver.id.clone(),
+            at: unix_now(),
+        };
+        let res = self.approvals.resolve(request_id, status);
+        self.audit_mutation(
+            &approver.id,
+            "approve_action",
+            Resource::tree(approval::APPROVALS_TREE),
+            &res,
+        );
+        let request = res?;
+        self.settle_queued_fact(&request);
+        Ok(request)
+    }
+
+    /// Rejects a pending approval request. Same authorization as
+    /// [`PAGICoreModel::approve_action`].
+    pub fn reject_action(
+        &self,
+        approver: &AgentIdentity,
+        request_id: &str,
+        reason: &str,
+    ) -> Result<ApprovalRequest, String> {
+        let approver = self.check_approver(approver, request_id)?;
+        let status = ApprovalStatus::Rejected {
+            approver: approver.id.clone(),
+            reason: reason.to_string(),
+            at: unix_now(),
+        };
+        let res = self.approvals.resolve(request_id, status);
+        self.audit_mutation(
+            &approver.id,
+            "reject_action",
+            Resource::tree(approval::APPROVALS_TREE),
+            &res,
+        );
+        let request = res?;
+        self.settle_queued_fact(&request);
+        Ok(request)
+    }
+
+    fn check_approver<'a>(
+        &self,
+        approver: &'a AgentIdentity,
+        request_id: &str,
+    ) -> Result<Cow<'a, AgentIdentity>, String> {
+        self.check_authorization_on(
+            approver,
+            AuthScope::WritePolicy,
+            &Resource::tree(approval::APPROVALS_TREE),
+        )?;
+        let approver = self.verified_identity(approver)?;
+        let request = self
+            .approvals
+            .get(request_id)
+            .ok_or_else(|| format!("Unknown approval request '{request_id}'"))?;
+        if request.requester == approver.id {
+            return Err(format!(
+                "Permission denied: agent '{}' cannot approve its own request",
+                approver.id
+            ));
+        }
+        Ok(approver)
+    }
+
+    /// Approval requests visible to `identity`: all of them for holders of
+    /// [`AuthScope::WritePolicy`] on the `approvals` tree, otherwise its own.
+    pub fn approval_requests(
+        &self,
+        identity: &AgentIdentity,
+    ) -> Result<Vec<ApprovalRequest>, String> {
+        let approver = self.is_authorized_on(
+            identity,
+            &AuthScope::WritePolicy,
+            &Resource::tree(approval::APPROVALS_TREE),
+        );
+        let identity = self.verified_identity(identity)?;
+        Ok(self
+            .approvals
+            .all()
+            .into_iter()
+            .filter(|r| approver || r.requester == identity.id)
+            .collect())
+    }
+
+    /// Settles pending requests older than the configured timeout according to its
+    /// [`TimeoutPolicy`] and returns them. [`IpcBus::forward_approvals`] calls this once
+    /// per second, and gated checks call it before looking up their request.
+    pub fn sweep_approvals(&self) -> Vec<ApprovalRequest> {
+        let Some(config) = self.approvals.config() else {
+            return Vec::new();
+        };
+        let expired = self.approvals.expire(config.on_timeout, unix_now());
+        for request in &expired {
+            self.append_audit(AuditEvent {
+                identity_id: "PAGICore".to_string(),
+                action: "approval_timeout".to_string(),
+                scope: Some(request.scope.clone()),
+                resource: request.resource.clone(),
+                outcome: AuditOutcome::Succeeded,
+                detail: Some(format!(
+                    "request '{}' from '{}' settled as {:?}",
+                    request.id, request.requester, config.on_timeout
+                )),
+            });
+            self.settle_queued_fact(request);
+        }
+        expired
+    }
+
+    /// Writes (if allowed) or drops the fact queued with a settled request.
+    fn settle_queued_fact(&self, request: &ApprovalRequest) {
+        let Some(fact) = request.fact.clone() else {
+            return;
+        };
+        if request.status.allows() == Some(true) {
+            let resource = Resource::fact(FACTS_TREE, &fact.fact_type, &fact.agent_id);
+            let res = self
+                .record_fact_unchecked(fact)
+                .map_err(|e| format!("KB write failed: {e}"));
+            self.audit_mutation(&request.requester, "record_fact", resource, &res);
+            if res.is_err() {
+                return;
+            }
+        }
+        if let Err(e) = self.approvals.mark_consumed(&request.id) {
+            event!(
+                Level::ERROR,
+                request_id = %request.id,
+                error = %e,
+                "Failed to settle approval"
+            );
+        }
+    }
+
+    /// Passes once a gated action's request has been approved (consuming it), otherwise
+    /// files or reports the pending request.
+    fn approval_gate(
+        &self,
+        identity_id: &str,
+        scope: &AuthScope,
+        resource: &Resource,
+    ) -> Result<(), String> {
+        let Some(config) = self.approvals.config().filter(|c| c.gates_scope(scope)) else {
+            return Ok(());
+        };
+        self.sweep_approvals();
+
+        if let Some(request) = self.approvals.find_open(identity_id, scope, resource) {
+            let Some(allowed) = request.status.allows() else {
+                return Err(request.pending_error());
+            };
+            self.approvals
+                .mark_consumed(&request.id)
+                .map_err(|e| format!("Approval write failed: {e}"))?;
+            return match request.status {
+                _ if allowed => Ok(()),
+                ApprovalStatus::Rejected { approver, reason, .. } => Err(format!(
+                    "Permission denied: approval request '{}' was rejected by '{approver}': \
+                     {reason}",
+                    request.id
+                )),
+                _ => Err(format!(
+                    "Permission denied: approval request '{}' timed out",
+                    request.id
+                )),
+            };
+        }
+
+        let summary = format!("{:?} on {resource}", scope.base());
+        let res = self
+            .approvals
+            .enqueue(
+                identity_id,
+                scope,
+                resource,
+                summary,
+                None,
+                config.timeout,
+                unix_now(),
+            )
+            .map_err(|e| format!("Approval write failed: {e}"));
+        self.audit_mutation(
+            identity_id,
+            "request_approval",
+            Resource::tree(approval::APPROVALS_TREE),
+            &res,
+        );
+        Err(res?.pending_error())
+    }
+
+    /// Replaces the quota rules. Without rules (the default) nothing is limited. Requires
+    /// [`AuthScope::WritePolicy`] on the `quotas` tree.
+    pub fn configure_quotas(
+        &self,
+        identity: &AgentIdentity,
+        rules: Vec<QuotaRule>,
+    ) -> Result<(), String> {
+        let resource = Resource::tree(quota::QUOTAS_RESOURCE);
+        self.check_authorization_on(identity, AuthScope::WritePolicy, &resource)?;
+        self.quotas.configure(rules);
+        self.audit_mutation(&identity.id, "configure_quotas", resource, &Ok(()));
+        Ok(())
+    }
+
+    /// Current usage of every quota bucket, for monitoring.
+    pub fn quota_usage(&self) -> Vec<QuotaUsage> {
+        self.quotas.usage()
+    }
+
+    /// Returns a handle to the quota manager (shared, not a copy). Requires
+    /// [`AuthScope::WritePolicy`] on the `quotas` tree, since the handle can replace the rules.
+    pub fn quota_manager(&self, identity: &AgentIdentity) -> Result<QuotaManager, String> {
+        self.check_authorization_on(
+            identity,
+            AuthScope::WritePolicy,
+            &Resource::tree(quota::QUOTAS_RESOURCE),
+        )?;
+        Ok(self.quotas.clone())
+    }
+
+    /// Replaces the gatekeeper (scope implications) used by every authorization check.
+    /// Requires [`AuthScope::WritePolicy`] on the `gatekeeper` tree.
+    pub fn configure_authorization(
+        &self,
+        identity: &AgentIdentity,
+        gatekeeper: AuthorizationGatekeeper,
+    ) -> Result<(), String> {
+        let resource = Resource::tree(auth::GATEKEEPER_RESOURCE);
+        self.check_authorization_on(identity, AuthScope::WritePolicy, &resource)?;
+        *self.gatekeeper.write().expect("gatekeeper lock poisoned") = gatekeeper;
+        self.audit_mutation(&identity.id, "configure_authorization", resource, &Ok(()));
+        Ok(())
+    }
+
+    /// Returns a copy of the current gatekeeper.
+    pub fn gatekeeper(&self) -> AuthorizationGatekeeper {
+        self.gatekeeper
+            .read()
+            .expect("gatekeeper lock poisoned")
+            .clone()
+    }
+
+    fn default_rules() -> Vec<PAGIRule> {
+        vec![
+            PAGIRule::new(
+                "rule_failure_rerun_deep",
+                "AnalysisResult",
+                "Failure",
+                "Rerun: Deep Search",
+            ),
+            PAGIRule::new(
+                "rule_cyber_alert_triage",
+                "AnalysisResult",
+                "CYBER_ALERT",
+                "TASK: CybersecurityAgent, INPUT: Triage alert",
+            ),
+        ]
+    }
+
+    /// Constructs the core model and opens/creates the persistent knowledge base.
+    ///
+    /// Note: this follows the prompt's "conceptual stand-in" approach and uses a simple
+    /// `unwrap`-style initialization.
+    // No `Default`: opening the on-disk KB can fail and shouldn't happen implicitly.
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        let db = sled::open(KNOWLEDGE_BASE_PATH).expect("failed to open sled knowledge base");
+        Self::from_db(db)
+    }
+
+    /// Creates a core model from an already-open Sled DB handle.
+    ///
+    /// Useful for agents that share the orchestrator's DB handle in-process. Agents running in
+    /// a separate process can't open the DB while the orchestrator holds it; they should use
+    /// the IPC RPC layer instead ([`IpcBus::serve_rpc`] / [`IpcClient::call`]).
+    pub fn from_db(db: sled::Db) -> Self {
+        let policies = PolicyEngine::open(&db).expect("failed to open policies tree");
+        let audit = AuditLog::open(&db).expect("failed to open audit tree");
+        let delegations = DelegationStore::open(&db).expect("failed to open delegations tree");
+        let approvals = ApprovalQueue::open(&db).expect("failed to open approvals tree");
+        let reflections = ReflectionStore::open(&db).expect("failed to open reflections tree");
+        let experiments = ExperimentStore::open(&db).expect("failed to open experiments tree");
+        let vectors = VectorIndex::open(&db).expect("failed to open vector index");
+        let fulltext = FullTextIndex::open(&db).expect("failed to open full-text index");
+        let graph = KnowledgeGraph::open(&db).expect("failed to open graph trees");
+        let retention = RetentionStore::open(&db).expect("failed to open retention tree");
+        let embedder = HashingEmbedder::default();
+        if vectors.embedder_id().is_none() {
+            vectors
+                .set_embedder_id(&embedder.id())
+                .expect("failed to record embedder");
+        }
+        if std::env::var(approval::PAGI_REQUIRE_APPROVALS_ENV).ok().as_deref() == Some("1") {
+            approvals.configure(Some(ApprovalConfig::default()));
+        }
+        let require_credentials =
+            std::env::var(credentials::PAGI_REQUIRE_CREDENTIALS_ENV).ok().as_deref() != Some("0");
+        let credentials = CredentialAuthority::open(&db).expect("failed to open credential keys");
+        let model = Self {
+            ipc_listener: None,
+            ipc_name: IpcConfig::from_env().endpoint().to_string(),
+            knowledge_base: db,
+            ipc_initialized: false,
+            rules: Self::default_rules(),
+            rule_queries: std::collections::HashMap::new(),
+            ipc_tokens: IpcTokenRegistry::new(),
+            liveness: LivenessTracker::default(),
+            gatekeeper: RwLock::new(AuthorizationGatekeeper::default()),
+            policies,
+            credentials,
+            require_credentials,
+            audit,
+            delegations,
+            quotas: QuotaManager::default(),
+            approvals,
+            reflections,
+            experiments,
+            embedder: RwLock::new(Arc::new(embedder)),
+            vectors,
+            fulltext,
+            graph,
+            retention,
+        };
+        if model.fulltext.is_empty() {
+            // Knowledge bases written before the full-text index existed.
+            model.reindex_fulltext().expect("failed to build full-text index");
+        }
+        let has_facts = model
+            .knowledge_base
+            .open_tree(FACTS_TREE)
+            .is_ok_and(|tree| !tree.is_empty());
+        if model.vectors.is_empty() && has_facts {
+            // Likewise for facts written before the vector index existed.
+            model
+                .reindex_embeddings()
+                .expect("failed to build vector index");
+        }
+        model
+    }
+
+    fn parse_llm_plan(&self, raw: &str) -> Result<Vec<Task>, String> {
+        let v: serde_json::Value = serde_json::from_str(raw)
+            .map_err(|e| format!("LLM returned non-JSON plan: {e}. Raw: {raw}"))?;
+        let arr = v
+            .as_array()
+            .ok_or_else(|| "LLM plan must be a JSON array".to_string())?;
+
+        let mut tasks = Vec::new();
+        for item in arr {
+            let agent_type = item
+                .get("agent_type")
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| "Task missing 'agent_type'".to_string())?;
+
+            let input_val = item
+                .get("input_data")
+                .cloned()
+                .unwrap_or_else(|| serde_json::json!({}));
+
+            let input_data = match input_val {
+                serde_json::Value::String(s) => s,
+                other => other.to_string(),
+            };
+
+            tasks.push(Task {
+                agent_type: agent_type.to_string(),
+                input_data,
+            });
+        }
+
+        Ok(tasks)
+    }
+
+    /// Applies symbolic rules against observed facts and returns action directives.
+    pub fn apply_rules_to_facts(&self, facts: Vec<AgentFact>) -> Vec<String> {
+        let mut directives = Vec::new();
+
+        for fact in facts {
+            for rule in &self.rules {
+                if rule.matches_with(&fact, self.rule_queries.get(&rule.id)) {
+                    directives.push(rule.action_directive.clone());
+                }
+            }
+        }
+
+        directives.sort();
+        directives.dedup();
+        directives
+    }
+
+    /// Applies the rule set to every fact in the knowledge base and returns the resulting
+    /// directives. Requires [`AuthScope::ReadFacts`]; directives are limited to the kinds the
+    /// identity may read (see [`Resource::directive`]).
+    pub fn symbolic_directives(&self, identity: &AgentIdentity) -> Result<Vec<String>, String> {
+        self.authorized_directives(identity, self.resolve_symbolic_directives())
+    }
+
+    /// Keeps only the directives whose kind `identity` may read. Requires
+    /// [`AuthScope::ReadFacts`] (see [`PAGICoreModel::check_any_authorization`]).
+    pub fn authorized_directives(
+        &self,
+        identity: &AgentIdentity,
+        directives: Vec<String>,
+    ) -> Result<Vec<String>, String> {
+        let reader = self.authorize_read(identity, AuthScope::ReadFacts)?;
+        Ok(self.readable_directives(&reader, directives))
+    }
+
+    /// The directives whose kind the already authorized `reader` may read.
+    fn readable_directives(&self, reader: &AgentIdentity, directives: Vec<String>) -> Vec<String> {
+        let gatekeeper = self.gatekeeper();
+        let statements = self.policies.statements();
+        let now = unix_now();
+        directives
+            .into_iter()
+            .filter(|d| {
+                self.policies
+                    .evaluate_against(
+                        &statements,
+                        &gatekeeper,
+                        reader,
+                        &AuthScope::ReadFacts,
+                        &Resource::directive(d),
+                        now,
+                        false,
+                    )
+                    .allowed
+            })
+            .collect()
+    }
+
+    /// Adds a rule to the rule set. Rule ids must be unique, and a `condition_query` must
+    /// parse as a [`FullTextQuery`]; it is parsed once, here.
+    pub fn add_rule(&mut self, rule: PAGIRule) -> Result<(), String> {
+        if self.rules.iter().any(|r| r.id == rule.id) {
+            return Err(format!("Rule '{}' already exists", rule.id));
+        }
+        if let Some(query) = rule.parse_query()? {
+            self.rule_queries.insert(rule.id.clone(), query);
+        }
+        self.rules.push(rule);
+        Ok(())
+    }
+
+    fn resolve_symbolic_directives(&self) -> Vec<String> {
+        let (indexed, scanned): (Vec<&PAGIRule>, Vec<&PAGIRule>) = self
+            .rules
+            .iter()
+            .partition(|r| self.rule_queries.contains_key(&r.id));
+        let mut directives = Vec::new();
+
+        // Rules with a full-text condition only need to look at the facts the index matches.
+        if !indexed.is_empty() {
+            let tree = self.knowledge_base.open_tree(FACTS_TREE).ok();
+            for rule in indexed {
+                let Some(query) = self.rule_queries.get(&rule.id) else {
+                    continue;
+                };
+                let triggered = self.fulltext.matching_keys(query).iter().any(|key| {
+                    tree.as_ref()
+                        .and_then(|t| t.get(key.as_bytes()).ok().flatten())
+                        .and_then(|v| serde_json::from_slice::<AgentFact>(&v).ok())
+                        .is_some_and(|fact| rule.matches_with(&fact, Some(query)))
+                });
+                if triggered {
+                    directives.push(rule.action_directive.clone());
+                }
+            }
+        }
+
+        // In a fuller implementation, we'd query a narrower window (e.g., since last run), or
+        // only facts produced by specific analysis agents. For now, scan all facts; retention
+        // policies (see `compact_facts`) keep that bounded.
+        if !scanned.is_empty() {
+            for fact in self.retrieve_facts_by_timestamp_unchecked(0) {
+                for rule in &scanned {
+                    if rule.matches_with(&fact, None) {
+                        directives.push(rule.action_directive.clone());
+                    }
+                }
+            }
+        }
+
+        directives.sort();
+        directives.dedup();
+        directives
+    }
+
+    fn apply_symbolic_directives_to_plan(&self, plan: Vec<Task>, directives: &[String]) -> Vec<Task> {
+        let wants_deep_rerun = directives
+            .iter()
+            .any(|d| d.to_lowercase().contains("deep"));
+
+        if !wants_deep_rerun {
+            return plan;
+        }
+
+        let mut out = Vec::new();
+
+        for task in plan {
+            if task.agent_type != "SearchAgent" {
+                out.push(task);
+                continue;
+            }
+
+            // Original task remains.
+            out.push(task.clone());
+
+            // Rerun variants (symbolic directive applied).
+            for variant in 1..=2u8 {
+                let mut payload = serde_json::from_str::<serde_json::Value>(&task.input_data)
+                    .unwrap_or_else(|_| serde_json::json!({ "raw": task.input_data }));
+
+                if let serde_json::Value::Object(ref mut m) = payload {
+                    m.insert("deep".to_string(), serde_json::Value::Bool(true));
+                    m.insert(
+                        "rerun_variant".to_string(),
+                        serde_json::Value::Number(variant.into()),
+                    );
+                    m.insert(
+                        "symbolic_directives".to_string(),
+                        serde_json::Value::Array(
+                            directives
+                                .iter()
+                                .cloned()
+                                .map(serde_json::Value::String)
+                                .collect(),
+                        ),
+                    );
+                }
+
+                out.push(Task {
+                    agent_type: "SearchAgent".to_string(),
+                    input_data: payload.to_string(),
+                });
+            }
+        }
+
+        out
+    }
+
+    /// Records a fact into the persistent knowledge base.
+    ///
+    /// The serialized fact counts against `WriteFacts` byte quotas. Facts of a type gated by
+    /// the [`ApprovalConfig`] are queued instead and written once approved; the call then
+    /// returns an [`approval::APPROVAL_PENDING_PREFIX`] error naming the request.
     #[tracing::instrument(
         level = "trace",
         skip(self, identity, fact),
@@ -371,32 +1695,234 @@ impl PAGICoreModel {
         )
     )]
     pub fn record_fact(&self, identity: &AgentIdentity, fact: AgentFact) -> Result<(), String> {
-        // Backwards-compatible gating: robotics agents may be granted a narrower scope than
-        // full KB writes.
-        if self
-            .check_authorization(identity, AuthScope::WriteFacts)
-            .is_err()
+        // Robotics agents holding only `RoboticsAction` are let through by the
+        // `bootstrap_robotics_write` policy.
+        let resource = Resource::fact(FACTS_TREE, &fact.fact_type, &fact.agent_id);
+        let size = serde_json::to_vec(&fact).map_or(0, |v| v.len() as u64);
+        self.check_authorization_metered(
+            identity,
+            AuthScope::WriteFacts,
+            &resource,
+            QuotaCost::bytes(size),
+        )?;
+        if let Some(config) = self
+            .approvals
+            .config()
+            .filter(|c| c.gates_fact(&fact.fact_type))
         {
-            self.check_authorization(identity, AuthScope::RoboticsAction)?;
+            let summary = format!("record {} fact: {}", fact.fact_type, fact.content);
+            let res = self
+                .approvals
+                .enqueue(
+                    &identity.id,
+                    &AuthScope::WriteFacts,
+                    &resource,
+                    summary,
+                    Some(fact),
+                    config.timeout,
+                    unix_now(),
+                )
+                .map_err(|e| format!("Approval write failed: {e}"));
+            self.audit_mutation(
+                &identity.id,
+                "request_approval",
+                Resource::tree(approval::APPROVALS_TREE),
+                &res,
+            );
+            return Err(res?.pending_error());
+        }
+        let res = self
+            .record_fact_unchecked(fact)
+            .map_err(|e| format!("KB write failed: {e}"));
+        self.audit_mutation(&identity.id, "record_fact", resource, &res);
+        res
+    }
+
+    fn record_fact_unchecked(&self, fact: AgentFact) -> Result<(), sled::Error> {
+        self.store_fact(fact).map(|_| ())
+    }
+
+    /// Writes and indexes `fact`; returns its key.
+    fn store_fact(&self, fact: AgentFact) -> Result<String, sled::Error> {
+        let tree = self.knowledge_base.open_tree(FACTS_TREE)?;
+        let id = self.knowledge_base.generate_id()?;
+
+        // Stable, lexicographically sortable key for timestamp queries.
+        let key = format!("{:020}_{id}", fact.timestamp);
+        let value = serde_json::to_vec(&fact).expect("failed to serialize AgentFact");
+
+        tree.insert(key.as_bytes(), value)?;
+        tree.flush()?;
+        self.fulltext.insert(&key, &fact.content)?;
+        self.vectors
+            .insert(&key, self.embedder().embed(&embedding::fact_text(&fact)))?;
+        Ok(key)
+    }
+
+    /// Removes the fact under `key` from the `facts` tree and every index; returns the
+    /// bytes freed across all of them.
+    fn remove_fact(&self, key: &str, fact: &AgentFact) -> Result<u64, String> {
+        let write_err = |e: sled::Error| format!("KB write failed: {e}");
+        let tree = self
+            .knowledge_base
+            .open_tree(FACTS_TREE)
+            .map_err(write_err)?;
+        let Some(value) = tree.remove(key.as_bytes()).map_err(write_err)? else {
+            return Ok(0);
+        };
+        let mut freed = (key.len() + value.len()) as u64;
+        freed += self
+            .fulltext
+            .remove(key, &fact.content)
+            .map_err(write_err)?;
+        freed += self.vectors.remove(key).map_err(write_err)?;
+        freed += self.graph.unlink_fact(key)?;
+        Ok(freed)
+    }
+
+    /// Adds every fact missing from the full-text index; returns how many were added.
+    pub fn reindex_fulltext(&self) -> Result<usize, String> {
+        let write_err = |e: sled::Error| format!("Full-text index write failed: {e}");
+        let tree = self
+            .knowledge_base
+            .open_tree(FACTS_TREE)
+            .map_err(write_err)?;
+        let mut count = 0;
+        for (k, v) in tree.iter().filter_map(|res| res.ok()) {
+            let key = String::from_utf8_lossy(&k);
+            if self.fulltext.contains(&key) {
+                continue;
+            }
+            let Ok(fact) = serde_json::from_slice::<AgentFact>(&v) else {
+                continue;
+            };
+            self.fulltext.insert(&key, &fact.content).map_err(write_err)?;
+            count += 1;
+        }
+        if count > 0 {
+            event!(Level::INFO, facts = count, "Indexed facts for full-text search");
+        }
+        Ok(count)
+    }
+
+    /// Facts whose content matches the full-text `query` (see [`FullTextQuery::parse`]), best
+    /// BM25 score first, at most `k`. Only facts `identity` may read are returned.
+    pub fn search_facts(
+        &self,
+        identity: &AgentIdentity,
+        query: &str,
+        k: usize,
+    ) -> Result<Vec<ScoredFact>, String> {
+        let query = FullTextQuery::parse(query)?;
+        let readable = self.fact_reader(identity)?;
+        let tree = self
+            .knowledge_base
+            .open_tree(FACTS_TREE)
+            .map_err(|e| format!("KB read failed: {e}"))?;
+        Ok(self
+            .fulltext
+            .search(&query)
+            .into_iter()
+            .filter_map(|(key, score)| {
+                let v = tree.get(key.as_bytes()).ok()??;
+                let fact = serde_json::from_slice::<AgentFact>(&v).ok()?;
+                readable(&fact).then_some(ScoredFact { key, fact, score })
+            })
+            .take(k)
+            .collect())
+    }
+
+    fn embedder(&self) -> Arc<dyn Embedder> {
+        self.embedder.read().expect("embedder lock poisoned").clone()
+    }
+
+    /// Replaces the embedder used for new facts and queries. If its [`Embedder::id`]
+    /// differs from the one that produced the stored vectors, every fact is re-embedded;
+    /// returns how many were.
+    pub fn configure_embedder(&self, embedder: Arc<dyn Embedder>) -> Result<usize, String> {
+        let id = embedder.id();
+        *self.embedder.write().expect("embedder lock poisoned") = embedder;
+        if self.vectors.embedder_id().as_deref() == Some(id.as_str()) {
+            return Ok(0);
+        }
+        self.reindex_embeddings()
+    }
+
+    /// Drops every stored vector and embeds all facts again with the current embedder.
+    pub fn reindex_embeddings(&self) -> Result<usize, String> {
+        let write_err = |e: sled::Error| format!("Vector index write failed: {e}");
+        let embedder = self.embedder();
+        self.vectors.clear().map_err(write_err)?;
+        let tree = self
+            .knowledge_base
+            .open_tree(FACTS_TREE)
+            .map_err(write_err)?;
+        let mut count = 0;
+        for (k, v) in tree.iter().filter_map(|res| res.ok()) {
+            let Ok(fact) = serde_json::from_slice::<AgentFact>(&v) else {
+                continue;
+            };
+            let key = String::from_utf8_lossy(&k);
+            self.vectors
+                .insert(&key, embedder.embed(&embedding::fact_text(&fact)))
+                .map_err(write_err)?;
+            count += 1;
         }
-        self.record_fact_unchecked(fact)
-            .map_err(|e| format!("KB write failed: {e}"))
+        self.vectors
+            .set_embedder_id(&embedder.id())
+            .map_err(write_err)?;
+        event!(Level::INFO, facts = count, embedder = %embedder.id(), "Reindexed fact embeddings");
+        Ok(count)
     }
 
-    fn record_fact_unchecked(&self, fact: AgentFact) -> Result<(), sled::Error> {
-        let tree = self.knowledge_base.open_tree(FACTS_TREE)?;
-        let id = self.knowledge_base.generate_id()?;
-
-        // Stable, lexicographically sortable key for timestamp queries.
-        let key = format!("{:020}_{id}", fact.timestamp);
-        let value = serde_json::to_vec(&fact).expect("failed to serialize AgentFact");
+    /// The `k` facts most similar to `query` that match `filters`, most similar first.
+    ///
+    /// Like [`PAGICoreModel::retrieve_facts_by_timestamp`], only facts `identity` may read
+    /// are returned. The ANN search widens until it finds `k` matches or has seen every
+    /// fact.
+    pub fn semantic_search(
+        &self,
+        identity: &AgentIdentity,
+        query: &str,
+        k: usize,
+        filters: &FactFilter,
+    ) -> Result<Vec<ScoredFact>, String> {
+        let readable = self.fact_reader(identity)?;
+        let tree = self
+            .knowledge_base
+            .open_tree(FACTS_TREE)
+            .map_err(|e| format!("KB read failed: {e}"))?;
+        let query = self.embedder().embed(query);
+        let total = self.vectors.len();
 
-        tree.insert(key.as_bytes(), value)?;
-        tree.flush()?;
-        Ok(())
+        let mut ef = (k * 4).max(32);
+        loop {
+            let hits: Vec<ScoredFact> = self
+                .vectors
+                .search(&query, ef)
+                .into_iter()
+                .filter_map(|(key, score)| {
+                    let v = tree.get(key.as_bytes()).ok()??;
+                    let fact = serde_json::from_slice::<AgentFact>(&v).ok()?;
+                    (filters.matches(&fact) && readable(&fact)).then_some(ScoredFact {
+                        key,
+                        fact,
+                        score,
+                    })
+                })
+                .take(k)
+                .collect();
+            if hits.len() >= k || ef >= total {
+                return Ok(hits);
+            }
+            ef = (ef * 4).min(total);
+        }
     }
 
     /// Retrieves all facts added since the given unix timestamp.
+    ///
+    /// Identities with a resource-scoped `ReadFacts` grant only see the facts it covers, and
+    /// facts matched by a `Deny` policy are hidden.
     #[tracing::instrument(
         level = "trace",
         skip(self, identity),
@@ -407,13 +1933,415 @@ impl PAGICoreModel {
         identity: &AgentIdentity,
         start_ts: u128,
     ) -> Result<Vec<AgentFact>, String> {
-        self.check_authorization(identity, AuthScope::ReadFacts)?;
-
-        let facts = self.retrieve_facts_by_timestamp_unchecked(start_ts);
+        let readable = self.fact_reader(identity)?;
+        let facts: Vec<AgentFact> = self
+            .retrieve_facts_by_timestamp_unchecked(start_ts)
+            .into_iter()
+            .filter(|f| readable(f))
+            .collect();
         tracing::event!(Level::DEBUG, facts_len = facts.len(), "KB read completed");
         Ok(facts)
     }
 
+    /// Checks `identity` for [`AuthScope::ReadFacts`] (see
+    /// [`PAGICoreModel::check_any_authorization`]) and returns whether it may read a given
+    /// fact: its `ReadFacts` grants must cover the fact and no `Deny` policy may match it.
+    fn fact_reader(
+        &self,
+        identity: &AgentIdentity,
+    ) -> Result<impl Fn(&AgentFact) -> bool + '_, String> {
+        let reader = self.authorize_read(identity, AuthScope::ReadFacts)?;
+        Ok(self.readable_facts(reader))
+    }
+
+    /// Whether the already authorized `reader` may read a given fact.
+    fn readable_facts(&self, reader: AgentIdentity) -> impl Fn(&AgentFact) -> bool + '_ {
+        let gatekeeper = self.gatekeeper();
+        let statements = self.policies.statements();
+        let now = unix_now();
+        move |f: &AgentFact| {
+            let resource = Resource::fact(FACTS_TREE, &f.fact_type, &f.agent_id);
+            self.policies
+                .evaluate_against(
+                    &statements,
+                    &gatekeeper,
+                    &reader,
+                    &AuthScope::ReadFacts,
+                    &resource,
+                    now,
+                    false,
+                )
+                .allowed
+        }
+    }
+
+    /// Ranks facts for `query` by semantic similarity, keyword overlap and recency (see
+    /// [`retrieval`]). Only facts `identity` may read are considered.
+    pub fn retrieve_relevant(
+        &self,
+        identity: &AgentIdentity,
+        query: &RetrievalQuery,
+    ) -> Result<Vec<RetrievedFact>, String> {
+        let readable = self.fact_reader(identity)?;
+        self.rank_facts(query, readable)
+    }
+
+    /// The `k` facts most relevant to `prompt` and the directives the rule set derives from
+    /// them, for planners (see [`PlanningContext::render`]). Directives are limited to the
+    /// kinds `identity` may read.
+    pub fn planning_context(
+        &self,
+        identity: &AgentIdentity,
+        prompt: &str,
+        k: usize,
+    ) -> Result<PlanningContext, String> {
+        let reader = self.authorize_read(identity, AuthScope::ReadFacts)?;
+        let facts = self.rank_facts(
+            &RetrievalQuery::new(prompt, k),
+            self.readable_facts(reader.clone()),
+        )?;
+        let derived = self.apply_rules_to_facts(facts.iter().map(|f| f.fact.clone()).collect());
+        let relations = if self
+            .check_authorization_on(identity, AuthScope::ReadFacts, &Self::graph_resource())
+            .is_ok()
+        {
+            self.relations_for(&facts)
+        } else {
+            Vec::new()
+        };
+        Ok(PlanningContext {
+            prompt: prompt.to_string(),
+            facts,
+            directives: self.readable_directives(&reader, derived),
+            relations,
+        })
+    }
+
+    /// The planner's own context for `prompt`: the most relevant facts and their relations,
+    /// plus the directives of a full rule pass over the KB. Like
+    /// [`Self::resolve_symbolic_directives`] it reads as the core, without an identity.
+    fn core_planning_context(&self, prompt: &str) -> PlanningContext {
+        let query = RetrievalQuery::new(prompt, retrieval::PLANNING_CONTEXT_K);
+        let facts = self.rank_facts(&query, |_| true).unwrap_or_else(|e| {
+            event!(Level::ERROR, error = %e, "Failed to retrieve planning context");
+            Vec::new()
+        });
+        PlanningContext {
+            prompt: prompt.to_string(),
+            // Rules see every fact; retrieval only adds context.
+            directives: self.resolve_symbolic_directives(),
+            relations: self.relations_for(&facts),
+            facts,
+        }
+    }
+
+    /// Graph relations extracted from `facts`, described for planners.
+    fn relations_for(&self, facts: &[RetrievedFact]) -> Vec<String> {
+        let keys: Vec<String> = facts.iter().map(|f| f.key.clone()).collect();
+        self.graph
+            .edges_for_facts(&keys)
+            .iter()
+            .map(GraphEdge::describe)
+            .collect()
+    }
+
+    fn graph_resource() -> Resource {
+        Resource::tree(graph::GRAPH_NODES_TREE)
+    }
+
+    /// Creates or updates the triple's nodes and edge. With `source_fact`, the key of a
+    /// stored fact (see [`ScoredFact::key`]), both are linked to that fact. Requires
+    /// [`AuthScope::WriteFacts`] on the `graph_nodes` tree.
+    pub fn upsert_triple(
+        &self,
+        identity: &AgentIdentity,
+        triple: &Triple,
+        source_fact: Option<&str>,
+    ) -> Result<GraphEdge, String> {
+        let resource = Self::graph_resource();
+        self.check_authorization_on(identity, AuthScope::WriteFacts, &resource)?;
+        let res = self.fact_exists(source_fact).and_then(|()| {
+            self.graph.upsert_triple(triple, source_fact, unix_now())
+        });
+        self.audit_mutation(&identity.id, "upsert_triple", resource, &res);
+        res
+    }
+
+    fn fact_exists(&self, key: Option<&str>) -> Result<(), String> {
+        let Some(key) = key else {
+            return Ok(());
+        };
+        let tree = self
+            .knowledge_base
+            .open_tree(FACTS_TREE)
+            .map_err(|e| format!("KB read failed: {e}"))?;
+        match tree.contains_key(key.as_bytes()) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(format!("Unknown fact '{key}'")),
+            Err(e) => Err(format!("KB read failed: {e}")),
+        }
+    }
+
+    /// Requires [`AuthScope::ReadFacts`] on the `graph_nodes` tree.
+    pub fn graph_node(
+        &self,
+        identity: &AgentIdentity,
+        id: &str,
+    ) -> Result<Option<GraphNode>, String> {
+        self.check_authorization_on(identity, AuthScope::ReadFacts, &Self::graph_resource())?;
+        Ok(self.graph.node(id))
+    }
+
+    /// Edges of node `id`, optionally of one `relation`. Requires [`AuthScope::ReadFacts`] on
+    /// the `graph_nodes` tree.
+    pub fn graph_edges(
+        &self,
+        identity: &AgentIdentity,
+        id: &str,
+        direction: Direction,
+        relation: Option<&str>,
+    ) -> Result<Vec<GraphEdge>, String> {
+        self.check_authorization_on(identity, AuthScope::ReadFacts, &Self::graph_resource())?;
+        Ok(self.graph.edges(id, direction, relation))
+    }
+
+    /// Nodes and edges within `depth` hops of `id`, following edges both ways. Requires
+    /// [`AuthScope::ReadFacts`] on the `graph_nodes` tree.
+    pub fn graph_neighborhood(
+        &self,
+        identity: &AgentIdentity,
+        id: &str,
+        depth: usize,
+    ) -> Result<Subgraph, String> {
+        self.check_authorization_on(identity, AuthScope::ReadFacts, &Self::graph_resource())?;
+        Ok(self.graph.neighborhood(id, depth, Direction::Both, None))
+    }
+
+    /// A shortest chain of edges from `from` to `to`, at most `max_depth` long. Requires
+    /// [`AuthScope::ReadFacts`] on the `graph_nodes` tree.
+    pub fn graph_path(
+        &self,
+        identity: &AgentIdentity,
+        from: &str,
+        to: &str,
+        max_depth: usize,
+        direction: Direction,
+    ) -> Result<Option<Vec<GraphEdge>>, String> {
+        self.check_authorization_on(identity, AuthScope::ReadFacts, &Self::graph_resource())?;
+        Ok(self.graph.path(from, to, max_depth, direction))
+    }
+
+    fn retention_resource() -> Resource {
+        Resource::tree(retention::RETENTION_TREE)
+    }
+
+    /// Adds or replaces the retention policy for `policy.fact_type`. Requires
+    /// [`AuthScope::WritePolicy`] on the `retention` tree.
+    pub fn set_retention_policy(
+        &self,
+        identity: &AgentIdentity,
+        policy: RetentionPolicy,
+    ) -> Result<(), String> {
+        let resource = Self::retention_resource();
+        self.check_authorization_on(identity, AuthScope::WritePolicy, &resource)?;
+        let res = self.retention.set(&policy);
+        self.audit_mutation(&identity.id, "set_retention_policy", resource, &res);
+        res
+    }
+
+    /// Stops expiring `fact_type`; returns whether it had a policy. Requires
+    /// [`AuthScope::WritePolicy`] on the `retention` tree.
+    pub fn remove_retention_policy(
+        &self,
+        identity: &AgentIdentity,
+        fact_type: &str,
+    ) -> Result<bool, String> {
+        let resource = Self::retention_resource();
+        self.check_authorization_on(identity, AuthScope::WritePolicy, &resource)?;
+        let res = self.retention.remove(fact_type);
+        self.audit_mutation(&identity.id, "remove_retention_policy", resource, &res);
+        res
+    }
+
+    /// Requires [`AuthScope::ReadFacts`] on the `retention` tree.
+    pub fn retention_policies(
+        &self,
+        identity: &AgentIdentity,
+    ) -> Result<Vec<RetentionPolicy>, String> {
+        self.check_authorization_on(identity, AuthScope::ReadFacts, &Self::retention_resource())?;
+        Ok(self.retention.all())
+    }
+
+    /// Totals over every compaction run. Requires [`AuthScope::ReadFacts`] on the `retention`
+    /// tree.
+    pub fn retention_stats(&self, identity: &AgentIdentity) -> Result<RetentionStats, String> {
+        self.check_authorization_on(identity, AuthScope::ReadFacts, &Self::retention_resource())?;
+        Ok(self.retention.stats())
+    }
+
+    /// Applies every retention policy: expired facts are deleted, archived or rolled up, and
+    /// dropped from the embedding, full-text and graph indexes. Requires
+    /// [`AuthScope::WritePolicy`] on the `retention` tree.
+    pub fn compact_facts(&self, identity: &AgentIdentity) -> Result<CompactionReport, String> {
+        let resource = Self::retention_resource();
+        self.check_authorization_on(identity, AuthScope::WritePolicy, &resource)?;
+        let res = self.compact_facts_unchecked(unix_now());
+        self.audit_mutation(&identity.id, "compact_facts", resource, &res);
+        res
+    }
+
+    fn compact_facts_unchecked(&self, now: u64) -> Result<CompactionReport, String> {
+        let write_err = |e: sled::Error| format!("Compaction failed: {e}");
+        let policies: std::collections::HashMap<String, RetentionPolicy> = self
+            .retention
+            .all()
+            .into_iter()
+            .map(|p| (p.fact_type.clone(), p))
+            .collect();
+        let mut report = CompactionReport {
+            ran_at: now,
+            ..Default::default()
+        };
+        let tree = self
+            .knowledge_base
+            .open_tree(FACTS_TREE)
+            .map_err(write_err)?;
+        let archive = self
+            .knowledge_base
+            .open_tree(retention::FACTS_ARCHIVE_TREE)
+            .map_err(write_err)?;
+
+        // Oldest first, grouped by fact type.
+        let mut by_type: std::collections::BTreeMap<String, Vec<(String, AgentFact)>> =
+            std::collections::BTreeMap::new();
+        for (k, v) in tree.iter().filter_map(|res| res.ok()) {
+            let Ok(fact) = serde_json::from_slice::<AgentFact>(&v) else {
+                continue;
+            };
+            if policies.contains_key(&fact.fact_type) {
+                let key = String::from_utf8_lossy(&k).into_owned();
+                by_type.entry(fact.fact_type.clone()).or_default().push((key, fact));
+            }
+        }
+
+        let mut removed_bytes = 0;
+        let mut rollup_bytes = 0;
+        for (fact_type, entries) in by_type {
+            let policy = &policies[&fact_type];
+            report.scanned += entries.len();
+            let facts: Vec<AgentFact> = entries.iter().map(|(_, f)| f.clone()).collect();
+            let expired = policy.expired(&facts, now);
+            if expired.is_empty() {
+                continue;
+            }
+            report.expired_by_type.insert(fact_type.clone(), expired.len());
+
+            // Roll-ups and archive copies are written before the facts are removed, so a
+            // failure in between never loses a fact without its summary.
+            if policy.action == ExpiryAction::Summarize {
+                let mut rollups: std::collections::BTreeMap<&str, Vec<AgentFact>> =
+                    std::collections::BTreeMap::new();
+                for &i in &expired {
+                    let fact = &entries[i].1;
+                    rollups.entry(&fact.agent_id).or_default().push(fact.clone());
+                }
+                for (agent_id, facts) in rollups {
+                    let rollup = retention::rollup(&fact_type, agent_id, &facts);
+                    let size = serde_json::to_vec(&rollup).map_or(0, |v| v.len());
+                    let key = self.store_fact(rollup).map_err(write_err)?;
+                    rollup_bytes += (key.len() + size) as u64;
+                    report.rollups_written += 1;
+                }
+            }
+            for i in expired {
+                let (key, fact) = &entries[i];
+                if policy.action == ExpiryAction::Archive {
+                    let value = serde_json::to_vec(fact).expect("failed to serialize AgentFact");
+                    archive.insert(key.as_bytes(), value).map_err(write_err)?;
+                }
+                removed_bytes += self.remove_fact(key, fact)?;
+                match policy.action {
+                    ExpiryAction::Delete => report.deleted += 1,
+                    ExpiryAction::Archive => report.archived += 1,
+                    ExpiryAction::Summarize => report.summarized += 1,
+                }
+            }
+        }
+        archive.flush().map_err(write_err)?;
+        tree.flush().map_err(write_err)?;
+        report.reclaimed_bytes = removed_bytes.saturating_sub(rollup_bytes);
+        let stats = self.retention.record_run(&report)?;
+        event!(
+            Level::INFO,
+            removed = report.removed(),
+            rollups = report.rollups_written,
+            reclaimed_bytes = report.reclaimed_bytes,
+            total_reclaimed_bytes = stats.bytes_reclaimed,
+            "Compacted facts"
+        );
+        Ok(report)
+    }
+
+    /// Scores the query's nearest neighbours, the newest facts and the full-text matches for
+    /// its terms and keywords, keeping the top `k` that pass the query's filters and
+    /// `readable`. Like [`PAGICoreModel::semantic_search`], widens the pool while fewer than
+    /// `k` pass.
+    fn rank_facts(
+        &self,
+        query: &RetrievalQuery,
+        readable: impl Fn(&AgentFact) -> bool,
+    ) -> Result<Vec<RetrievedFact>, String> {
+        let tree = self
+            .knowledge_base
+            .open_tree(FACTS_TREE)
+            .map_err(|e| format!("KB read failed: {e}"))?;
+        let embedded = self.embedder().embed(&query.text);
+        let lexical = query
+            .fulltext_query()
+            .map(|q| self.fulltext.search(&q))
+            .unwrap_or_default();
+        let total = tree.len();
+        let now = unix_now();
+
+        let mut pool = (query.k * 4).max(64);
+        loop {
+            let mut candidates: std::collections::HashMap<String, f32> =
+                self.vectors.search(&embedded, pool).into_iter().collect();
+            let recent = tree
+                .iter()
+                .keys()
+                .rev()
+                .filter_map(|k| k.ok())
+                .take(pool)
+                .map(|k| String::from_utf8_lossy(&k).into_owned());
+            let matched = lexical.iter().take(pool).map(|(key, _)| key.clone());
+            for key in recent.chain(matched) {
+                if let std::collections::hash_map::Entry::Vacant(entry) = candidates.entry(key) {
+                    let similarity = self.vectors.similarity(entry.key(), &embedded);
+                    entry.insert(similarity.unwrap_or(0.0));
+                }
+            }
+
+            let mut ranked: Vec<RetrievedFact> = candidates
+                .into_iter()
+                .filter_map(|(key, similarity)| {
+                    let v = tree.get(key.as_bytes()).ok()??;
+                    let fact = serde_json::from_slice::<AgentFact>(&v).ok()?;
+                    (query.admits(&fact) && readable(&fact))
+                        .then(|| query.score(key, fact, similarity, now))
+                })
+                .collect();
+            if ranked.len() >= query.k || pool >= total {
+                ranked.sort_by(|a, b| {
+                    b.score.total_cmp(&a.score).then_with(|| a.key.cmp(&b.key))
+                });
+                ranked.truncate(query.k);
+                return Ok(ranked);
+            }
+            pool = (pool * 4).min(total);
+        }
+    }
+
     fn retrieve_facts_by_timestamp_unchecked(&self, start_ts: u128) -> Vec<AgentFact> {
         let start_ts_u64 = u64::try_from(start_ts).unwrap_or(u64::MAX);
 
@@ -436,9 +2364,302 @@ impl PAGICoreModel {
             .collect()
     }
 
+    /// Stores a reflection about `reflection.target_agent` as `Proposed`. Requires
+    /// [`AuthScope::WriteFacts`] on the reflection (see [`PAGICoreModel::reflection_resource`]).
+    pub fn propose_reflection(
+        &self,
+        identity: &AgentIdentity,
+        reflection: ReflectionFact,
+    ) -> Result<Reflection, String> {
+        let resource = Self::reflection_resource(&reflection.target_agent);
+        self.check_authorization_on(identity, AuthScope::WriteFacts, &resource)?;
+        let res = self.reflections.propose(&identity.id, reflection, unix_now());
+        self.audit_mutation(&identity.id, "propose_reflection", resource, &res);
+        res
+    }
+
+    /// Accepts, applies or reverts a reflection. Changing what steers the planner requires
+    /// [`AuthScope::WritePolicy`] on the reflection.
+    pub fn set_reflection_status(
+        &self,
+        identity: &AgentIdentity,
+        id: &str,
+        status: ReflectionStatus,
+    ) -> Result<Reflection, String> {
+        let resource = self.stored_reflection_resource(id)?;
+        self.check_authorization_on(identity, AuthScope::WritePolicy, &resource)?;
+        let res = self
+            .reflections
+            .transition(id, status, &identity.id, unix_now());
+        self.audit_mutation(&identity.id, "set_reflection_status", resource, &res);
+        res
+    }
+
+    /// Replaces a reflection with a new `Proposed` one for the same agent. Requires
+    /// [`AuthScope::WritePolicy`] on the reflection.
+    pub fn supersede_reflection(
+        &self,
+        identity: &AgentIdentity,
+        id: &str,
+        replacement: ReflectionFact,
+    ) -> Result<Reflection, String> {
+        let resource = self.stored_reflection_resource(id)?;
+        self.check_authorization_on(identity, AuthScope::WritePolicy, &resource)?;
+        let res = self
+            .reflections
+            .supersede(id, &identity.id, replacement, unix_now());
+        self.audit_mutation(&identity.id, "supersede_reflection", resource, &res);
+        res
+    }
+
+    /// Attaches a measured outcome to a reflection. Requires [`AuthScope::WriteFacts`] on
+    /// the reflection.
+    pub fn record_reflection_outcome(
+        &self,
+        identity: &AgentIdentity,
+        id: &str,
+        metric: &str,
+        value: f64,
+        note: Option<String>,
+    ) -> Result<Reflection, String> {
+        let resource = self.stored_reflection_resource(id)?;
+        self.check_authorization_on(identity, AuthScope::WriteFacts, &resource)?;
+        let outcome = ReflectionOutcome {
+            metric: metric.to_string(),
+            value,
+            note,
+            recorded_at: unix_now(),
+        };
+        let res = self.reflections.record_outcome(id, outcome);
+        self.audit_mutation(&identity.id, "record_reflection_outcome", resource, &res);
+        res
+    }
+
+    /// Reflections about `target_agent`, newest first. Requires [`AuthScope::ReadFacts`] on
+    /// its reflections.
+    pub fn reflections_for_agent(
+        &self,
+        identity: &AgentIdentity,
+        target_agent: &str,
+    ) -> Result<Vec<Reflection>, String> {
+        self.check_authorization_on(
+            identity,
+            AuthScope::ReadFacts,
+            &Self::reflection_resource(target_agent),
+        )?;
+        Ok(self.reflections.for_agent(target_agent))
+    }
+
+    /// Records how a task run went as an `ExecutionResult` fact attributed to `identity`,
+    /// the input to [`PAGICoreModel::generate_reflections`].
+    pub fn record_execution_result(
+        &self,
+        identity: &AgentIdentity,
+        result: &ExecutionResult,
+    ) -> Result<(), String> {
+        self.record_fact(identity, result.to_fact(&identity.id, unix_now()))
+    }
+
+    /// Analyzes the `ExecutionResult` facts recorded since `since_ts` and stores a
+    /// `Proposed` reflection for every agent type that trips a heuristic (see
+    /// [`reflection::generator`]). Directives already proposed or active for that agent are
+    /// skipped. Requires [`AuthScope::ReadFacts`] on the facts and
+    /// [`AuthScope::WriteFacts`] on the reflections.
+    pub fn generate_reflections(
+        &self,
+        identity: &AgentIdentity,
+        since_ts: u64,
+        config: GeneratorConfig,
+    ) -> Result<Vec<Reflection>, String> {
+        let facts = self.retrieve_facts_by_timestamp(identity, u128::from(since_ts))?;
+        let generator = ReflectionGenerator::new(config);
+        let proposals = generator.propose(&generator.analyze(&facts));
+
+        let mut stored = Vec::new();
+        for proposal in proposals {
+            let duplicate = self
+                .reflections
+                .for_agent(&proposal.target_agent)
+                .iter()
+                .any(|r| {
+                    r.new_directive == proposal.new_directive
+                        && r.superseded_by.is_none()
+                        && r.status != ReflectionStatus::Reverted
+                });
+            if !duplicate {
+                stored.push(self.propose_reflection(identity, proposal)?);
+            }
+        }
+        Ok(stored)
+    }
+
+    /// Starts an A/B experiment that applies a `Proposed` reflection to
+    /// `config.treatment_fraction` of plans. Changes what steers the planner, so requires
+    /// [`AuthScope::WritePolicy`] on the reflection.
+    pub fn start_experiment(
+        &self,
+        identity: &AgentIdentity,
+        reflection_id: &str,
+        config: ExperimentConfig,
+    ) -> Result<Experiment, String> {
+        let resource = self.stored_reflection_resource(reflection_id)?;
+        self.check_authorization_on(identity, AuthScope::WritePolicy, &resource)?;
+        let res = match self.reflections.get(reflection_id) {
+            Some(r) if r.status == ReflectionStatus::Proposed && r.superseded_by.is_none() => {
+                self.experiments
+                    .start(reflection_id, &r.target_agent, config, &identity.id, unix_now())
+            }
+            _ => Err(format!("Reflection '{reflection_id}' is not an open proposal")),
+        };
+        self.audit_mutation(&identity.id, "start_experiment", resource, &res);
+        res
+    }
+
+    /// Records how one plan of an experiment went; the arm comes from the plan's
+    /// [`ExperimentTag`]. Requires [`AuthScope::WriteFacts`] on the reflection.
+    pub fn record_experiment_outcome(
+        &self,
+        identity: &AgentIdentity,
+        tag: &ExperimentTag,
+        success: bool,
+        latency_ms: u64,
+    ) -> Result<(), String> {
+        let resource = self.experiment_resource(&tag.experiment_id)?;
+        self.check_authorization_on(identity, AuthScope::WriteFacts, &resource)?;
+        let sample = reflection::ExperimentSample {
+            arm: tag.arm,
+            success,
+            latency_ms,
+            recorded_at: unix_now(),
+        };
+        let res = self.experiments.record(&tag.experiment_id, sample);
+        self.audit_mutation(&identity.id, "record_experiment_outcome", resource, &res);
+        res
+    }
+
+    /// Success-rate and latency deltas between the arms. Requires [`AuthScope::ReadFacts`]
+    /// on the reflection.
+    pub fn experiment_report(
+        &self,
+        identity: &AgentIdentity,
+        experiment_id: &str,
+    ) -> Result<ExperimentReport, String> {
+        let resource = self.experiment_resource(experiment_id)?;
+        self.check_authorization_on(identity, AuthScope::ReadFacts, &resource)?;
+        self.experiments.report(experiment_id)
+    }
+
+    /// Ends an experiment and accepts or reverts its reflection. With `decision` `None`
+    /// the report's recommendation is used, which fails until both arms have enough
+    /// samples. Requires [`AuthScope::WritePolicy`] on the reflection.
+    pub fn conclude_experiment(
+        &self,
+        identity: &AgentIdentity,
+        experiment_id: &str,
+        decision: Option<ExperimentDecision>,
+    ) -> Result<ExperimentReport, String> {
+        let resource = self.experiment_resource(experiment_id)?;
+        self.check_authorization_on(identity, AuthScope::WritePolicy, &resource)?;
+        let res = self.experiments.report(experiment_id).and_then(|report| {
+            let decision = decision.or(report.recommendation).ok_or_else(|| {
+                format!("Experiment '{experiment_id}' does not have enough samples yet")
+            })?;
+            let status = match decision {
+                ExperimentDecision::Accept => ReflectionStatus::Accepted,
+                ExperimentDecision::Revert => ReflectionStatus::Reverted,
+            };
+            let reflection = self
+                .reflections
+                .get(&report.reflection_id)
+                .ok_or_else(|| format!("Unknown reflection '{}'", report.reflection_id))?;
+            if !reflection.status.can_transition_to(status) {
+                return Err(format!(
+                    "Reflection '{}' can't move from {:?} to {:?}",
+                    reflection.id, reflection.status, status
+                ));
+            }
+            let now = unix_now();
+            self.experiments
+                .conclude(experiment_id, decision, &identity.id, now)?;
+            if let Err(e) =
+                self.reflections
+                    .transition(&report.reflection_id, status, &identity.id, now)
+            {
+                // The reflection changed since the check; keep the experiment running.
+                self.experiments.reopen(experiment_id)?;
+                return Err(e);
+            }
+            Ok(report)
+        });
+        self.audit_mutation(&identity.id, "conclude_experiment", resource, &res);
+        res
+    }
+
+    /// The resource reflections about `target_agent` are authorized against: tree
+    /// `reflections`, fact type `Reflection`, agent `target_agent`.
+    pub fn reflection_resource(target_agent: &str) -> Resource {
+        Resource::fact(
+            reflection::REFLECTIONS_TREE,
+            reflection::REFLECTION_RESOURCE_TYPE,
+            target_agent,
+        )
+    }
+
+    fn stored_reflection_resource(&self, id: &str) -> Result<Resource, String> {
+        self.reflections
+            .get(id)
+            .map(|r| Self::reflection_resource(&r.target_agent))
+            .ok_or_else(|| format!("Unknown reflection '{id}'"))
+    }
+
+    fn experiment_resource(&self, experiment_id: &str) -> Result<Resource, String> {
+        self.experiments
+            .get(experiment_id)
+            .map(|e| Self::reflection_resource(&e.target_agent))
+            .ok_or_else(|| format!("Unknown experiment '{experiment_id}'"))
+    }
+
+    /// Records the plan an active reflection produced and marks it `Applied`.
+    fn mark_reflection_applied(&self, target_agent: &str, plan: &[Task]) {
+        let Some(active) = self.reflections.active_for(target_agent) else {
+            return;
+        };
+        let mut res = self.reflections.record_plan(&active.id, plan.to_vec());
+        if active.status == ReflectionStatus::Accepted {
+            res = res.and_then(|_| {
+                self.reflections.transition(
+                    &active.id,
+                    ReflectionStatus::Applied,
+                    "PAGICore",
+                    unix_now(),
+                )
+            });
+        }
+        if let Err(ref e) = res {
+            event!(
+                Level::ERROR,
+                reflection_id = %active.id,
+                error = %e,
+                "Failed to record applied reflection"
+            );
+        }
+        self.audit_mutation(
+            "PAGICore",
+            "apply_reflection",
+            Self::reflection_resource(target_agent),
+            &res,
+        );
+    }
+
+    /// The reflection steering `target_agent`: the newest active one in the reflection
+    /// store, otherwise the newest legacy `ReflectionFact` fact.
     fn latest_reflection_for_agent(&self, target_agent: &str) -> Option<ReflectionFact> {
-        // Reflections are stored as AgentFact entries with fact_type == "ReflectionFact" and
-        // JSON-encoded ReflectionFact in `content`.
+        if let Some(active) = self.reflections.active_for(target_agent) {
+            return Some(active.to_fact());
+        }
+
+        // Legacy reflections are stored as AgentFact entries with fact_type ==
+        // "ReflectionFact" and JSON-encoded ReflectionFact in `content`.
         let facts = self.retrieve_facts_by_timestamp_unchecked(0);
 
         facts
@@ -454,22 +2675,37 @@ impl PAGICoreModel {
 
     /// Initializes the IPC server (local socket listener) used for near-real-time status updates.
     ///
-    /// The listener is stored internally and can be extracted using [`PAGICoreModel::take_ipc_listener`].
+    /// The socket name is taken from [`IpcConfig::from_env`]. The listener is stored internally
+    /// and can be extracted using [`PAGICoreModel::take_ipc_listener`].
     pub fn init_ipc_server(&mut self) -> Result<(), String> {
+        self.init_ipc_server_with(&IpcConfig::from_env())
+    }
+
+    /// Like [`PAGICoreModel::init_ipc_server`], with an explicit [`IpcConfig`].
+    ///
+    /// A leftover socket is only removed if no live server answers on it, and the new socket
+    /// is restricted to the current user. This legacy listener is local-socket only; TCP
+    /// configs are served by [`IpcBus::bind_authenticated`].
+    pub fn init_ipc_server_with(&mut self, config: &IpcConfig) -> Result<(), String> {
         if self.ipc_listener.is_some() {
             return Ok(());
         }
 
-        // Best-effort cleanup on Unix if a prior run left the socket path behind.
-        #[cfg(unix)]
-        {
-            let _ = std::fs::remove_file(PAGI_IPC_NAME);
-        }
+        let name = match config.endpoint() {
+            IpcEndpoint::LocalSocket(name) => name,
+            tcp @ IpcEndpoint::Tcp(_) => {
+                return Err(format!(
+                    "init_ipc_server only supports local sockets ({tcp}); use IpcBus::bind_authenticated"
+                ))
+            }
+        };
+        ipc::prepare_socket_path(&name)?;
 
-        let listener = LocalSocketListener::bind(PAGI_IPC_NAME)
-            .map_err(|e| format!("Failed to bind IPC server ({}): {e}", PAGI_IPC_NAME))?;
+        let listener = ipc::with_private_umask(|| LocalSocketListener::bind(name.as_str()))
+            .map_err(|e| format!("Failed to bind IPC server ({name}): {e}"))?;
+        ipc::restrict_socket_permissions(&name)?;
 
-        self.ipc_name = PAGI_IPC_NAME.to_string();
+        self.ipc_name = name;
         self.ipc_listener = Some(listener);
         self.ipc_initialized = true;
         Ok(())
@@ -485,6 +2721,76 @@ impl PAGICoreModel {
         self.ipc_listener.take()
     }
 
+    /// Issues an IPC authentication token bound to `identity`.
+    ///
+    /// Hand the token to the agent process (e.g. via an environment variable) so it can
+    /// connect with [`IpcClient::connect_with_token`].
+    pub fn issue_ipc_token(&self, identity: &AgentIdentity) -> Result<String, String> {
+        self.ipc_tokens.issue(identity)
+    }
+
+    /// Returns a handle to this core's token registry, for use with
+    /// [`IpcBus::bind_authenticated`].
+    pub fn ipc_token_registry(&self) -> IpcTokenRegistry {
+        self.ipc_tokens.clone()
+    }
+
+    /// Sets the expected heartbeat interval and how many may be missed before an agent is
+    /// considered lost.
+    pub fn configure_heartbeats(&self, config: HeartbeatConfig) {
+        self.liveness.set_config(config);
+    }
+
+    /// Returns a handle to the liveness table (shared, not a copy).
+    pub fn liveness_tracker(&self) -> LivenessTracker {
+        self.liveness.clone()
+    }
+
+    /// Current liveness of every agent seen over IPC.
+    pub fn agent_liveness(&self) -> Vec<AgentLiveness> {
+        self.liveness.snapshot()
+    }
+
+    /// Marks agents that missed too many heartbeats as lost and records an `AgentLost` fact
+    /// for each, so rules can react. Returns the newly lost agents.
+    ///
+    /// [`IpcBus::track_liveness`] calls this once per heartbeat interval.
+    pub fn sweep_liveness(&self) -> Vec<AgentLiveness> {
+        let lost = self.liveness.sweep();
+
+        for agent in &lost {
+            event!(
+                Level::WARN,
+                agent_id = %agent.agent_id,
+                last_seen = agent.last_seen,
+                "Agent missed heartbeats; marking lost"
+            );
+
+            let fact = AgentFact {
+                agent_id: "PAGICore".to_string(),
+                timestamp: unix_now(),
+                fact_type: ipc::AGENT_LOST_FACT_TYPE.to_string(),
+                content: serde_json::json!({
+                    "agent_id": agent.agent_id,
+                    "last_seen": agent.last_seen,
+                    "current_task": agent.current_task,
+                    "state": agent.state,
+                })
+                .to_string(),
+            };
+            let resource = Resource::fact(FACTS_TREE, &fact.fact_type, &fact.agent_id);
+            let res = self
+                .record_fact_unchecked(fact)
+                .map_err(|e| format!("KB write failed: {e}"));
+            if let Err(ref e) = res {
+                event!(Level::ERROR, error = %e, "Failed to record AgentLost fact");
+            }
+            self.audit_mutation("PAGICore", "record_fact", resource, &res);
+        }
+
+        lost
+    }
+
     /// Produces a high-level plan from a user prompt.
     ///
     /// For the example prompt:
@@ -503,34 +2809,96 @@ impl PAGICoreModel {
         // Always keep the fast-path deterministic for security triage.
         let lowered = prompt.to_lowercase();
         if lowered.contains("siem") || lowered.contains("crowdstrike") || lowered.contains("rapid7") {
-            return self.general_reasoning_fallback(prompt);
+            return self.general_reasoning_fallback(prompt, &PlanningContext::default());
         }
 
+        // Consult memory first: symbolic directives from the whole KB, plus the facts most
+        // relevant to the prompt (semantic similarity, keywords and recency) as context.
+        let context = self.core_planning_context(prompt);
+
         // If the orchestrator didn't/couldn't provide an LLM response, fall back to the
         // symbolic/rule-based planner.
         if llm_response_json.trim().is_empty()
             || std::env::var("PAGI_DISABLE_LLM").ok().as_deref() == Some("1")
         {
-            return self.general_reasoning_fallback(prompt);
+            return self.general_reasoning_fallback(prompt, &context);
+        }
+
+        // Parse the LLM plan; if parsing fails, fall back.
+        match self.parse_llm_plan(llm_response_json) {
+            Ok(tasks) if !tasks.is_empty() => {
+                // Symbolic integration: apply symbolic directives over LLM output, and
+                // reflections when there are none.
+                if context.directives.is_empty() {
+                    Ok(self.apply_reflections_to_plan(tasks))
+                } else {
+                    Ok(self.apply_symbolic_directives_to_plan(tasks, &context.directives))
+                }
+            }
+            Ok(_) => self.general_reasoning_fallback(prompt, &context),
+            Err(_) => self.general_reasoning_fallback(prompt, &context),
         }
+    }
+
+    /// Rewrites `plan` with the [`PlanTransform`]s of the reflections steering its agents.
+    ///
+    /// For each agent type in the plan, a running experiment on that agent takes precedence:
+    /// its proposal is applied on the treatment arm only, and the whole plan is tagged with
+    /// the arm. At most one experiment is assigned per plan. Agents without an experiment
+    /// use their active reflection, which is then marked `Applied`.
+    fn apply_reflections_to_plan(&self, plan: Plan) -> Plan {
+        let mut agents: Vec<String> = plan.iter().map(|t| t.agent_type.clone()).collect();
+        agents.sort();
+        agents.dedup();
 
-        // Parse the LLM plan; if parsing fails, fall back.
-        match self.parse_llm_plan(llm_response_json) {
-            Ok(tasks) if !tasks.is_empty() => {
-                // Symbolic integration: apply symbolic directives over LLM output.
-                let directives = self.resolve_symbolic_directives();
-                if directives.is_empty() {
-                    Ok(tasks)
-                } else {
-                    Ok(self.apply_symbolic_directives_to_plan(tasks, &directives))
+        let mut plan = plan;
+        let mut tag = None;
+        let mut applied = Vec::new();
+        for agent in agents {
+            if tag.is_none() {
+                match self.experiments.assign(&agent) {
+                    Ok(Some((experiment, arm))) => {
+                        let proposal = self.reflections.get(&experiment.reflection_id);
+                        if let (ExperimentArm::Treatment, Some(r)) = (arm, proposal) {
+                            let transforms =
+                                PlanTransform::from_directive(&agent, &r.new_directive);
+                            plan = apply_transforms(plan, &transforms, &r.new_directive);
+                        }
+                        tag = Some(ExperimentTag {
+                            experiment_id: experiment.id,
+                            arm,
+                        });
+                        continue;
+                    }
+                    Ok(None) => {}
+                    Err(e) => event!(Level::ERROR, error = %e, "Failed to assign experiment arm"),
                 }
             }
-            Ok(_) => self.general_reasoning_fallback(prompt),
-            Err(_) => self.general_reasoning_fallback(prompt),
+
+            let Some(reflection) = self.latest_reflection_for_agent(&agent) else {
+                continue;
+            };
+            let transforms = PlanTransform::from_directive(&agent, &reflection.new_directive);
+            if !transforms.is_empty() {
+                plan = apply_transforms(plan, &transforms, &reflection.new_directive);
+                applied.push(agent);
+            }
+        }
+
+        for agent in applied {
+            self.mark_reflection_applied(&agent, &plan);
+        }
+        match tag {
+            Some(tag) => tag.apply(plan),
+            None => plan,
         }
     }
 
-    fn general_reasoning_fallback(&self, prompt: &str) -> Result<Vec<Task>, String> {
+    fn general_reasoning_fallback(
+        &self,
+        prompt: &str,
+        context: &PlanningContext,
+    ) -> Result<Vec<Task>, String> {
         let normalized = prompt.trim();
         let lowered = normalized.to_lowercase();
 
@@ -571,58 +2939,12 @@ impl PAGICoreModel {
             ];
 
             // Symbolic integration: prioritize symbolic directives over reflection.
-            let directives = self.resolve_symbolic_directives();
-            if !directives.is_empty() {
-                return Ok(self.apply_symbolic_directives_to_plan(base_plan, &directives));
-            }
-
-            // Reflection fallback: if no symbolic directive is ready, use reflection facts.
-            if let Some(reflection) = self.latest_reflection_for_agent("SearchAgent") {
-                let directive = reflection.new_directive.to_lowercase();
-                if directive.contains("split") || directive.contains("concurr") {
-                    let tasks = vec![
-                        Task {
-                            agent_type: "SearchAgent".to_string(),
-                            input_data: serde_json::json!({
-                                "query": "top anti-aging compounds overview",
-                                "deliverable": "high-level summary",
-                                "directive_applied": reflection.new_directive,
-                            })
-                            .to_string(),
-                        },
-                        Task {
-                            agent_type: "SearchAgent".to_string(),
-                            input_data: serde_json::json!({
-                                "query": "anti-aging: rapamycin metformin spermidine",
-                                "deliverable": "mechanisms + evidence",
-                                "directive_applied": reflection.new_directive,
-                            })
-                            .to_string(),
-                        },
-                        Task {
-                            agent_type: "SearchAgent".to_string(),
-                            input_data: serde_json::json!({
-                                "query": "anti-aging: senolytics fisetin quercetin",
-                                "deliverable": "senolytic candidates summary",
-                                "directive_applied": reflection.new_directive,
-                            })
-                            .to_string(),
-                        },
-                        Task {
-                            agent_type: "CalendarAgent".to_string(),
-                            input_data: serde_json::json!({
-                                "title": "Anti-aging compounds research review",
-                                "timeframe": "next week",
-                                "agenda": "Present research findings and next steps",
-                            })
-                            .to_string(),
-                        },
-                    ];
-                    return Ok(tasks);
-                }
+            if !context.directives.is_empty() {
+                return Ok(self.apply_symbolic_directives_to_plan(base_plan, &context.directives));
             }
 
-            Ok(base_plan)
+            // Reflection fallback: if no symbolic directive is ready, use reflections.
+            Ok(self.apply_reflections_to_plan(base_plan))
         } else {
             Err("No planning rule matched this prompt (stub planner).".to_string())
         }
@@ -639,7 +2961,7 @@ mod tests {
             .temporary(true)
             .open()
             .expect("failed to open temporary sled db");
-        let model = PAGICoreModel::from_db(db);
+        let model = PAGICoreModel::from_db(db).require_credentials(false);
         let prompt = "Please research the top anti-aging compounds and schedule a team meeting for next week to present the findings.";
         let tasks = model
             // In tests we pass an empty LLM response so the core uses the deterministic fallback.
@@ -652,13 +2974,37 @@ mod tests {
         assert_eq!(tasks[1].agent_type, "CalendarAgent");
     }
 
+    #[test]
+    fn sweep_liveness_records_agent_lost_fact() {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("failed to open temporary sled db");
+        let model = PAGICoreModel::from_db(db).require_credentials(false);
+        model.configure_heartbeats(HeartbeatConfig {
+            interval: std::time::Duration::from_millis(1),
+            missed_threshold: 1,
+        });
+
+        model.liveness_tracker().observe("SearchAgent", None);
+        std::thread::sleep(std::time::Duration::from_millis(10));
+
+        let lost = model.sweep_liveness();
+        assert_eq!(lost.len(), 1);
+
+        let facts = model.retrieve_facts_by_timestamp_unchecked(0);
+        assert_eq!(facts.len(), 1);
+        assert_eq!(facts[0].fact_type, "AgentLost");
+        assert!(facts[0].content.contains("SearchAgent"));
+    }
+
     #[test]
     fn apply_rules_to_facts_returns_directive_on_match() {
         let db = sled::Config::new()
             .temporary(true)
             .open()
             .expect("failed to open temporary sled db");
-        let model = PAGICoreModel::from_db(db);
+        let model = PAGICoreModel::from_db(db).require_credentials(false);
 
         let facts = vec![AgentFact {
             agent_id: "ReflectiveAgent".to_string(),
@@ -670,4 +3016,1091 @@ mod tests {
         let directives = model.apply_rules_to_facts(facts);
         assert!(directives.iter().any(|d| d.contains("Deep Search")));
     }
+
+    #[test]
+    fn resource_scoped_grants_limit_writes_and_filter_reads() {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("failed to open temporary sled db");
+        let model = PAGICoreModel::from_db(db).require_credentials(false);
+        let fact = |agent_id: &str, fact_type: &str| AgentFact {
+            agent_id: agent_id.to_string(),
+            timestamp: 1,
+            fact_type: fact_type.to_string(),
+            content: "Failure: timeout".to_string(),
+        };
+
+        let search = AgentIdentity::new(
+            "SearchAgent",
+            vec![
+                AuthScope::scoped(
+                    AuthScope::WriteFacts,
+                    ResourcePattern::fact_type("AnalysisResult"),
+                ),
+                AuthScope::scoped(AuthScope::ReadFacts, ResourcePattern::own_facts()),
+            ],
+        );
+        let admin = AgentIdentity::new("Orchestrator", vec![AuthScope::WriteFacts]);
+
+        model
+            .record_fact(&search, fact("SearchAgent", "AnalysisResult"))
+            .expect("allowed fact type");
+        assert!(model
+            .record_fact(&search, fact("SearchAgent", "CalendarEvent"))
+            .is_err());
+        model
+            .record_fact(&admin, fact("CalendarAgent", "AnalysisResult"))
+            .expect("unrestricted write");
+
+        let visible = model
+            .retrieve_facts_by_timestamp(&search, 0)
+            .expect("scoped read");
+        assert_eq!(visible.len(), 1);
+        assert_eq!(visible[0].agent_id, "SearchAgent");
+        // Scoped to facts, not directives.
+        assert!(model.symbolic_directives(&search).expect("ok").is_empty());
+
+        // With WriteFacts => ReadFacts configured, the unrestricted writer can read everything.
+        assert!(model.retrieve_facts_by_timestamp(&admin, 0).is_err());
+        let implied_read = || {
+            AuthorizationGatekeeper::new()
+                .with_implication(AuthScope::WriteFacts, AuthScope::ReadFacts)
+        };
+        assert!(model.configure_authorization(&admin, implied_read()).is_err());
+        let policy_admin = AgentIdentity::new("PolicyAdmin", vec![AuthScope::WritePolicy]);
+        model
+            .configure_authorization(&policy_admin, implied_read())
+            .expect("WritePolicy may configure the gatekeeper");
+        assert_eq!(
+            model
+                .retrieve_facts_by_timestamp(&admin, 0)
+                .expect("implied read")
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn deny_policy_requires_write_policy_and_overrides_scopes() {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("failed to open temporary sled db");
+        let model = PAGICoreModel::from_db(db).require_credentials(false);
+        let admin = AgentIdentity::new("Orchestrator", vec![AuthScope::WritePolicy]);
+        let search = AgentIdentity::new(
+            "SearchAgent",
+            vec![AuthScope::ReadFacts, AuthScope::WriteFacts],
+        );
+        let deny = PolicyStatement {
+            id: "no_search_cyber".to_string(),
+            effect: Effect::Deny,
+            principal: "SearchAgent".to_string(),
+            action: AuthScope::WriteFacts,
+            resource: ResourcePattern::fact_type("CyberAlert"),
+            conditions: vec![],
+        };
+        let cyber = Resource::fact("facts", "CyberAlert", "SearchAgent");
+
+        assert!(model.put_policy(&search, deny.clone()).is_err());
+        assert!(model.simulate(&search, AuthScope::WriteFacts, &cyber).allowed);
+        model.put_policy(&admin, deny).expect("admin may write policy");
+
+        let decision = model.simulate(&search, AuthScope::WriteFacts, &cyber);
+        assert_eq!(
+            decision.reason,
+            DecisionReason::ExplicitDeny("no_search_cyber".to_string())
+        );
+        let err = model
+            .record_fact(
+                &search,
+                AgentFact {
+                    agent_id: "SearchAgent".to_string(),
+                    timestamp: 1,
+                    fact_type: "CyberAlert".to_string(),
+                    content: "CYBER_ALERT".to_string(),
+                },
+            )
+            .expect_err("denied by policy");
+        assert!(err.contains("no_search_cyber"));
+        assert_eq!(model.policies(&search).expect("read").len(), 2);
+
+        // Both the policy write and the denial are on the audit chain.
+        let records = model.audit_records(&search).expect("audit read");
+        assert!(records
+            .iter()
+            .any(|r| r.event.action == "put_policy" && r.event.outcome == AuditOutcome::Succeeded));
+        assert!(records.iter().any(|r| r.event.outcome == AuditOutcome::Denied
+            && r.event.detail.as_deref().is_some_and(|d| d.contains("no_search_cyber"))));
+        let head = model.verify_audit_log().expect("intact");
+        assert_eq!(head.len, records.len() as u64);
+        assert_eq!(model.verify_audit_log_anchor(&head), Ok(head.clone()));
+    }
+
+    #[test]
+    fn cores_sharing_a_db_extend_one_audit_chain() {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("failed to open temporary sled db");
+        let first = PAGICoreModel::from_db(db.clone()).require_credentials(false);
+        let second = PAGICoreModel::from_db(db).require_credentials(false);
+        let writer = AgentIdentity::new("SearchAgent", vec![AuthScope::WriteFacts]);
+        for (i, model) in [&first, &second, &first, &second].into_iter().enumerate() {
+            model
+                .record_fact(
+                    &writer,
+                    AgentFact {
+                        agent_id: "SearchAgent".to_string(),
+                        timestamp: i as u64,
+                        fact_type: "AnalysisResult".to_string(),
+                        content: format!("result {i}"),
+                    },
+                )
+                .expect("write");
+        }
+
+        let head = first.verify_audit_log().expect("intact");
+        assert_eq!(second.verify_audit_log(), Ok(head.clone()));
+        // An authorization decision and a mutation per write.
+        assert_eq!(head.len, 8);
+    }
+
+    #[test]
+    fn enforced_credentials_replace_caller_built_scopes() {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("failed to open temporary sled db");
+        let mut model = PAGICoreModel::from_db(db);
+        let operator = model
+            .bootstrap_credential(
+                &AgentIdentity::new("Operator", vec![AuthScope::WritePolicy]),
+                std::time::Duration::from_secs(60),
+            )
+            .expect("bootstrap");
+
+        let search = AgentIdentity::new("SearchAgent", vec![AuthScope::WriteFacts]);
+        let fact = AgentFact {
+            agent_id: "SearchAgent".to_string(),
+            timestamp: 1,
+            fact_type: "AnalysisResult".to_string(),
+            content: "Success".to_string(),
+        };
+        assert!(model.record_fact(&search, fact.clone()).is_err());
+
+        // Only a verified WritePolicy holder can issue credentials or reach the keys.
+        let ttl = std::time::Duration::from_secs(60);
+        let self_issued = AgentIdentity::new("SearchAgent", vec![AuthScope::WritePolicy]);
+        assert!(model.issue_credential(&self_issued, &search, ttl).is_err());
+        assert!(model.credential_authority(&self_issued).is_err());
+        assert!(model.credential_authority(&operator).is_ok());
+        let credentialed = model
+            .issue_credential(&operator, &search, ttl)
+            .expect("issue");
+        model
+            .record_fact(&credentialed, fact)
+            .expect("credentialed write");
+
+        // Adding scopes to the struct doesn't add them to the credential.
+        let mut inflated = credentialed.clone();
+        inflated.scopes.push(AuthScope::ReadFacts);
+        assert!(model.retrieve_facts_by_timestamp(&inflated, 0).is_err());
+    }
+
+    #[test]
+    fn delegated_and_elevated_scopes_are_limited_and_audited() {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("failed to open temporary sled db");
+        let model = PAGICoreModel::from_db(db).require_credentials(false);
+        let orchestrator = AgentIdentity::new("Orchestrator", vec![AuthScope::WritePolicy]);
+        let cyber = AgentIdentity::new("CybersecurityAgent", vec![AuthScope::ReadFacts]);
+        let quarantine = |id: &str| PolicyStatement {
+            id: id.to_string(),
+            effect: Effect::Deny,
+            principal: "*".to_string(),
+            action: AuthScope::ReadFacts,
+            resource: ResourcePattern::directive_kind("Rerun"),
+            conditions: Vec::new(),
+        };
+        let ttl = std::time::Duration::from_secs(60);
+
+        assert!(model.put_policy(&cyber, quarantine("q1")).is_err());
+        assert!(model
+            .delegate(&cyber, "SearchAgent", vec![AuthScope::WritePolicy], ttl, None)
+            .is_err());
+
+        // A single-use delegation.
+        model
+            .delegate(
+                &orchestrator,
+                "CybersecurityAgent",
+                vec![AuthScope::WritePolicy],
+                ttl,
+                Some(1),
+            )
+            .expect("delegate");
+        model.put_policy(&cyber, quarantine("q1")).expect("delegated write");
+        assert!(model.put_policy(&cyber, quarantine("q2")).is_err());
+        assert!(model.active_delegations(&cyber).unwrap().is_empty());
+
+        // Just-in-time elevation needs someone else's approval.
+        let request = model
+            .request_elevation(&cyber, vec![AuthScope::WritePolicy], ttl, None, "quarantine q2")
+            .expect("request");
+        assert!(model.approve_elevation(&cyber, &request.id).is_err());
+        let granted = model
+            .approve_elevation(&orchestrator, &request.id)
+            .expect("approve");
+        assert_eq!(granted.delegator, "Orchestrator");
+        model.put_policy(&cyber, quarantine("q2")).expect("elevated write");
+        assert!(matches!(
+            model.elevation_requests(&cyber).unwrap()[0].status,
+            ElevationStatus::Approved { .. }
+        ));
+
+        // Reads charge a use-limited delegation once per call, and side-effect-free checks
+        // don't see it at all.
+        model
+            .delegate(&cyber, "Dashboard", vec![AuthScope::ReadFacts], ttl, Some(2))
+            .expect("delegate");
+        let dashboard = AgentIdentity::new("Dashboard", vec![]);
+        let any_fact = Resource::fact(FACTS_TREE, "AnalysisResult", "SearchAgent");
+        assert!(!model.is_authorized_on(&dashboard, &AuthScope::ReadFacts, &any_fact));
+        model
+            .retrieve_facts_by_timestamp(&dashboard, 0)
+            .expect("first delegated read");
+        model
+            .symbolic_directives(&dashboard)
+            .expect("second delegated read");
+        assert!(model.search_facts(&dashboard, "anything", 5).is_err());
+
+        // Expired delegations stop applying and are revoked by the sweep.
+        model
+            .delegate(
+                &orchestrator,
+                "CalendarAgent",
+                vec![AuthScope::WritePolicy],
+                std::time::Duration::ZERO,
+                None,
+            )
+            .expect("delegate");
+        let calendar = AgentIdentity::new("CalendarAgent", vec![]);
+        assert!(model.put_policy(&calendar, quarantine("q3")).is_err());
+        assert!(model.sweep_delegations().is_empty());
+
+        let actions: Vec<String> = model
+            .audit
+            .records()
+            .into_iter()
+            .map(|r| r.event.action)
+            .collect();
+        for action in ["delegate", "use_delegation", "approve_elevation", "expire_delegation"] {
+            assert!(actions.iter().any(|a| a == action), "missing {action}");
+        }
+    }
+
+    #[test]
+    fn quotas_reject_writes_with_a_distinct_error() {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("failed to open temporary sled db");
+        let model = PAGICoreModel::from_db(db).require_credentials(false);
+        let search = AgentIdentity::new("SearchAgent", vec![AuthScope::WriteFacts]);
+        let admin = AgentIdentity::new("Orchestrator", vec![AuthScope::WritePolicy]);
+        let rules = vec![QuotaRule::new(
+            "search_writes",
+            "SearchAgent",
+            AuthScope::WriteFacts,
+            QuotaUnit::Requests,
+            QuotaLimit::per_minute(1),
+        )];
+        assert!(model.configure_quotas(&search, Vec::new()).is_err());
+        model.configure_quotas(&admin, rules).expect("configure");
+        let fact = AgentFact {
+            agent_id: "SearchAgent".to_string(),
+            timestamp: 1,
+            fact_type: "AnalysisResult".to_string(),
+            content: "Success".to_string(),
+        };
+
+        model.record_fact(&search, fact.clone()).expect("first write");
+        let err = model.record_fact(&search, fact.clone()).unwrap_err();
+        assert!(quota::is_quota_exceeded(&err), "{err}");
+
+        // A missing grant is not a quota error.
+        let calendar = AgentIdentity::new("CalendarAgent", vec![]);
+        let err = model.record_fact(&calendar, fact).unwrap_err();
+        assert!(!quota::is_quota_exceeded(&err));
+
+        let usage = model.quota_usage();
+        assert_eq!(usage.len(), 1);
+        assert_eq!((usage[0].consumed, usage[0].rejected), (1, 1));
+        assert!(model.audit.records().iter().any(|r| r.event.outcome == AuditOutcome::Denied
+            && r.event.detail.as_deref().is_some_and(quota::is_quota_exceeded)));
+    }
+
+    #[test]
+    fn gated_actions_wait_for_approval_and_queued_facts_are_written() {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("failed to open temporary sled db");
+        let model = PAGICoreModel::from_db(db).require_credentials(false);
+        let mut notices = model.approval_notices();
+        let robot = AgentIdentity::new("RoboticsAgent", vec![AuthScope::RoboticsAction]);
+        let operator = AgentIdentity::new("Operator", vec![AuthScope::WritePolicy]);
+        let arm = Resource::tree("arm");
+        assert!(model
+            .configure_approvals(&robot, Some(ApprovalConfig::default()))
+            .is_err());
+        model
+            .configure_approvals(&operator, Some(ApprovalConfig::default()))
+            .expect("configure");
+        // One move per minute; pending attempts must not use it up.
+        model
+            .configure_quotas(
+                &operator,
+                vec![QuotaRule::new(
+                    "arm_moves",
+                    "RoboticsAgent",
+                    AuthScope::RoboticsAction,
+                    QuotaUnit::Requests,
+                    QuotaLimit::per_minute(1),
+                )],
+            )
+            .expect("configure");
+
+        let err = model
+            .check_authorization_on(&robot, AuthScope::RoboticsAction, &arm)
+            .unwrap_err();
+        assert!(approval::is_approval_pending(&err), "{err}");
+        let request = model.approval_requests(&robot).unwrap().remove(0);
+        assert!(matches!(
+            notices.try_recv(),
+            Ok(IpcMessage::ApprovalRequested { .. })
+        ));
+        assert!(model.approve_action(&robot, &request.id).is_err());
+
+        model.approve_action(&operator, &request.id).expect("approve");
+        model
+            .check_authorization_on(&robot, AuthScope::RoboticsAction, &arm)
+            .expect("approved once");
+        let err = model
+            .check_authorization_on(&robot, AuthScope::RoboticsAction, &arm)
+            .unwrap_err();
+        assert!(approval::is_approval_pending(&err));
+
+        // Gated facts are queued with the request and written on approval.
+        let fact = AgentFact {
+            agent_id: "RoboticsAgent".to_string(),
+            timestamp: 1,
+            fact_type: "RoboticsAction".to_string(),
+            content: "move arm".to_string(),
+        };
+        let err = model.record_fact(&robot, fact.clone()).unwrap_err();
+        assert!(approval::is_approval_pending(&err));
+        assert!(model.retrieve_facts_by_timestamp_unchecked(0).is_empty());
+        let queued = model
+            .approval_requests(&operator)
+            .unwrap()
+            .into_iter()
+            .find(|r| r.fact.is_some())
+            .expect("queued fact");
+        model.approve_action(&operator, &queued.id).expect("approve");
+        assert_eq!(model.retrieve_facts_by_timestamp_unchecked(0), vec![fact]);
+
+        // Scopes outside the gate are unaffected.
+        let search = AgentIdentity::new("SearchAgent", vec![AuthScope::WriteFacts]);
+        model
+            .check_authorization(&search, AuthScope::WriteFacts)
+            .expect("ungated");
+    }
+
+    #[tokio::test]
+    async fn accepted_reflections_steer_the_fallback_planner() {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("failed to open temporary sled db");
+        let model = PAGICoreModel::from_db(db).require_credentials(false);
+        let prompt = "Please research the top anti-aging compounds and schedule a team meeting for next week to present the findings.";
+        let reflective = AgentIdentity::new("ReflectiveAgent", vec![AuthScope::WriteFacts]);
+        let orchestrator = AgentIdentity::new("Orchestrator", vec![AuthScope::WritePolicy]);
+
+        let proposed = model
+            .propose_reflection(
+                &reflective,
+                ReflectionFact {
+                    target_agent: "SearchAgent".to_string(),
+                    critique: "Broad query timed out".to_string(),
+                    new_directive: "Split the query into concurrent sub-queries".to_string(),
+                },
+            )
+            .expect("propose");
+        // Proposals don't steer planning, and only WritePolicy holders can accept them.
+        assert_eq!(model.general_reasoning(prompt, "").await.unwrap().len(), 2);
+        assert!(model
+            .set_reflection_status(&reflective, &proposed.id, ReflectionStatus::Accepted)
+            .is_err());
+
+        model
+            .set_reflection_status(&orchestrator, &proposed.id, ReflectionStatus::Accepted)
+            .expect("accept");
+        let plan = model.general_reasoning(prompt, "").await.unwrap();
+        assert_eq!(plan.len(), 4);
+
+        let reader = AgentIdentity::new("Dashboard", vec![AuthScope::ReadFacts]);
+        let stored = model
+            .reflections_for_agent(&reader, "SearchAgent")
+            .expect("read")
+            .remove(0);
+        assert_eq!(stored.status, ReflectionStatus::Applied);
+        assert_eq!(stored.modified_plan.map(|p| p.len()), Some(4));
+    }
+
+    #[test]
+    fn semantic_search_ranks_by_similarity_within_filters_and_grants() {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("failed to open temporary sled db");
+        let model = PAGICoreModel::from_db(db.clone()).require_credentials(false);
+        let writer = AgentIdentity::new("Orchestrator", vec![AuthScope::WriteFacts]);
+        for (i, (fact_type, content)) in [
+            ("SearchResult", "rapamycin extends lifespan in mice"),
+            ("SearchResult", "quarterly budget review meeting notes"),
+            ("AnalysisResult", "rapamycin and metformin lifespan comparison"),
+            ("CyberAlert", "rapamycin lifespan phishing lure detected"),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            model
+                .record_fact(
+                    &writer,
+                    AgentFact {
+                        agent_id: "SearchAgent".to_string(),
+                        timestamp: 10 + i as u64,
+                        fact_type: fact_type.to_string(),
+                        content: content.to_string(),
+                    },
+                )
+                .expect("record");
+        }
+
+        let reader = AgentIdentity::new(
+            "Researcher",
+            vec![
+                AuthScope::scoped(AuthScope::ReadFacts, ResourcePattern::fact_type("SearchResult")),
+                AuthScope::scoped(
+                    AuthScope::ReadFacts,
+                    ResourcePattern::fact_type("AnalysisResult"),
+                ),
+            ],
+        );
+        let hits = model
+            .semantic_search(&reader, "rapamycin lifespan", 3, &FactFilter::default())
+            .expect("search");
+        // The CyberAlert is the closest match but not readable.
+        assert_eq!(hits.len(), 3);
+        assert!(hits.iter().all(|h| h.fact.fact_type != "CyberAlert"));
+        assert!(hits[0].fact.content.contains("rapamycin"));
+        assert!(hits[2].fact.content.contains("budget"));
+        assert!(hits[0].score > hits[2].score);
+
+        let filtered = model
+            .semantic_search(
+                &reader,
+                "rapamycin lifespan",
+                3,
+                &FactFilter::fact_type("AnalysisResult"),
+            )
+            .expect("search");
+        assert_eq!(filtered.len(), 1);
+        assert!(model
+            .semantic_search(&writer, "rapamycin", 1, &FactFilter::default())
+            .is_err());
+
+        // Switching embedders re-embeds every fact.
+        assert_eq!(
+            model.configure_embedder(Arc::new(HashingEmbedder::new(32))),
+            Ok(4)
+        );
+        assert_eq!(
+            model.configure_embedder(Arc::new(HashingEmbedder::new(32))),
+            Ok(0)
+        );
+        // Facts without vectors (e.g. written before the index existed) are embedded on open.
+        model.vectors.clear().expect("clear");
+        drop(model);
+        let model = PAGICoreModel::from_db(db).require_credentials(false);
+        assert_eq!(model.vectors.len(), 4);
+    }
+
+    #[test]
+    fn search_facts_ranks_matches_and_drives_query_rules() {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("failed to open temporary sled db");
+        let mut model = PAGICoreModel::from_db(db).require_credentials(false);
+        let writer = AgentIdentity::new("Orchestrator", vec![AuthScope::WriteFacts]);
+        for (i, (fact_type, content)) in [
+            ("SearchResult", "rapamycin extends lifespan in mice; rapamycin dosing"),
+            ("SearchResult", "metformin and rapamycin in worms"),
+            ("AnalysisResult", "phishing drill scheduled for finance"),
+            ("AnalysisResult", "phishing campaign targeting finance credentials"),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            model
+                .record_fact(
+                    &writer,
+                    AgentFact {
+                        agent_id: "SearchAgent".to_string(),
+                        timestamp: 10 + i as u64,
+                        fact_type: fact_type.to_string(),
+                        content: content.to_string(),
+                    },
+                )
+                .expect("record");
+        }
+
+        let reader = AgentIdentity::new("Researcher", vec![AuthScope::ReadFacts]);
+        let hits = model.search_facts(&reader, "rapamycin", 10).expect("search");
+        assert_eq!(hits.len(), 2);
+        assert!(hits[0].fact.content.contains("dosing"));
+        let hits = model
+            .search_facts(&reader, "\"phishing campaign\" OR worms", 10)
+            .expect("search");
+        assert_eq!(hits.len(), 2);
+        assert!(model.search_facts(&reader, "(rapamycin", 10).is_err());
+        assert!(model.search_facts(&writer, "rapamycin", 10).is_err());
+
+        let rule = || {
+            PAGIRule::new(
+                "rule_phishing",
+                "AnalysisResult",
+                "",
+                "TASK: CybersecurityAgent, INPUT: Phishing",
+            )
+        };
+        assert!(model.add_rule(rule().with_query("(phishing")).is_err());
+        model
+            .add_rule(rule().with_query("phishing AND NOT drill"))
+            .expect("valid rule");
+        assert!(model.add_rule(rule()).is_err(), "duplicate rule id");
+        assert_eq!(
+            model.resolve_symbolic_directives(),
+            ["TASK: CybersecurityAgent, INPUT: Phishing"]
+        );
+        let drill_only = model.retrieve_facts_by_timestamp_unchecked(12);
+        assert!(model.apply_rules_to_facts(drill_only[..1].to_vec()).is_empty());
+    }
+
+    #[test]
+    fn graph_triples_link_facts_and_reach_planners() {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("failed to open temporary sled db");
+        let model = PAGICoreModel::from_db(db).require_credentials(false);
+        let writer = AgentIdentity::new("Extractor", vec![AuthScope::WriteFacts]);
+        let reader = AgentIdentity::new("Planner", vec![AuthScope::ReadFacts]);
+        model
+            .record_fact(
+                &writer,
+                AgentFact {
+                    agent_id: "SearchAgent".to_string(),
+                    timestamp: 10,
+                    fact_type: "SearchResult".to_string(),
+                    content: "rapamycin inhibits mTOR, which drives aging".to_string(),
+                },
+            )
+            .expect("record");
+        let key = model.search_facts(&reader, "rapamycin", 1).expect("search")[0]
+            .key
+            .clone();
+
+        let rapamycin = Entity::new("compound", "rapamycin");
+        let mtor = Entity::new("protein", "mTOR");
+        let aging = Entity::new("process", "aging");
+        for (subject, relation, object) in [
+            (&rapamycin, "inhibits", &mtor),
+            (&mtor, "drives", &aging),
+        ] {
+            model
+                .upsert_triple(
+                    &writer,
+                    &Triple::new(subject.clone(), relation, object.clone()),
+                    Some(&key),
+                )
+                .expect("upsert");
+        }
+        let unknown = Triple::new(rapamycin.clone(), "treats", aging.clone());
+        assert!(model.upsert_triple(&writer, &unknown, Some("missing")).is_err());
+        assert!(model.upsert_triple(&reader, &unknown, None).is_err());
+        assert!(model.graph_neighborhood(&writer, &mtor.id(), 1).is_err());
+
+        let hood = model
+            .graph_neighborhood(&reader, &mtor.id(), 1)
+            .expect("neighborhood");
+        assert_eq!(hood.nodes.len(), 3);
+        let path = model
+            .graph_path(&reader, &rapamycin.id(), &aging.id(), 2, Direction::Outgoing)
+            .expect("path")
+            .expect("connected");
+        assert_eq!(path.len(), 2);
+        assert_eq!(
+            model.graph_node(&reader, &rapamycin.id()).expect("node").expect("exists").sources,
+            [key]
+        );
+
+        let context = model
+            .planning_context(&reader, "rapamycin and aging", 4)
+            .expect("context");
+        assert_eq!(
+            context.relations,
+            [
+                "compound:rapamycin -inhibits-> protein:mtor",
+                "protein:mtor -drives-> process:aging"
+            ]
+        );
+    }
+
+    #[test]
+    fn compaction_applies_retention_policies_and_cleans_indexes() {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("failed to open temporary sled db");
+        let model = PAGICoreModel::from_db(db).require_credentials(false);
+        let writer = AgentIdentity::new("Orchestrator", vec![AuthScope::WriteFacts]);
+        let reader = AgentIdentity::new("Auditor", vec![AuthScope::ReadFacts]);
+        let admin = AgentIdentity::new("Admin", vec![AuthScope::WritePolicy]);
+        for (timestamp, fact_type, content) in [
+            (10, "SearchResult", "rapamycin extends lifespan in mice"),
+            (20, "SearchResult", "metformin trial enrolment"),
+            (30, "SearchResult", "senolytics clear senescent cells"),
+            (11, "Heartbeat", "SearchAgent alive"),
+            (12, "Heartbeat", "SearchAgent alive again"),
+            (13, "CyberAlert", "phishing lure detected"),
+        ] {
+            model
+                .record_fact(
+                    &writer,
+                    AgentFact {
+                        agent_id: "SearchAgent".to_string(),
+                        timestamp,
+                        fact_type: fact_type.to_string(),
+                        content: content.to_string(),
+                    },
+                )
+                .expect("record");
+        }
+        let rapamycin_key = model.search_facts(&reader, "rapamycin", 1).expect("search")[0]
+            .key
+            .clone();
+        model
+            .upsert_triple(
+                &writer,
+                &Triple::new(
+                    Entity::new("compound", "rapamycin"),
+                    "extends_lifespan_of",
+                    Entity::new("organism", "mice"),
+                ),
+                Some(&rapamycin_key),
+            )
+            .expect("upsert");
+
+        let search_policy = RetentionPolicy::new("SearchResult")
+            .keep_latest_per_agent(1)
+            .with_action(ExpiryAction::Archive);
+        assert!(model
+            .set_retention_policy(&writer, search_policy.clone())
+            .is_err());
+        model
+            .set_retention_policy(&admin, search_policy)
+            .expect("set");
+        model
+            .set_retention_policy(
+                &admin,
+                RetentionPolicy::new("Heartbeat")
+                    .with_ttl(60)
+                    .with_action(ExpiryAction::Summarize),
+            )
+            .expect("set");
+        assert!(model.compact_facts(&reader).is_err());
+
+        let report = model.compact_facts(&admin).expect("compact");
+        assert_eq!(report.scanned, 5);
+        assert_eq!((report.archived, report.summarized), (2, 2));
+        assert_eq!(report.rollups_written, 1);
+        // At least the vectors of the four removed facts, beyond their records.
+        let vector_bytes = 4 * model.embedder().dimensions() * std::mem::size_of::<f32>();
+        assert!(report.reclaimed_bytes > vector_bytes as u64);
+
+        let remaining = model.retrieve_facts_by_timestamp(&reader, 0).expect("read");
+        let types: Vec<&str> = remaining.iter().map(|f| f.fact_type.as_str()).collect();
+        // The roll-up keeps the timestamp of the newest fact it replaces.
+        assert_eq!(types, ["RollUp", "CyberAlert", "SearchResult"]);
+        assert!(remaining[0].content.starts_with("Rolled up 2 Heartbeat fact(s)"));
+        assert!(model.search_facts(&reader, "rapamycin", 5).expect("search").is_empty());
+        assert!(model
+            .semantic_search(&reader, "rapamycin lifespan", 5, &FactFilter::default())
+            .expect("search")
+            .iter()
+            .all(|h| !h.fact.content.contains("rapamycin")));
+        assert_eq!(
+            model.graph_node(&reader, "compound:rapamycin").expect("node"),
+            None
+        );
+        let archive = model
+            .knowledge_base
+            .open_tree(retention::FACTS_ARCHIVE_TREE)
+            .expect("archive");
+        assert!(archive.contains_key(rapamycin_key.as_bytes()).expect("archive read"));
+
+        // Nothing left has expired.
+        assert_eq!(model.compact_facts(&admin).expect("compact").removed(), 0);
+        let stats = model.retention_stats(&reader).expect("stats");
+        assert_eq!((stats.runs, stats.facts_removed), (2, 4));
+        assert_eq!(stats.bytes_reclaimed, report.reclaimed_bytes);
+    }
+
+    #[tokio::test]
+    async fn planning_consults_the_most_relevant_facts() {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("failed to open temporary sled db");
+        let model = PAGICoreModel::from_db(db).require_credentials(false);
+        let prompt = "Please research the top anti-aging compounds and schedule a team meeting for next week to present the findings.";
+        let writer = AgentIdentity::new("ReflectiveAgent", vec![AuthScope::WriteFacts]);
+        let record = |timestamp: u64, fact_type: &str, content: String| {
+            model
+                .record_fact(
+                    &writer,
+                    AgentFact {
+                        agent_id: "ReflectiveAgent".to_string(),
+                        timestamp,
+                        fact_type: fact_type.to_string(),
+                        content,
+                    },
+                )
+                .expect("record");
+        };
+        // An old but relevant failure, buried under newer unrelated facts.
+        record(
+            1,
+            "AnalysisResult",
+            "Failure: anti-aging compounds research timed out".to_string(),
+        );
+        let now = unix_now();
+        for i in 0..40 {
+            record(now, "Heartbeat", format!("agent {i} is healthy"));
+        }
+
+        let reader = AgentIdentity::new("Planner", vec![AuthScope::ReadFacts]);
+        let context = model
+            .planning_context(&reader, prompt, 5)
+            .expect("context");
+        assert_eq!(context.facts.len(), 5);
+        assert_eq!(context.facts[0].fact.fact_type, "AnalysisResult");
+        assert!(context.facts[0].keyword > 0.0);
+        assert_eq!(context.directives, ["Rerun: Deep Search"]);
+        assert!(context.render().contains("Failure: anti-aging"));
+
+        let recent_only = model
+            .retrieve_relevant(
+                &reader,
+                &RetrievalQuery::new(prompt, 5).with_filters(FactFilter {
+                    since: Some(now),
+                    ..Default::default()
+                }),
+            )
+            .expect("retrieve");
+        assert!(recent_only.iter().all(|f| f.fact.fact_type == "Heartbeat"));
+
+        // The planner sees the same failure and reruns the search deeper.
+        let plan = model.general_reasoning(prompt, "").await.unwrap();
+        assert!(plan.iter().any(|t| t.input_data.contains("rerun_variant")));
+    }
+
+    #[test]
+    fn retrieval_finds_filtered_and_keyword_matches_beyond_the_pool() {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("failed to open temporary sled db");
+        let model = PAGICoreModel::from_db(db).require_credentials(false);
+        let writer = AgentIdentity::new("SearchAgent", vec![AuthScope::WriteFacts]);
+        let record = |timestamp: u64, fact_type: &str, content: String| {
+            model
+                .record_fact(
+                    &writer,
+                    AgentFact {
+                        agent_id: "SearchAgent".to_string(),
+                        timestamp,
+                        fact_type: fact_type.to_string(),
+                        content,
+                    },
+                )
+                .expect("record");
+        };
+        record(1, "CyberAlert", "Zebra sighting near the perimeter".to_string());
+        for i in 0..200 {
+            record(100 + i, "Heartbeat", format!("agent {i} is healthy"));
+        }
+
+        let reader = AgentIdentity::new("Planner", vec![AuthScope::ReadFacts]);
+        let retrieve = |query: RetrievalQuery| {
+            model
+                .retrieve_relevant(&reader, &query)
+                .expect("retrieve")
+                .into_iter()
+                .map(|f| f.fact.fact_type)
+                .collect::<Vec<_>>()
+        };
+        let cyber_only = RetrievalQuery::new("agent health", 3).with_filters(FactFilter {
+            fact_types: vec!["CyberAlert".to_string()],
+            ..Default::default()
+        });
+        assert_eq!(retrieve(cyber_only), ["CyberAlert"]);
+        let zebra = RetrievalQuery::new("agent health", 3).with_keywords(vec!["zebra".to_string()]);
+        assert_eq!(retrieve(zebra), ["CyberAlert"]);
+        assert_eq!(retrieve(RetrievalQuery::new("zebra", 1)), ["CyberAlert"]);
+    }
+
+    #[tokio::test]
+    async fn planning_keeps_directives_from_facts_outside_the_context() {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("failed to open temporary sled db");
+        let model = PAGICoreModel::from_db(db).require_credentials(false);
+        let prompt = "Please research the top anti-aging compounds and schedule a team meeting for next week to present the findings.";
+        let writer = AgentIdentity::new("ReflectiveAgent", vec![AuthScope::WriteFacts]);
+        let record = |timestamp: u64, fact_type: &str, content: String| {
+            model
+                .record_fact(
+                    &writer,
+                    AgentFact {
+                        agent_id: "ReflectiveAgent".to_string(),
+                        timestamp,
+                        fact_type: fact_type.to_string(),
+                        content,
+                    },
+                )
+                .expect("record");
+        };
+        // An unrelated failure, outranked by many relevant notes.
+        record(1, "AnalysisResult", "Failure: mailbox sync stalled".to_string());
+        let now = unix_now();
+        for i in 0..40 {
+            record(now, "SearchResult", format!("anti-aging compounds research note {i}"));
+        }
+
+        let reader = AgentIdentity::new("Planner", vec![AuthScope::ReadFacts]);
+        let context = model
+            .planning_context(&reader, prompt, retrieval::PLANNING_CONTEXT_K)
+            .expect("context");
+        assert!(context.facts.iter().all(|f| f.fact.fact_type == "SearchResult"));
+
+        let plan = model.general_reasoning(prompt, "").await.unwrap();
+        assert!(plan.iter().any(|t| t.input_data.contains("rerun_variant")));
+    }
+
+    #[tokio::test]
+    async fn reflection_directives_transform_llm_plans() {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("failed to open temporary sled db");
+        let model = PAGICoreModel::from_db(db).require_credentials(false);
+        let orchestrator = AgentIdentity::new(
+            "Orchestrator",
+            vec![AuthScope::WriteFacts, AuthScope::WritePolicy],
+        );
+        let proposed = model
+            .propose_reflection(
+                &orchestrator,
+                ReflectionFact {
+                    target_agent: "SearchAgent".to_string(),
+                    critique: "Single query missed half the topic".to_string(),
+                    new_directive: "Split the query into 2 sub-queries and verify the results"
+                        .to_string(),
+                },
+            )
+            .expect("propose");
+        model
+            .set_reflection_status(&orchestrator, &proposed.id, ReflectionStatus::Accepted)
+            .expect("accept");
+
+        let llm = serde_json::json!([{
+            "agent_type": "SearchAgent",
+            "input_data": { "query": "quantum error correction" },
+        }]);
+        let plan = model
+            .general_reasoning("Research quantum error correction", &llm.to_string())
+            .await
+            .unwrap();
+        let agents: Vec<&str> = plan.iter().map(|t| t.agent_type.as_str()).collect();
+        assert_eq!(agents, ["SearchAgent", "SearchAgent", "ReflectiveAgent"]);
+        assert!(plan[1].input_data.contains("quantum error correction: mechanisms"));
+    }
+
+    #[tokio::test]
+    async fn experiments_apply_a_proposal_per_arm_and_conclude_it() {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("failed to open temporary sled db");
+        let model = PAGICoreModel::from_db(db).require_credentials(false);
+        let prompt = "Please research the top anti-aging compounds and schedule a team meeting for next week to present the findings.";
+        let orchestrator = AgentIdentity::new(
+            "Orchestrator",
+            vec![
+                AuthScope::ReadFacts,
+                AuthScope::WriteFacts,
+                AuthScope::WritePolicy,
+            ],
+        );
+        let proposed = model
+            .propose_reflection(
+                &orchestrator,
+                ReflectionFact {
+                    target_agent: "SearchAgent".to_string(),
+                    critique: "Broad query timed out".to_string(),
+                    new_directive: "Split the query into concurrent sub-queries".to_string(),
+                },
+            )
+            .expect("propose");
+
+        // Everything goes to the treatment arm.
+        let config = ExperimentConfig {
+            treatment_fraction: 1.0,
+            min_samples: 2,
+            ..Default::default()
+        };
+        let experiment = model
+            .start_experiment(&orchestrator, &proposed.id, config)
+            .expect("start");
+        let plan = model.general_reasoning(prompt, "").await.unwrap();
+        assert_eq!(plan.len(), 4);
+        let tag = ExperimentTag::from_task(&plan[0]).expect("tagged");
+        assert_eq!(tag.arm, ExperimentArm::Treatment);
+
+        let control = ExperimentTag {
+            experiment_id: experiment.id.clone(),
+            arm: ExperimentArm::Control,
+        };
+        for (tag, success, latency_ms) in [
+            (&tag, true, 800),
+            (&tag, true, 900),
+            (&control, false, 30_000),
+            (&control, true, 2_000),
+        ] {
+            model
+                .record_experiment_outcome(&orchestrator, tag, success, latency_ms)
+                .expect("record");
+        }
+        let reader = AgentIdentity::new("Dashboard", vec![AuthScope::ReadFacts]);
+        let report = model
+            .experiment_report(&reader, &experiment.id)
+            .expect("report");
+        assert_eq!(report.treatment.plans, 1);
+        assert_eq!(report.success_rate_delta, 0.5);
+        assert_eq!(report.recommendation, Some(ExperimentDecision::Accept));
+        assert!(model
+            .conclude_experiment(&reader, &experiment.id, None)
+            .is_err());
+
+        model
+            .conclude_experiment(&orchestrator, &experiment.id, None)
+            .expect("conclude");
+        let stored = model
+            .reflections_for_agent(&reader, "SearchAgent")
+            .expect("read")
+            .remove(0);
+        assert_eq!(stored.status, ReflectionStatus::Accepted);
+        // With the experiment over, the accepted reflection steers every plan, untagged.
+        let plan = model.general_reasoning(prompt, "").await.unwrap();
+        assert_eq!(plan.len(), 4);
+        assert!(ExperimentTag::from_task(&plan[0]).is_none());
+
+        // A decision the reflection can no longer follow leaves the experiment running.
+        let second = model
+            .propose_reflection(
+                &orchestrator,
+                ReflectionFact {
+                    target_agent: "SearchAgent".to_string(),
+                    critique: "Results were stale".to_string(),
+                    new_directive: "Prefer sources from the last year".to_string(),
+                },
+            )
+            .expect("propose");
+        let experiment = model
+            .start_experiment(&orchestrator, &second.id, ExperimentConfig::default())
+            .expect("start");
+        model
+            .set_reflection_status(&orchestrator, &second.id, ReflectionStatus::Reverted)
+            .expect("revert");
+        assert!(model
+            .conclude_experiment(&orchestrator, &experiment.id, Some(ExperimentDecision::Accept))
+            .is_err());
+        assert!(model
+            .experiments
+            .get(&experiment.id)
+            .is_some_and(|e| e.is_running()));
+    }
+
+    #[test]
+    fn timeouts_generate_a_split_proposal_once() {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("failed to open temporary sled db");
+        let model = PAGICoreModel::from_db(db).require_credentials(false);
+        let orchestrator = AgentIdentity::new(
+            "Orchestrator",
+            vec![AuthScope::ReadFacts, AuthScope::WriteFacts],
+        );
+        for status in [
+            ExecutionStatus::Timeout,
+            ExecutionStatus::Timeout,
+            ExecutionStatus::Success,
+        ] {
+            let result = ExecutionResult {
+                agent_type: "SearchAgent".to_string(),
+                status,
+                latency_ms: 30_000,
+                result_count: None,
+                error: None,
+            };
+            model
+                .record_execution_result(&orchestrator, &result)
+                .expect("record");
+        }
+
+        let proposed = model
+            .generate_reflections(&orchestrator, 0, GeneratorConfig::default())
+            .expect("generate");
+        assert_eq!(proposed.len(), 1);
+        assert_eq!(proposed[0].target_agent, "SearchAgent");
+        assert_eq!(proposed[0].status, ReflectionStatus::Proposed);
+        assert!(proposed[0].new_directive.contains("Split"));
+
+        // Re-running doesn't duplicate the open proposal.
+        assert!(model
+            .generate_reflections(&orchestrator, 0, GeneratorConfig::default())
+            .unwrap()
+            .is_empty());
+    }
 }
@@ -0,0 +1,432 @@
+//! Policy-as-data authorization.
+//!
+//! [`PolicyStatement`]s live in the `policies` KB tree and are evaluated on every
+//! authorization check next to the identity's scopes:
+//!
+//! 1. any matching `Deny` statement denies, even if a scope or another statement allows;
+//! 2. otherwise a scope grant (see [`AuthorizationGatekeeper`]) allows;
+//! 3. otherwise a matching `Allow` statement allows;
+//! 4. otherwise the request is denied.
+//!
+//! Statements are written through [`PAGICoreModel::put_policy`](crate::PAGICoreModel::put_policy),
+//! which requires [`AuthScope::WritePolicy`]. [`PAGICoreModel::simulate`](crate::PAGICoreModel::simulate)
+//! evaluates a request without side effects, for testing policies before relying on them.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::auth::{
+    glob_match, AgentIdentity, AuthScope, AuthorizationGatekeeper, Resource, ResourcePattern,
+};
+
+/// KB tree holding policy statements, keyed by statement id.
+pub const POLICIES_TREE: &str = "policies";
+
+/// Key marking that the bootstrap statements were seeded, so deleting them sticks.
+const BOOTSTRAP_MARKER: &[u8] = b"\0bootstrapped";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Effect {
+    Allow,
+    Deny,
+}
+
+/// Extra conditions a statement only applies under.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PolicyCondition {
+    /// UTC hour range `[start_hour, end_hour)`; wraps past midnight when `start > end`.
+    TimeWindow { start_hour: u8, end_hour: u8 },
+    /// Applies from this unix timestamp (seconds) on.
+    NotBefore(u64),
+    /// Applies until this unix timestamp (seconds).
+    NotAfter(u64),
+    /// Applies while the principal has been allowed by this statement fewer than `max`
+    /// times in the last `per_secs` seconds. Meant for `Allow` statements.
+    MaxRate { max: u32, per_secs: u64 },
+    /// Applies only to identities holding this exact grant, or the unrestricted form of its
+    /// scope. A resource-scoped grant doesn't satisfy an unrestricted condition.
+    HasScope(AuthScope),
+}
+
+/// One allow/deny rule.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PolicyStatement {
+    pub id: String,
+    pub effect: Effect,
+    /// Glob over agent ids (`*` for everyone).
+    pub principal: String,
+    /// The scope being requested; only its base is compared.
+    pub action: AuthScope,
+    #[serde(default)]
+    pub resource: ResourcePattern,
+    #[serde(default)]
+    pub conditions: Vec<PolicyCondition>,
+}
+
+/// Why a request was allowed or denied.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DecisionReason {
+    /// Denied by the statement with this id.
+    ExplicitDeny(String),
+    /// Allowed by one of the identity's scopes.
+    ScopeGrant,
+    /// Allowed by the statement with this id.
+    PolicyAllow(String),
+    /// Nothing allowed it.
+    NoMatch,
+}
+
+/// Outcome of evaluating a request.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuthDecision {
+    pub allowed: bool,
+    pub reason: DecisionReason,
+}
+
+/// Allow timestamps per `(statement id, principal)` for [`PolicyCondition::MaxRate`].
+type RateTable = HashMap<(String, String), VecDeque<u64>>;
+
+/// Statements every fresh knowledge base starts with.
+///
+/// `bootstrap_robotics_write` keeps the historical behaviour of letting `RoboticsAction`
+/// stand in for `WriteFacts`, limited to `RoboticsAction` facts and to holders of an
+/// unrestricted `RoboticsAction` grant; delete it to revoke that.
+pub fn bootstrap_statements() -> Vec<PolicyStatement> {
+    vec![PolicyStatement {
+        id: "bootstrap_robotics_write".to_string(),
+        effect: Effect::Allow,
+        principal: "*".to_string(),
+        action: AuthScope::WriteFacts,
+        resource: ResourcePattern::fact_type("RoboticsAction"),
+        conditions: vec![PolicyCondition::HasScope(AuthScope::RoboticsAction)],
+    }]
+}
+
+/// Evaluates requests against the statements in the `policies` tree.
+///
+/// Clones share the rate-limit counters.
+#[derive(Debug, Clone)]
+pub struct PolicyEngine {
+    tree: sled::Tree,
+    rate_hits: Arc<Mutex<RateTable>>,
+}
+
+impl PolicyEngine {
+    /// Opens the `policies` tree, seeding [`bootstrap_statements`] the first time.
+    pub fn open(db: &sled::Db) -> Result<Self, sled::Error> {
+        let engine = Self {
+            tree: db.open_tree(POLICIES_TREE)?,
+            rate_hits: Arc::new(Mutex::new(HashMap::new())),
+        };
+        if !engine.tree.contains_key(BOOTSTRAP_MARKER)? {
+            for statement in bootstrap_statements() {
+                engine.put(&statement)?;
+            }
+            engine.tree.insert(BOOTSTRAP_MARKER, &[])?;
+            engine.tree.flush()?;
+        }
+        Ok(engine)
+    }
+
+    /// Inserts or replaces a statement. Callers are responsible for authorization.
+    pub fn put(&self, statement: &PolicyStatement) -> Result<(), sled::Error> {
+        let value = serde_json::to_vec(statement).expect("failed to serialize PolicyStatement");
+        self.tree.insert(statement.id.as_bytes(), value)?;
+        self.tree.flush()?;
+        Ok(())
+    }
+
+    /// Removes a statement. Returns whether it existed.
+    pub fn remove(&self, id: &str) -> Result<bool, sled::Error> {
+        let existed = self.tree.remove(id.as_bytes())?.is_some();
+        self.tree.flush()?;
+        Ok(existed)
+    }
+
+    /// All stored statements, ordered by id. Undecodable entries are skipped.
+    pub fn statements(&self) -> Vec<PolicyStatement> {
+        self.tree
+            .iter()
+            .filter_map(|res| res.ok())
+            .filter_map(|(_, v)| serde_json::from_slice(&v).ok())
+            .collect()
+    }
+
+    /// Evaluates `action` on `resource` for `identity` at `now` (unix seconds).
+    ///
+    /// With `record` set, an allow by a rate-limited statement counts against its limit;
+    /// simulations pass `false`.
+    pub fn evaluate(
+        &self,
+        gatekeeper: &AuthorizationGatekeeper,
+        identity: &AgentIdentity,
+        action: &AuthScope,
+        resource: &Resource,
+        now: u64,
+        record: bool,
+    ) -> AuthDecision {
+        let statements = self.statements();
+        self.evaluate_against(
+            &statements,
+            gatekeeper,
+            identity,
+            action,
+            resource,
+            now,
+            record,
+        )
+    }
+
+    /// Like [`PolicyEngine::evaluate`] over an already loaded statement list, for checking
+    /// many resources in one pass.
+    #[allow(clippy::too_many_arguments)]
+    pub fn evaluate_against(
+        &self,
+        statements: &[PolicyStatement],
+        gatekeeper: &AuthorizationGatekeeper,
+        identity: &AgentIdentity,
+        action: &AuthScope,
+        resource: &Resource,
+        now: u64,
+        record: bool,
+    ) -> AuthDecision {
+        let applicable: Vec<&PolicyStatement> = statements
+            .iter()
+            .filter(|s| self.applies(s, gatekeeper, identity, action, now))
+            .filter(|s| s.resource.matches(resource, &identity.id))
+            .collect();
+
+        if let Some(deny) = applicable.iter().find(|s| s.effect == Effect::Deny) {
+            return AuthDecision {
+                allowed: false,
+                reason: DecisionReason::ExplicitDeny(deny.id.clone()),
+            };
+        }
+
+        if gatekeeper.authorize(identity, action, resource).is_ok() {
+            return AuthDecision {
+                allowed: true,
+                reason: DecisionReason::ScopeGrant,
+            };
+        }
+
+        if let Some(allow) = applicable.iter().find(|s| s.effect == Effect::Allow) {
+            if record {
+                self.record_hit(allow, &identity.id, now);
+            }
+            return AuthDecision {
+                allowed: true,
+                reason: DecisionReason::PolicyAllow(allow.id.clone()),
+            };
+        }
+
+        AuthDecision {
+            allowed: false,
+            reason: DecisionReason::NoMatch,
+        }
+    }
+
+    /// Whether any `Allow` statement could grant `action` to `identity` on some resource.
+    pub fn may_allow_any(
+        &self,
+        gatekeeper: &AuthorizationGatekeeper,
+        identity: &AgentIdentity,
+        action: &AuthScope,
+        now: u64,
+    ) -> bool {
+        self.statements().iter().any(|s| {
+            s.effect == Effect::Allow && self.applies(s, gatekeeper, identity, action, now)
+        })
+    }
+
+    /// Principal, action and condition match (resource is checked separately).
+    fn applies(
+        &self,
+        statement: &PolicyStatement,
+        gatekeeper: &AuthorizationGatekeeper,
+        identity: &AgentIdentity,
+        action: &AuthScope,
+        now: u64,
+    ) -> bool {
+        statement.action.base() == action.base()
+            && glob_match(&statement.principal, &identity.id)
+            && statement
+                .conditions
+                .iter()
+                .all(|c| self.condition_holds(statement, c, gatekeeper, identity, now))
+    }
+
+    fn condition_holds(
+        &self,
+        statement: &PolicyStatement,
+        condition: &PolicyCondition,
+        gatekeeper: &AuthorizationGatekeeper,
+        identity: &AgentIdentity,
+        now: u64,
+    ) -> bool {
+        match condition {
+            PolicyCondition::TimeWindow {
+                start_hour,
+                end_hour,
+            } => {
+                let hour = ((now / 3600) % 24) as u8;
+                if start_hour <= end_hour {
+                    (*start_hour..*end_hour).contains(&hour)
+                } else {
+                    hour >= *start_hour || hour < *end_hour
+                }
+            }
+            PolicyCondition::NotBefore(ts) => now >= *ts,
+            PolicyCondition::NotAfter(ts) => now <= *ts,
+            PolicyCondition::MaxRate { max, per_secs } => {
+                let mut hits = self.rate_hits.lock().expect("policy rate table poisoned");
+                let key = (statement.id.clone(), identity.id.clone());
+                let window = hits.entry(key).or_default();
+                while window
+                    .front()
+                    .is_some_and(|t| now.saturating_sub(*t) >= *per_secs)
+                {
+                    window.pop_front();
+                }
+                window.len() < *max as usize
+            }
+            PolicyCondition::HasScope(scope) => {
+                gatekeeper.effective_scopes(identity).iter().any(|g| {
+                    g == scope || (g.resource_pattern().is_none() && g.base() == scope.base())
+                })
+            }
+        }
+    }
+
+    fn record_hit(&self, statement: &PolicyStatement, principal: &str, now: u64) {
+        if !statement
+            .conditions
+            .iter()
+            .any(|c| matches!(c, PolicyCondition::MaxRate { .. }))
+        {
+            return;
+        }
+        self.rate_hits
+            .lock()
+            .expect("policy rate table poisoned")
+            .entry((statement.id.clone(), principal.to_string()))
+            .or_default()
+            .push_back(now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn explicit_deny_wins_and_conditions_gate_allows() {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("failed to open temporary sled db");
+        let engine = PolicyEngine::open(&db).expect("open policies");
+        let gk = AuthorizationGatekeeper::new();
+        let fact = Resource::fact("facts", "AnalysisResult", "SearchAgent");
+
+        let writer = AgentIdentity::new("SearchAgent", vec![AuthScope::WriteFacts]);
+        engine
+            .put(&PolicyStatement {
+                id: "deny_search_analysis".to_string(),
+                effect: Effect::Deny,
+                principal: "Search*".to_string(),
+                action: AuthScope::WriteFacts,
+                resource: ResourcePattern::fact_type("AnalysisResult"),
+                conditions: vec![PolicyCondition::TimeWindow {
+                    start_hour: 22,
+                    end_hour: 6,
+                }],
+            })
+            .expect("put");
+        let noon = 12 * 3600;
+        let midnight = 24 * 3600;
+        assert_eq!(
+            engine
+                .evaluate(&gk, &writer, &AuthScope::WriteFacts, &fact, noon, true)
+                .reason,
+            DecisionReason::ScopeGrant
+        );
+        assert_eq!(
+            engine
+                .evaluate(&gk, &writer, &AuthScope::WriteFacts, &fact, midnight, true)
+                .reason,
+            DecisionReason::ExplicitDeny("deny_search_analysis".to_string())
+        );
+
+        // Bootstrap: RoboticsAction stands in for WriteFacts.
+        let robot = AgentIdentity::new("ArmAgent", vec![AuthScope::RoboticsAction]);
+        let robot_fact = Resource::fact("facts", "RoboticsAction", "ArmAgent");
+        assert!(
+            engine
+                .evaluate(&gk, &robot, &AuthScope::WriteFacts, &robot_fact, noon, true)
+                .allowed
+        );
+        // ...but only for RoboticsAction facts, and not for a grant scoped to some resources.
+        let other_fact = Resource::fact("facts", "AnalysisResult", "ArmAgent");
+        assert!(
+            !engine
+                .evaluate(&gk, &robot, &AuthScope::WriteFacts, &other_fact, noon, true)
+                .allowed
+        );
+        let arm_only = AgentIdentity::new(
+            "ArmAgent",
+            vec![AuthScope::scoped(
+                AuthScope::RoboticsAction,
+                ResourcePattern::tree("arm/*"),
+            )],
+        );
+        assert!(
+            !engine
+                .evaluate(
+                    &gk,
+                    &arm_only,
+                    &AuthScope::WriteFacts,
+                    &robot_fact,
+                    noon,
+                    true
+                )
+                .allowed
+        );
+
+        // Rate-limited allow: two writes per minute.
+        engine
+            .put(&PolicyStatement {
+                id: "calendar_rate".to_string(),
+                effect: Effect::Allow,
+                principal: "CalendarAgent".to_string(),
+                action: AuthScope::WriteFacts,
+                resource: ResourcePattern::default(),
+                conditions: vec![PolicyCondition::MaxRate {
+                    max: 2,
+                    per_secs: 60,
+                }],
+            })
+            .expect("put");
+        let calendar = AgentIdentity::new("CalendarAgent", vec![]);
+        let cal_fact = Resource::fact("facts", "CalendarEvent", "CalendarAgent");
+        let eval = |now, record| {
+            engine
+                .evaluate(
+                    &gk,
+                    &calendar,
+                    &AuthScope::WriteFacts,
+                    &cal_fact,
+                    now,
+                    record,
+                )
+                .allowed
+        };
+        assert!(eval(noon, false));
+        assert!(eval(noon, true));
+        assert!(eval(noon + 1, true));
+        assert!(!eval(noon + 2, true));
+        assert!(eval(noon + 61, true));
+    }
+}
@@ -0,0 +1,392 @@
+//! Token-bucket quotas per identity and scope.
+//!
+//! A [`QuotaRule`] caps how much of a scope matching identities may use: requests (e.g.
+//! writes per second, `ExternalAPI` calls per minute) or bytes (e.g. fact bytes written per
+//! day). Each `(rule, identity)` pair gets its own bucket that refills continuously.
+//!
+//! Quotas are checked after authorization succeeds; a request that would overdraw any
+//! matching bucket fails with a [`QuotaExceeded`] error and debits none of them. Errors are
+//! plain strings like the rest of the authorization path, so callers tell quota failures
+//! apart with [`is_quota_exceeded`].
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::auth::{glob_match, AuthScope};
+
+/// Prefix of every quota error message.
+pub const QUOTA_EXCEEDED_PREFIX: &str = "Quota exceeded";
+
+/// Tree name quota configuration is authorized against. Rules are kept in memory; nothing
+/// is stored under it.
+pub const QUOTAS_RESOURCE: &str = "quotas";
+
+/// What a rule counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QuotaUnit {
+    Requests,
+    Bytes,
+}
+
+/// Bucket size and refill rate: up to `capacity` units, refilled by `capacity` every
+/// `per_secs`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaLimit {
+    pub capacity: u64,
+    pub per_secs: u64,
+}
+
+impl QuotaLimit {
+    pub fn per_second(capacity: u64) -> Self {
+        Self {
+            capacity,
+            per_secs: 1,
+        }
+    }
+
+    pub fn per_minute(capacity: u64) -> Self {
+        Self {
+            capacity,
+            per_secs: 60,
+        }
+    }
+
+    pub fn per_day(capacity: u64) -> Self {
+        Self {
+            capacity,
+            per_secs: 86_400,
+        }
+    }
+
+    /// Units added back per second.
+    fn refill_rate(&self) -> f64 {
+        self.capacity as f64 / self.per_secs.max(1) as f64
+    }
+}
+
+/// A limit on one scope for identities matching `principal`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaRule {
+    pub id: String,
+    /// Glob over identity ids (`*` for everyone).
+    pub principal: String,
+    /// Compared by base scope, so resource-scoped grants share the bucket.
+    pub scope: AuthScope,
+    pub unit: QuotaUnit,
+    pub limit: QuotaLimit,
+}
+
+impl QuotaRule {
+    pub fn new(
+        id: impl Into<String>,
+        principal: impl Into<String>,
+        scope: AuthScope,
+        unit: QuotaUnit,
+        limit: QuotaLimit,
+    ) -> Self {
+        Self {
+            id: id.into(),
+            principal: principal.into(),
+            scope,
+            unit,
+            limit,
+        }
+    }
+
+    fn applies(&self, identity_id: &str, scope: &AuthScope) -> bool {
+        self.scope.base() == scope.base() && glob_match(&self.principal, identity_id)
+    }
+}
+
+/// What a single authorized request costs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct QuotaCost {
+    pub requests: u64,
+    pub bytes: u64,
+}
+
+impl QuotaCost {
+    /// One request, no payload.
+    pub fn request() -> Self {
+        Self {
+            requests: 1,
+            bytes: 0,
+        }
+    }
+
+    /// One request carrying `bytes` of payload.
+    pub fn bytes(bytes: u64) -> Self {
+        Self { requests: 1, bytes }
+    }
+
+    fn of(&self, unit: QuotaUnit) -> u64 {
+        match unit {
+            QuotaUnit::Requests => self.requests,
+            QuotaUnit::Bytes => self.bytes,
+        }
+    }
+}
+
+/// A request rejected by a quota rule.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuotaExceeded {
+    pub rule_id: String,
+    pub identity_id: String,
+    pub scope: AuthScope,
+    /// How long until the bucket holds enough for the request, if it ever can.
+    pub retry_after: Option<Duration>,
+}
+
+impl std::fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{QUOTA_EXCEEDED_PREFIX}: agent '{}' exhausted quota '{}' for {:?}",
+            self.identity_id,
+            self.rule_id,
+            self.scope.base()
+        )?;
+        match self.retry_after {
+            Some(d) => write!(f, " (retry after {}ms)", d.as_millis()),
+            None => write!(f, " (request exceeds the quota's capacity)"),
+        }
+    }
+}
+
+/// Whether an authorization error came from a quota rather than a missing grant.
+pub fn is_quota_exceeded(err: &str) -> bool {
+    err.starts_with(QUOTA_EXCEEDED_PREFIX)
+}
+
+/// Usage of one `(rule, identity)` bucket, for monitoring.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuotaUsage {
+    pub rule_id: String,
+    pub identity_id: String,
+    pub scope: AuthScope,
+    pub unit: QuotaUnit,
+    pub capacity: u64,
+    /// Units available right now.
+    pub available: u64,
+    /// Units consumed since the bucket was created.
+    pub consumed: u64,
+    /// Requests rejected by this bucket.
+    pub rejected: u64,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+    consumed: u64,
+    rejected: u64,
+}
+
+impl Bucket {
+    fn refill(&mut self, limit: &QuotaLimit, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.refill_rate()).min(limit.capacity as f64);
+        self.last = now;
+    }
+}
+
+/// Buckets keyed by `(rule id, identity id)`.
+type BucketTable = HashMap<(String, String), Bucket>;
+
+/// Enforces [`QuotaRule`]s. Clones share rules and buckets.
+#[derive(Debug, Clone, Default)]
+pub struct QuotaManager {
+    rules: Arc<RwLock<Vec<QuotaRule>>>,
+    buckets: Arc<Mutex<BucketTable>>,
+}
+
+impl QuotaManager {
+    pub fn new(rules: Vec<QuotaRule>) -> Self {
+        let manager = Self::default();
+        manager.configure(rules);
+        manager
+    }
+
+    /// Replaces the rule set. Buckets of removed or changed rules start over.
+    pub fn configure(&self, rules: Vec<QuotaRule>) {
+        let mut current = self.rules.write().expect("quota rules lock poisoned");
+        self.buckets
+            .lock()
+            .expect("quota buckets lock poisoned")
+            .retain(|(rule_id, _), _| {
+                let old = current.iter().find(|r| &r.id == rule_id);
+                old.is_some() && rules.iter().any(|r| Some(r) == old)
+            });
+        *current = rules;
+    }
+
+    pub fn rules(&self) -> Vec<QuotaRule> {
+        self.rules
+            .read()
+            .expect("quota rules lock poisoned")
+            .clone()
+    }
+
+    /// Debits `cost` from every bucket matching `identity_id` and `scope`, or none of them
+    /// if any would be overdrawn.
+    pub fn charge(
+        &self,
+        identity_id: &str,
+        scope: &AuthScope,
+        cost: QuotaCost,
+    ) -> Result<(), Box<QuotaExceeded>> {
+        self.charge_at(identity_id, scope, cost, Instant::now())
+    }
+
+    pub fn charge_at(
+        &self,
+        identity_id: &str,
+        scope: &AuthScope,
+        cost: QuotaCost,
+        now: Instant,
+    ) -> Result<(), Box<QuotaExceeded>> {
+        let rules = self.rules.read().expect("quota rules lock poisoned");
+        let matching: Vec<&QuotaRule> = rules
+            .iter()
+            .filter(|r| r.applies(identity_id, scope) && cost.of(r.unit) > 0)
+            .collect();
+        if matching.is_empty() {
+            return Ok(());
+        }
+
+        let mut buckets = self.buckets.lock().expect("quota buckets lock poisoned");
+        for rule in &matching {
+            let bucket = buckets
+                .entry((rule.id.clone(), identity_id.to_string()))
+                .or_insert_with(|| Bucket {
+                    tokens: rule.limit.capacity as f64,
+                    last: now,
+                    consumed: 0,
+                    rejected: 0,
+                });
+            bucket.refill(&rule.limit, now);
+
+            let needed = cost.of(rule.unit) as f64;
+            if bucket.tokens < needed {
+                bucket.rejected += 1;
+                let retry_after = (needed <= rule.limit.capacity as f64).then(|| {
+                    Duration::from_secs_f64((needed - bucket.tokens) / rule.limit.refill_rate())
+                });
+                return Err(Box::new(QuotaExceeded {
+                    rule_id: rule.id.clone(),
+                    identity_id: identity_id.to_string(),
+                    scope: scope.base().clone(),
+                    retry_after,
+                }));
+            }
+        }
+        for rule in &matching {
+            let bucket = buckets
+                .get_mut(&(rule.id.clone(), identity_id.to_string()))
+                .expect("bucket created above");
+            bucket.tokens -= cost.of(rule.unit) as f64;
+            bucket.consumed += cost.of(rule.unit);
+        }
+        Ok(())
+    }
+
+    /// Current usage of every bucket, ordered by rule and identity.
+    pub fn usage(&self) -> Vec<QuotaUsage> {
+        let now = Instant::now();
+        let rules = self.rules.read().expect("quota rules lock poisoned");
+        let mut buckets = self.buckets.lock().expect("quota buckets lock poisoned");
+        let mut usage: Vec<QuotaUsage> = buckets
+            .iter_mut()
+            .filter_map(|((rule_id, identity_id), bucket)| {
+                let rule = rules.iter().find(|r| &r.id == rule_id)?;
+                bucket.refill(&rule.limit, now);
+                Some(QuotaUsage {
+                    rule_id: rule_id.clone(),
+                    identity_id: identity_id.clone(),
+                    scope: rule.scope.base().clone(),
+                    unit: rule.unit,
+                    capacity: rule.limit.capacity,
+                    available: bucket.tokens as u64,
+                    consumed: bucket.consumed,
+                    rejected: bucket.rejected,
+                })
+            })
+            .collect();
+        usage.sort_by(|a, b| (&a.rule_id, &a.identity_id).cmp(&(&b.rule_id, &b.identity_id)));
+        usage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets_refill_and_reject_without_partial_debits() {
+        let quotas = QuotaManager::new(vec![
+            QuotaRule::new(
+                "writes",
+                "*",
+                AuthScope::WriteFacts,
+                QuotaUnit::Requests,
+                QuotaLimit::per_second(2),
+            ),
+            QuotaRule::new(
+                "search_bytes",
+                "SearchAgent",
+                AuthScope::WriteFacts,
+                QuotaUnit::Bytes,
+                QuotaLimit::per_day(100),
+            ),
+        ]);
+        let t0 = Instant::now();
+        let write = AuthScope::WriteFacts;
+
+        quotas
+            .charge_at("SearchAgent", &write, QuotaCost::bytes(40), t0)
+            .unwrap();
+        quotas
+            .charge_at("SearchAgent", &write, QuotaCost::bytes(40), t0)
+            .unwrap();
+        let err = quotas
+            .charge_at("SearchAgent", &write, QuotaCost::bytes(10), t0)
+            .unwrap_err();
+        assert_eq!(err.rule_id, "writes");
+        assert!(is_quota_exceeded(&err.to_string()));
+
+        // Half a second refills one write, but the byte bucket is short and nothing is debited.
+        let t1 = t0 + Duration::from_millis(500);
+        let err = quotas
+            .charge_at("SearchAgent", &write, QuotaCost::bytes(30), t1)
+            .unwrap_err();
+        assert_eq!(err.rule_id, "search_bytes");
+        quotas
+            .charge_at("SearchAgent", &write, QuotaCost::bytes(20), t1)
+            .unwrap();
+
+        // Other identities have their own buckets and no byte rule.
+        quotas
+            .charge_at("CalendarAgent", &write, QuotaCost::bytes(1_000), t0)
+            .unwrap();
+        quotas
+            .charge_at(
+                "CalendarAgent",
+                &AuthScope::ReadFacts,
+                QuotaCost::request(),
+                t0,
+            )
+            .unwrap();
+
+        let usage = quotas.usage();
+        let search_bytes = usage
+            .iter()
+            .find(|u| u.rule_id == "search_bytes")
+            .expect("usage");
+        assert_eq!(search_bytes.consumed, 100);
+        assert_eq!(search_bytes.rejected, 1);
+        assert_eq!(usage.iter().filter(|u| u.rule_id == "writes").count(), 2);
+    }
+}
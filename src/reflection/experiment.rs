@@ -0,0 +1,530 @@
+//! A/B evaluation of reflection directives.
+//!
+//! An [`Experiment`] applies a `Proposed` reflection to a random fraction of plans (the
+//! treatment arm) and leaves the rest unchanged (the control arm). Plans are tagged with the
+//! experiment id and arm (see [`ExperimentTag`]). Executors then record one
+//! [`ExperimentSample`] per plan in the `experiment_outcomes` tree.
+//!
+//! An [`ExperimentReport`] compares the arms' success rates and mean latencies. Once both arms
+//! have enough samples it recommends accepting or reverting the reflection.
+
+use serde::{Deserialize, Serialize};
+
+use crate::Task;
+
+/// KB tree holding experiments, keyed by experiment id.
+pub const EXPERIMENTS_TREE: &str = "experiments";
+
+/// KB tree holding samples, keyed by `<experiment id>/<sample id>`.
+pub const EXPERIMENT_OUTCOMES_TREE: &str = "experiment_outcomes";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExperimentArm {
+    Control,
+    Treatment,
+}
+
+impl ExperimentArm {
+    /// Picks the treatment arm with probability `treatment_fraction`.
+    pub fn pick(treatment_fraction: f64) -> Self {
+        let mut bytes = [0u8; 8];
+        getrandom::getrandom(&mut bytes).expect("failed to read OS randomness");
+        let draw = u64::from_le_bytes(bytes) as f64 / u64::MAX as f64;
+        if draw < treatment_fraction {
+            ExperimentArm::Treatment
+        } else {
+            ExperimentArm::Control
+        }
+    }
+}
+
+/// How an experiment splits traffic and when its result counts.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExperimentConfig {
+    /// Share of plans (0.0-1.0) that get the directive.
+    pub treatment_fraction: f64,
+    /// Samples each arm needs before the report recommends anything.
+    pub min_samples: usize,
+    /// How far the treatment success rate may fall below control and still be accepted.
+    pub success_tolerance: f64,
+    /// How far the treatment mean latency may rise above control, as a fraction of the
+    /// control latency, and still be accepted.
+    pub latency_tolerance: f64,
+}
+
+impl Default for ExperimentConfig {
+    fn default() -> Self {
+        Self {
+            treatment_fraction: 0.5,
+            min_samples: 10,
+            success_tolerance: 0.05,
+            latency_tolerance: 0.1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExperimentDecision {
+    Accept,
+    Revert,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExperimentStatus {
+    Running,
+    Concluded {
+        decision: ExperimentDecision,
+        by: String,
+        at: u64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Experiment {
+    pub id: String,
+    /// The `Proposed` reflection under test.
+    pub reflection_id: String,
+    pub target_agent: String,
+    pub config: ExperimentConfig,
+    pub started_by: String,
+    pub created_at: u64,
+    pub status: ExperimentStatus,
+    /// Plans assigned to each arm so far.
+    pub control_plans: u64,
+    pub treatment_plans: u64,
+}
+
+impl Experiment {
+    pub fn is_running(&self) -> bool {
+        self.status == ExperimentStatus::Running
+    }
+}
+
+/// The marker added to the `input_data` of every task in an experiment plan, so executors
+/// know where to record the outcome.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExperimentTag {
+    pub experiment_id: String,
+    pub arm: ExperimentArm,
+}
+
+impl ExperimentTag {
+    /// Adds the tag to each task whose `input_data` is a JSON object.
+    pub fn apply(&self, plan: Vec<Task>) -> Vec<Task> {
+        plan.into_iter()
+            .map(|mut task| {
+                if let Ok(serde_json::Value::Object(mut input)) =
+                    serde_json::from_str(&task.input_data)
+                {
+                    input.insert(
+                        "experiment".to_string(),
+                        serde_json::to_value(self).expect("failed to serialize ExperimentTag"),
+                    );
+                    task.input_data = serde_json::Value::Object(input).to_string();
+                }
+                task
+            })
+            .collect()
+    }
+
+    /// Reads the tag back from a task, if it has one.
+    pub fn from_task(task: &Task) -> Option<Self> {
+        let input: serde_json::Value = serde_json::from_str(&task.input_data).ok()?;
+        serde_json::from_value(input.get("experiment")?.clone()).ok()
+    }
+}
+
+/// The outcome of one plan in one arm.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExperimentSample {
+    pub arm: ExperimentArm,
+    pub success: bool,
+    pub latency_ms: u64,
+    pub recorded_at: u64,
+}
+
+/// Aggregates for one arm.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ArmStats {
+    pub plans: u64,
+    pub samples: usize,
+    pub successes: usize,
+    pub success_rate: f64,
+    pub mean_latency_ms: f64,
+}
+
+impl ArmStats {
+    fn from_samples<'a>(plans: u64, samples: impl Iterator<Item = &'a ExperimentSample>) -> Self {
+        let mut stats = ArmStats {
+            plans,
+            ..Default::default()
+        };
+        let mut total_latency = 0u64;
+        for sample in samples {
+            stats.samples += 1;
+            stats.successes += usize::from(sample.success);
+            total_latency += sample.latency_ms;
+        }
+        if stats.samples > 0 {
+            stats.success_rate = stats.successes as f64 / stats.samples as f64;
+            stats.mean_latency_ms = total_latency as f64 / stats.samples as f64;
+        }
+        stats
+    }
+}
+
+/// Treatment versus control, with a recommendation once both arms have enough samples.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExperimentReport {
+    pub experiment_id: String,
+    pub reflection_id: String,
+    pub control: ArmStats,
+    pub treatment: ArmStats,
+    /// Treatment minus control; positive is better.
+    pub success_rate_delta: f64,
+    /// Treatment minus control; negative is better.
+    pub latency_delta_ms: f64,
+    /// `None` until both arms reach `min_samples`.
+    pub recommendation: Option<ExperimentDecision>,
+}
+
+impl ExperimentReport {
+    /// Accepts when the treatment is better on at least one metric and not worse than the
+    /// tolerances on either; reverts otherwise.
+    pub fn compute(experiment: &Experiment, samples: &[ExperimentSample]) -> Self {
+        let arm = |arm: ExperimentArm| samples.iter().filter(move |s| s.arm == arm);
+        let control = ArmStats::from_samples(experiment.control_plans, arm(ExperimentArm::Control));
+        let treatment =
+            ArmStats::from_samples(experiment.treatment_plans, arm(ExperimentArm::Treatment));
+        let success_rate_delta = treatment.success_rate - control.success_rate;
+        let latency_delta_ms = treatment.mean_latency_ms - control.mean_latency_ms;
+
+        let config = &experiment.config;
+        let enough = control.samples >= config.min_samples.max(1)
+            && treatment.samples >= config.min_samples.max(1);
+        let recommendation = enough.then(|| {
+            let improved = success_rate_delta > 0.0 || latency_delta_ms < 0.0;
+            let regressed = success_rate_delta < -config.success_tolerance
+                || latency_delta_ms > control.mean_latency_ms * config.latency_tolerance;
+            if improved && !regressed {
+                ExperimentDecision::Accept
+            } else {
+                ExperimentDecision::Revert
+            }
+        });
+
+        Self {
+            experiment_id: experiment.id.clone(),
+            reflection_id: experiment.reflection_id.clone(),
+            control,
+            treatment,
+            success_rate_delta,
+            latency_delta_ms,
+            recommendation,
+        }
+    }
+}
+
+/// KB-backed experiments and their samples.
+#[derive(Debug, Clone)]
+pub struct ExperimentStore {
+    db: sled::Db,
+    experiments: sled::Tree,
+    outcomes: sled::Tree,
+}
+
+impl ExperimentStore {
+    pub fn open(db: &sled::Db) -> Result<Self, sled::Error> {
+        Ok(Self {
+            db: db.clone(),
+            experiments: db.open_tree(EXPERIMENTS_TREE)?,
+            outcomes: db.open_tree(EXPERIMENT_OUTCOMES_TREE)?,
+        })
+    }
+
+    /// Starts an experiment on `reflection_id`. Fails if `target_agent` already has one
+    /// running.
+    pub fn start(
+        &self,
+        reflection_id: &str,
+        target_agent: &str,
+        config: ExperimentConfig,
+        by: &str,
+        now: u64,
+    ) -> Result<Experiment, String> {
+        if !(0.0..=1.0).contains(&config.treatment_fraction) {
+            return Err(format!(
+                "Treatment fraction {} is outside 0.0-1.0",
+                config.treatment_fraction
+            ));
+        }
+        if let Some(running) = self.running_for(target_agent) {
+            return Err(format!(
+                "Experiment '{}' is already running for '{target_agent}'",
+                running.id
+            ));
+        }
+        let id = self.db.generate_id().map_err(write_err)?;
+        let experiment = Experiment {
+            id: format!("exp-{id:020}"),
+            reflection_id: reflection_id.to_string(),
+            target_agent: target_agent.to_string(),
+            config,
+            started_by: by.to_string(),
+            created_at: now,
+            status: ExperimentStatus::Running,
+            control_plans: 0,
+            treatment_plans: 0,
+        };
+        self.put(&experiment)?;
+        Ok(experiment)
+    }
+
+    pub fn get(&self, id: &str) -> Option<Experiment> {
+        let v = self.experiments.get(id.as_bytes()).ok()??;
+        serde_json::from_slice(&v).ok()
+    }
+
+    /// All experiments, oldest first.
+    pub fn all(&self) -> Vec<Experiment> {
+        self.experiments
+            .iter()
+            .filter_map(|res| res.ok())
+            .filter_map(|(_, v)| serde_json::from_slice(&v).ok())
+            .collect()
+    }
+
+    pub fn running_for(&self, target_agent: &str) -> Option<Experiment> {
+        self.all()
+            .into_iter()
+            .find(|e| e.is_running() && e.target_agent == target_agent)
+    }
+
+    /// Picks an arm for a new plan of the running experiment on `target_agent` and counts
+    /// it.
+    pub fn assign(
+        &self,
+        target_agent: &str,
+    ) -> Result<Option<(Experiment, ExperimentArm)>, String> {
+        let Some(running) = self.running_for(target_agent) else {
+            return Ok(None);
+        };
+        let arm = ExperimentArm::pick(running.config.treatment_fraction);
+        let experiment = self.update(&running.id, |experiment| {
+            // Concluded since `running_for`; nothing to count.
+            if !experiment.is_running() {
+                return Ok(());
+            }
+            match arm {
+                ExperimentArm::Control => experiment.control_plans += 1,
+                ExperimentArm::Treatment => experiment.treatment_plans += 1,
+            }
+            Ok(())
+        })?;
+        Ok(experiment.is_running().then_some((experiment, arm)))
+    }
+
+    pub fn record(&self, id: &str, sample: ExperimentSample) -> Result<(), String> {
+        let experiment = self
+            .get(id)
+            .ok_or_else(|| format!("Unknown experiment '{id}'"))?;
+        if !experiment.is_running() {
+            return Err(format!("Experiment '{id}' has concluded"));
+        }
+        let seq = self.db.generate_id().map_err(write_err)?;
+        let value = serde_json::to_vec(&sample).expect("failed to serialize ExperimentSample");
+        self.outcomes
+            .insert(format!("{id}/{seq:020}"), value)
+            .map_err(write_err)?;
+        self.outcomes.flush().map_err(write_err)?;
+        Ok(())
+    }
+
+    pub fn samples(&self, id: &str) -> Vec<ExperimentSample> {
+        self.outcomes
+            .scan_prefix(format!("{id}/"))
+            .filter_map(|res| res.ok())
+            .filter_map(|(_, v)| serde_json::from_slice(&v).ok())
+            .collect()
+    }
+
+    pub fn report(&self, id: &str) -> Result<ExperimentReport, String> {
+        let experiment = self
+            .get(id)
+            .ok_or_else(|| format!("Unknown experiment '{id}'"))?;
+        Ok(ExperimentReport::compute(&experiment, &self.samples(id)))
+    }
+
+    /// Stops a running experiment with `decision`.
+    pub fn conclude(
+        &self,
+        id: &str,
+        decision: ExperimentDecision,
+        by: &str,
+        now: u64,
+    ) -> Result<Experiment, String> {
+        self.update(id, |experiment| {
+            if !experiment.is_running() {
+                return Err(format!("Experiment '{id}' has already concluded"));
+            }
+            experiment.status = ExperimentStatus::Concluded {
+                decision,
+                by: by.to_string(),
+                at: now,
+            };
+            Ok(())
+        })
+    }
+
+    /// Undoes [`ExperimentStore::conclude`], for when the reflection couldn't follow the
+    /// decision.
+    pub(crate) fn reopen(&self, id: &str) -> Result<Experiment, String> {
+        self.update(id, |experiment| {
+            experiment.status = ExperimentStatus::Running;
+            Ok(())
+        })
+    }
+
+    /// Applies `change` to the stored experiment as one atomic read-modify-write. `change`
+    /// may run more than once if the entry is modified concurrently.
+    fn update(
+        &self,
+        id: &str,
+        change: impl Fn(&mut Experiment) -> Result<(), String>,
+    ) -> Result<Experiment, String> {
+        let unknown = || format!("Unknown experiment '{id}'");
+        let mut out = Err(unknown());
+        self.experiments
+            .fetch_and_update(id.as_bytes(), |old| {
+                out = Err(unknown());
+                let old = old?;
+                let Ok(mut experiment) = serde_json::from_slice::<Experiment>(old) else {
+                    return Some(old.to_vec());
+                };
+                match change(&mut experiment) {
+                    Ok(()) => {
+                        let value = serde_json::to_vec(&experiment)
+                            .expect("failed to serialize Experiment");
+                        out = Ok(experiment);
+                        Some(value)
+                    }
+                    Err(e) => {
+                        out = Err(e);
+                        Some(old.to_vec())
+                    }
+                }
+            })
+            .map_err(write_err)?;
+        self.experiments.flush().map_err(write_err)?;
+        out
+    }
+
+    fn put(&self, experiment: &Experiment) -> Result<(), String> {
+        let value = serde_json::to_vec(experiment).expect("failed to serialize Experiment");
+        self.experiments
+            .insert(experiment.id.as_bytes(), value)
+            .map_err(write_err)?;
+        self.experiments.flush().map_err(write_err)?;
+        Ok(())
+    }
+}
+
+fn write_err(e: sled::Error) -> String {
+    format!("Experiment write failed: {e}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(arm: ExperimentArm, success: bool, latency_ms: u64) -> ExperimentSample {
+        ExperimentSample {
+            arm,
+            success,
+            latency_ms,
+            recorded_at: 1,
+        }
+    }
+
+    #[test]
+    fn reports_compare_arms_once_both_have_enough_samples() {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("failed to open temporary sled db");
+        let store = ExperimentStore::open(&db).expect("open");
+        let config = ExperimentConfig {
+            treatment_fraction: 1.0,
+            min_samples: 2,
+            ..Default::default()
+        };
+        let experiment = store
+            .start("refl-1", "SearchAgent", config.clone(), "Orchestrator", 1)
+            .expect("start");
+        assert!(store
+            .start("refl-2", "SearchAgent", config, "Orchestrator", 1)
+            .is_err());
+
+        let (_, arm) = store.assign("SearchAgent").unwrap().expect("running");
+        assert_eq!(arm, ExperimentArm::Treatment);
+        assert!(store.assign("CalendarAgent").unwrap().is_none());
+
+        use ExperimentArm::*;
+        for s in [
+            sample(Control, true, 900),
+            sample(Control, false, 1_100),
+            sample(Treatment, true, 400),
+        ] {
+            store.record(&experiment.id, s).expect("record");
+        }
+        let report = store.report(&experiment.id).expect("report");
+        assert_eq!(report.treatment.plans, 1);
+        assert_eq!(report.control.samples, 2);
+        assert_eq!(report.recommendation, None);
+
+        store
+            .record(&experiment.id, sample(Treatment, true, 600))
+            .expect("record");
+        let report = store.report(&experiment.id).expect("report");
+        assert_eq!(report.success_rate_delta, 0.5);
+        assert_eq!(report.latency_delta_ms, -500.0);
+        assert_eq!(report.recommendation, Some(ExperimentDecision::Accept));
+
+        // Faster but much less reliable is a regression.
+        let mut worse = store.get(&experiment.id).unwrap();
+        worse.id = "exp-other".to_string();
+        let report = ExperimentReport::compute(
+            &worse,
+            &[
+                sample(Control, true, 900),
+                sample(Control, true, 900),
+                sample(Treatment, true, 100),
+                sample(Treatment, false, 100),
+            ],
+        );
+        assert_eq!(report.recommendation, Some(ExperimentDecision::Revert));
+
+        store
+            .conclude(
+                &experiment.id,
+                ExperimentDecision::Accept,
+                "Orchestrator",
+                2,
+            )
+            .expect("conclude");
+        assert!(store.running_for("SearchAgent").is_none());
+        assert!(store
+            .record(&experiment.id, sample(Control, true, 1))
+            .is_err());
+
+        let tag = ExperimentTag {
+            experiment_id: experiment.id.clone(),
+            arm: Treatment,
+        };
+        let plan = tag.apply(vec![Task {
+            agent_type: "SearchAgent".to_string(),
+            input_data: r#"{"query":"q"}"#.to_string(),
+        }]);
+        assert_eq!(ExperimentTag::from_task(&plan[0]), Some(tag));
+    }
+}
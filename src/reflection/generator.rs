@@ -0,0 +1,241 @@
+//! Reflection proposals synthesized from execution outcomes.
+//!
+//! Executors record one [`ExecutionResult`] fact per task run. The [`ReflectionGenerator`]
+//! groups recent results by agent type, computes [`ExecutionStats`] and applies simple
+//! heuristics (e.g. "split the query when SearchAgent times out") to produce
+//! [`ReflectionFact`] proposals. Proposals are stored as `Proposed` reflections, so they
+//! only steer planning once someone accepts them.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{AgentFact, ReflectionFact};
+
+/// `fact_type` of facts holding a JSON [`ExecutionResult`].
+pub const EXECUTION_RESULT_FACT_TYPE: &str = "ExecutionResult";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExecutionStatus {
+    Success,
+    Failure,
+    Timeout,
+}
+
+/// How one task run went.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecutionResult {
+    pub agent_type: String,
+    pub status: ExecutionStatus,
+    pub latency_ms: u64,
+    /// Number of items the run produced, for agents that return collections.
+    #[serde(default)]
+    pub result_count: Option<u64>,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl ExecutionResult {
+    /// Wraps the result in a fact attributed to `agent_id`.
+    pub fn to_fact(&self, agent_id: &str, timestamp: u64) -> AgentFact {
+        AgentFact {
+            agent_id: agent_id.to_string(),
+            timestamp,
+            fact_type: EXECUTION_RESULT_FACT_TYPE.to_string(),
+            content: serde_json::to_string(self).expect("failed to serialize ExecutionResult"),
+        }
+    }
+
+    /// Decodes an `ExecutionResult` fact; other facts yield `None`.
+    pub fn from_fact(fact: &AgentFact) -> Option<Self> {
+        if fact.fact_type != EXECUTION_RESULT_FACT_TYPE {
+            return None;
+        }
+        serde_json::from_str(&fact.content).ok()
+    }
+}
+
+/// Aggregates for one agent type.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ExecutionStats {
+    pub agent_type: String,
+    pub runs: usize,
+    pub failures: usize,
+    pub timeouts: usize,
+    /// Successful runs that returned no items.
+    pub empty_results: usize,
+    pub mean_latency_ms: f64,
+    pub max_latency_ms: u64,
+}
+
+impl ExecutionStats {
+    fn rate(&self, count: usize) -> f64 {
+        if self.runs == 0 {
+            0.0
+        } else {
+            count as f64 / self.runs as f64
+        }
+    }
+}
+
+/// Thresholds for the heuristics. A heuristic fires when its rate reaches the threshold
+/// over at least `min_runs` runs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneratorConfig {
+    pub min_runs: usize,
+    pub timeout_rate: f64,
+    pub failure_rate: f64,
+    pub empty_rate: f64,
+    /// Mean latency above which runs are considered slow.
+    pub slow_latency_ms: f64,
+}
+
+impl Default for GeneratorConfig {
+    fn default() -> Self {
+        Self {
+            min_runs: 3,
+            timeout_rate: 0.3,
+            failure_rate: 0.3,
+            empty_rate: 0.5,
+            slow_latency_ms: 10_000.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ReflectionGenerator {
+    config: GeneratorConfig,
+}
+
+impl ReflectionGenerator {
+    pub fn new(config: GeneratorConfig) -> Self {
+        Self { config }
+    }
+
+    /// Per-agent-type statistics over the `ExecutionResult` facts in `facts`, ordered by
+    /// agent type.
+    pub fn analyze(&self, facts: &[AgentFact]) -> Vec<ExecutionStats> {
+        let mut stats: Vec<ExecutionStats> = Vec::new();
+        for result in facts.iter().filter_map(ExecutionResult::from_fact) {
+            let idx = match stats.iter().position(|s| s.agent_type == result.agent_type) {
+                Some(idx) => idx,
+                None => {
+                    stats.push(ExecutionStats {
+                        agent_type: result.agent_type.clone(),
+                        ..Default::default()
+                    });
+                    stats.len() - 1
+                }
+            };
+            let s = &mut stats[idx];
+            s.runs += 1;
+            match result.status {
+                ExecutionStatus::Failure => s.failures += 1,
+                ExecutionStatus::Timeout => s.timeouts += 1,
+                ExecutionStatus::Success if result.result_count == Some(0) => s.empty_results += 1,
+                ExecutionStatus::Success => {}
+            }
+            // Running mean.
+            s.mean_latency_ms += (result.latency_ms as f64 - s.mean_latency_ms) / s.runs as f64;
+            s.max_latency_ms = s.max_latency_ms.max(result.latency_ms);
+        }
+        stats.sort_by(|a, b| a.agent_type.cmp(&b.agent_type));
+        stats
+    }
+
+    /// Reflection proposals for the agents whose stats trip a heuristic. At most one
+    /// proposal per agent: timeouts, then failures, then empty results, then latency.
+    pub fn propose(&self, stats: &[ExecutionStats]) -> Vec<ReflectionFact> {
+        stats
+            .iter()
+            .filter(|s| s.runs >= self.config.min_runs)
+            .filter_map(|s| self.heuristic(s))
+            .collect()
+    }
+
+    fn heuristic(&self, s: &ExecutionStats) -> Option<ReflectionFact> {
+        let c = &self.config;
+        let (critique, directive) = if s.rate(s.timeouts) >= c.timeout_rate {
+            (
+                format!("{} of {} runs timed out", s.timeouts, s.runs),
+                if s.agent_type == "SearchAgent" {
+                    "Split the query into smaller concurrent sub-queries".to_string()
+                } else {
+                    "Split the task into smaller steps and run them concurrently".to_string()
+                },
+            )
+        } else if s.rate(s.failures) >= c.failure_rate {
+            (
+                format!("{} of {} runs failed", s.failures, s.runs),
+                "Retry failed runs with backoff and escalate repeated errors for triage"
+                    .to_string(),
+            )
+        } else if s.rate(s.empty_results) >= c.empty_rate {
+            (
+                format!("{} of {} runs returned no results", s.empty_results, s.runs),
+                "Broaden the query or relax filters when no results come back".to_string(),
+            )
+        } else if s.mean_latency_ms >= c.slow_latency_ms {
+            (
+                format!(
+                    "Mean latency {:.0}ms (max {}ms) over {} runs",
+                    s.mean_latency_ms, s.max_latency_ms, s.runs
+                ),
+                "Split slow requests into concurrent parts and cache repeated lookups".to_string(),
+            )
+        } else {
+            return None;
+        };
+        Some(ReflectionFact {
+            target_agent: s.agent_type.clone(),
+            critique,
+            new_directive: directive,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(agent: &str, status: ExecutionStatus, latency_ms: u64, count: Option<u64>) -> AgentFact {
+        ExecutionResult {
+            agent_type: agent.to_string(),
+            status,
+            latency_ms,
+            result_count: count,
+            error: None,
+        }
+        .to_fact("Orchestrator", 1)
+    }
+
+    #[test]
+    fn heuristics_pick_one_directive_per_agent() {
+        use ExecutionStatus::*;
+        let facts = vec![
+            run("SearchAgent", Timeout, 30_000, None),
+            run("SearchAgent", Timeout, 30_000, None),
+            run("SearchAgent", Success, 2_000, Some(5)),
+            run("CalendarAgent", Success, 100, Some(0)),
+            run("CalendarAgent", Success, 120, Some(0)),
+            run("CalendarAgent", Success, 90, Some(1)),
+            run("CybersecurityAgent", Failure, 50, None),
+            run("CybersecurityAgent", Success, 50, Some(1)),
+        ];
+        let generator = ReflectionGenerator::default();
+        let stats = generator.analyze(&facts);
+        assert_eq!(stats.len(), 3);
+        let search = stats
+            .iter()
+            .find(|s| s.agent_type == "SearchAgent")
+            .unwrap();
+        assert_eq!((search.runs, search.timeouts), (3, 2));
+        assert!((search.mean_latency_ms - 20_666.67).abs() < 1.0);
+
+        let proposals = generator.propose(&stats);
+        // CybersecurityAgent has too few runs.
+        assert_eq!(proposals.len(), 2);
+        assert_eq!(proposals[0].target_agent, "CalendarAgent");
+        assert!(proposals[0].new_directive.contains("Broaden"));
+        assert_eq!(proposals[1].target_agent, "SearchAgent");
+        assert!(proposals[1].new_directive.contains("Split the query"));
+    }
+}
@@ -0,0 +1,463 @@
+//! First-class reflection store.
+//!
+//! A [`Reflection`] is a critique of one agent's behaviour plus the directive meant to fix
+//! it. Unlike the legacy [`ReflectionFact`](crate::ReflectionFact) JSON blobs in the `facts`
+//! tree, reflections live in their own `reflections` tree with a lifecycle:
+//!
+//! ```text
+//! Proposed -> Accepted -> Applied
+//!     \           \          \
+//!      +-----------+----------+--> Reverted
+//! ```
+//!
+//! Only accepted or applied reflections steer planning. Each reflection also records the
+//! plan it produced, measured outcomes, and which reflection it superseded (or was
+//! superseded by).
+//!
+//! [`generator`] proposes reflections automatically from recorded execution outcomes, and
+//! [`experiment`] A/B-tests a proposed directive before it is accepted.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{ReflectionFact, Task};
+
+pub mod experiment;
+pub use experiment::{
+    ArmStats, Experiment, ExperimentArm, ExperimentConfig, ExperimentDecision, ExperimentReport,
+    ExperimentSample, ExperimentStatus, ExperimentStore, ExperimentTag,
+};
+
+pub mod generator;
+pub use generator::{
+    ExecutionResult, ExecutionStats, ExecutionStatus, GeneratorConfig, ReflectionGenerator,
+};
+
+/// KB tree holding reflections, keyed by reflection id.
+pub const REFLECTIONS_TREE: &str = "reflections";
+
+/// `fact_type` used for reflections in [`Resource`](crate::Resource)s, so grants can be
+/// scoped like fact grants.
+pub const REFLECTION_RESOURCE_TYPE: &str = "Reflection";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReflectionStatus {
+    Proposed,
+    Accepted,
+    Applied,
+    Reverted,
+}
+
+impl ReflectionStatus {
+    /// Whether the lifecycle allows moving from `self` to `next`.
+    pub fn can_transition_to(self, next: ReflectionStatus) -> bool {
+        use ReflectionStatus::*;
+        matches!(
+            (self, next),
+            (Proposed, Accepted) | (Accepted, Applied) | (Proposed | Accepted | Applied, Reverted)
+        )
+    }
+
+    /// Whether reflections in this state may steer planning.
+    pub fn is_active(self) -> bool {
+        matches!(self, ReflectionStatus::Accepted | ReflectionStatus::Applied)
+    }
+}
+
+/// A lifecycle change, kept for review.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusChange {
+    pub status: ReflectionStatus,
+    pub by: String,
+    pub at: u64,
+}
+
+/// A measurement taken after a reflection was applied (e.g. `latency_ms`, `success_rate`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReflectionOutcome {
+    pub metric: String,
+    pub value: f64,
+    pub note: Option<String>,
+    pub recorded_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reflection {
+    pub id: String,
+    pub target_agent: String,
+    pub critique: String,
+    pub new_directive: String,
+    /// Identity that proposed it.
+    pub author: String,
+    pub status: ReflectionStatus,
+    pub created_at: u64,
+    pub history: Vec<StatusChange>,
+    /// The plan produced when the reflection was applied.
+    pub modified_plan: Option<Vec<Task>>,
+    pub outcomes: Vec<ReflectionOutcome>,
+    pub supersedes: Option<String>,
+    pub superseded_by: Option<String>,
+}
+
+impl Reflection {
+    /// Whether this reflection should steer planning now.
+    pub fn is_active(&self) -> bool {
+        self.status.is_active() && self.superseded_by.is_none()
+    }
+
+    /// The legacy view used by planners that predate the store.
+    pub fn to_fact(&self) -> ReflectionFact {
+        ReflectionFact {
+            target_agent: self.target_agent.clone(),
+            critique: self.critique.clone(),
+            new_directive: self.new_directive.clone(),
+        }
+    }
+}
+
+/// KB-backed reflection store.
+#[derive(Debug, Clone)]
+pub struct ReflectionStore {
+    db: sled::Db,
+    tree: sled::Tree,
+}
+
+impl ReflectionStore {
+    pub fn open(db: &sled::Db) -> Result<Self, sled::Error> {
+        Ok(Self {
+            db: db.clone(),
+            tree: db.open_tree(REFLECTIONS_TREE)?,
+        })
+    }
+
+    /// Stores a new reflection in the `Proposed` state.
+    pub fn propose(
+        &self,
+        author: &str,
+        reflection: ReflectionFact,
+        now: u64,
+    ) -> Result<Reflection, String> {
+        let reflection = self.draft(author, reflection, now)?;
+        self.put(&reflection)?;
+        Ok(reflection)
+    }
+
+    /// A new `Proposed` reflection with a fresh id, not yet stored.
+    fn draft(
+        &self,
+        author: &str,
+        reflection: ReflectionFact,
+        now: u64,
+    ) -> Result<Reflection, String> {
+        let id = self.db.generate_id().map_err(write_err)?;
+        let reflection = Reflection {
+            // Zero-padded so ids sort in creation order.
+            id: format!("refl-{id:020}"),
+            target_agent: reflection.target_agent,
+            critique: reflection.critique,
+            new_directive: reflection.new_directive,
+            author: author.to_string(),
+            status: ReflectionStatus::Proposed,
+            created_at: now,
+            history: vec![StatusChange {
+                status: ReflectionStatus::Proposed,
+                by: author.to_string(),
+                at: now,
+            }],
+            modified_plan: None,
+            outcomes: Vec::new(),
+            supersedes: None,
+            superseded_by: None,
+        };
+        Ok(reflection)
+    }
+
+    pub fn get(&self, id: &str) -> Option<Reflection> {
+        let v = self.tree.get(id.as_bytes()).ok()??;
+        serde_json::from_slice(&v).ok()
+    }
+
+    /// All reflections, oldest first.
+    pub fn all(&self) -> Vec<Reflection> {
+        self.tree
+            .iter()
+            .filter_map(|res| res.ok())
+            .filter_map(|(_, v)| serde_json::from_slice(&v).ok())
+            .collect()
+    }
+
+    /// Reflections about `target_agent`, newest first.
+    pub fn for_agent(&self, target_agent: &str) -> Vec<Reflection> {
+        let mut reflections: Vec<Reflection> = self
+            .all()
+            .into_iter()
+            .filter(|r| r.target_agent == target_agent)
+            .collect();
+        reflections.reverse();
+        reflections
+    }
+
+    /// The newest reflection about `target_agent` that may steer planning.
+    pub fn active_for(&self, target_agent: &str) -> Option<Reflection> {
+        self.for_agent(target_agent)
+            .into_iter()
+            .find(Reflection::is_active)
+    }
+
+    /// Moves a reflection along its lifecycle.
+    pub fn transition(
+        &self,
+        id: &str,
+        status: ReflectionStatus,
+        by: &str,
+        now: u64,
+    ) -> Result<Reflection, String> {
+        self.update(id, |r| {
+            if !r.status.can_transition_to(status) {
+                return Err(format!(
+                    "Reflection '{}' can't move from {:?} to {:?}",
+                    r.id, r.status, status
+                ));
+            }
+            r.status = status;
+            r.history.push(StatusChange {
+                status,
+                by: by.to_string(),
+                at: now,
+            });
+            Ok(())
+        })
+    }
+
+    pub fn record_plan(&self, id: &str, plan: Vec<Task>) -> Result<Reflection, String> {
+        self.update(id, |r| {
+            r.modified_plan = Some(plan.clone());
+            Ok(())
+        })
+    }
+
+    pub fn record_outcome(
+        &self,
+        id: &str,
+        outcome: ReflectionOutcome,
+    ) -> Result<Reflection, String> {
+        self.update(id, |r| {
+            r.outcomes.push(outcome.clone());
+            Ok(())
+        })
+    }
+
+    /// Proposes `replacement` for the same agent and links it to `old_id`. The old
+    /// reflection stops steering planning; the replacement starts once accepted.
+    pub fn supersede(
+        &self,
+        old_id: &str,
+        author: &str,
+        replacement: ReflectionFact,
+        now: u64,
+    ) -> Result<Reflection, String> {
+        let mut new = self.draft(author, replacement, now)?;
+        new.supersedes = Some(old_id.to_string());
+        self.put(&new)?;
+        // Claim the old reflection atomically; if it was superseded meanwhile, drop ours.
+        let claimed = self.update(old_id, |r| {
+            if r.superseded_by.is_some() {
+                return Err(format!("Reflection '{old_id}' was already superseded"));
+            }
+            if r.target_agent != new.target_agent {
+                return Err(format!(
+                    "Reflection '{old_id}' targets '{}', not '{}'",
+                    r.target_agent, new.target_agent
+                ));
+            }
+            r.superseded_by = Some(new.id.clone());
+            Ok(())
+        });
+        if let Err(e) = claimed {
+            self.tree.remove(new.id.as_bytes()).map_err(write_err)?;
+            self.tree.flush().map_err(write_err)?;
+            return Err(e);
+        }
+        Ok(new)
+    }
+
+    /// Applies `change` to reflection `id` atomically. `change` may run more than once
+    /// under contention; a stored value it can't decode or that `change` rejects is kept.
+    fn update(
+        &self,
+        id: &str,
+        change: impl Fn(&mut Reflection) -> Result<(), String>,
+    ) -> Result<Reflection, String> {
+        let unknown = || format!("Unknown reflection '{id}'");
+        let mut out = Err(unknown());
+        self.tree
+            .fetch_and_update(id.as_bytes(), |old| {
+                out = Err(unknown());
+                let old = old?;
+                let Ok(mut reflection) = serde_json::from_slice::<Reflection>(old) else {
+                    return Some(old.to_vec());
+                };
+                match change(&mut reflection) {
+                    Ok(()) => {
+                        let value = serde_json::to_vec(&reflection)
+                            .expect("failed to serialize Reflection");
+                        out = Ok(reflection);
+                        Some(value)
+                    }
+                    Err(e) => {
+                        out = Err(e);
+                        Some(old.to_vec())
+                    }
+                }
+            })
+            .map_err(write_err)?;
+        self.tree.flush().map_err(write_err)?;
+        out
+    }
+
+    fn put(&self, reflection: &Reflection) -> Result<(), String> {
+        let value = serde_json::to_vec(reflection).expect("failed to serialize Reflection");
+        self.tree
+            .insert(reflection.id.as_bytes(), value)
+            .map_err(write_err)?;
+        self.tree.flush().map_err(write_err)?;
+        Ok(())
+    }
+}
+
+fn write_err(e: sled::Error) -> String {
+    format!("Reflection write failed: {e}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fact(directive: &str) -> ReflectionFact {
+        ReflectionFact {
+            target_agent: "SearchAgent".to_string(),
+            critique: "Single broad query timed out".to_string(),
+            new_directive: directive.to_string(),
+        }
+    }
+
+    #[test]
+    fn lifecycle_and_supersede_control_the_active_reflection() {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("failed to open temporary sled db");
+        let store = ReflectionStore::open(&db).expect("open");
+
+        let first = store
+            .propose("ReflectiveAgent", fact("Split the query"), 10)
+            .expect("propose");
+        assert!(store.active_for("SearchAgent").is_none());
+        assert!(store
+            .transition(&first.id, ReflectionStatus::Applied, "Orchestrator", 11)
+            .is_err());
+        store
+            .transition(&first.id, ReflectionStatus::Accepted, "Orchestrator", 11)
+            .expect("accept");
+        assert_eq!(
+            store.active_for("SearchAgent").map(|r| r.id),
+            Some(first.id.clone())
+        );
+
+        store
+            .record_outcome(
+                &first.id,
+                ReflectionOutcome {
+                    metric: "latency_ms".to_string(),
+                    value: 850.0,
+                    note: None,
+                    recorded_at: 12,
+                },
+            )
+            .expect("outcome");
+
+        let second = store
+            .supersede(
+                &first.id,
+                "ReflectiveAgent",
+                fact("Run sub-queries concurrently"),
+                13,
+            )
+            .expect("supersede");
+        assert_eq!(second.supersedes.as_deref(), Some(first.id.as_str()));
+        assert!(store
+            .supersede(&first.id, "ReflectiveAgent", fact("x"), 14)
+            .is_err());
+        assert!(store.active_for("SearchAgent").is_none());
+
+        store
+            .transition(&second.id, ReflectionStatus::Accepted, "Orchestrator", 15)
+            .expect("accept");
+        assert_eq!(
+            store.active_for("SearchAgent").map(|r| r.id),
+            Some(second.id.clone())
+        );
+        let history = store.for_agent("SearchAgent");
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].id, second.id);
+        assert_eq!(history[1].outcomes.len(), 1);
+        assert_eq!(
+            history[1].superseded_by.as_deref(),
+            Some(second.id.as_str())
+        );
+
+        store
+            .transition(&second.id, ReflectionStatus::Reverted, "Orchestrator", 16)
+            .expect("revert");
+        assert!(store
+            .transition(&second.id, ReflectionStatus::Accepted, "Orchestrator", 17)
+            .is_err());
+        assert!(store.active_for("SearchAgent").is_none());
+    }
+
+    #[test]
+    fn concurrent_updates_are_not_lost() {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("failed to open temporary sled db");
+        let store = ReflectionStore::open(&db).expect("open");
+        let id = store
+            .propose("ReflectiveAgent", fact("Split the query"), 10)
+            .expect("propose")
+            .id;
+        store
+            .transition(&id, ReflectionStatus::Accepted, "Orchestrator", 11)
+            .expect("accept");
+
+        std::thread::scope(|scope| {
+            for i in 0..8 {
+                let (store, id) = (&store, &id);
+                scope.spawn(move || {
+                    for j in 0..10 {
+                        store
+                            .record_outcome(
+                                id,
+                                ReflectionOutcome {
+                                    metric: "latency_ms".to_string(),
+                                    value: f64::from(i * 10 + j),
+                                    note: None,
+                                    recorded_at: 12,
+                                },
+                            )
+                            .expect("outcome");
+                        store.record_plan(id, Vec::new()).expect("plan");
+                    }
+                });
+            }
+            scope.spawn(|| {
+                store
+                    .transition(&id, ReflectionStatus::Reverted, "Orchestrator", 12)
+                    .expect("revert");
+            });
+        });
+
+        let reflection = store.get(&id).expect("stored");
+        assert_eq!(reflection.outcomes.len(), 80);
+        assert_eq!(reflection.status, ReflectionStatus::Reverted);
+    }
+}
@@ -0,0 +1,355 @@
+//! Retention policies and compaction of the `facts` tree.
+//!
+//! A [`RetentionPolicy`] covers one fact type. A fact expires once it is older than the
+//! policy's TTL, falls outside the newest `max_count` facts of its type, or falls outside the
+//! newest `keep_latest_per_agent` facts its agent wrote of that type. Types without a policy
+//! are kept forever.
+//!
+//! Compaction ([`PAGICoreModel::compact_facts`](crate::PAGICoreModel::compact_facts)) removes
+//! expired facts and their embeddings, full-text postings and graph links. What happens to the
+//! fact itself depends on the policy's [`ExpiryAction`]. Each run returns a
+//! [`CompactionReport`], and running totals are kept as [`RetentionStats`].
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::AgentFact;
+
+/// KB tree holding policies under `policy/<fact type>` and the running [`RetentionStats`].
+pub const RETENTION_TREE: &str = "retention";
+
+/// KB tree receiving archived facts, keyed like the `facts` tree.
+pub const FACTS_ARCHIVE_TREE: &str = "facts_archive";
+
+/// Fact type of the roll-ups written by [`ExpiryAction::Summarize`].
+pub const ROLLUP_FACT_TYPE: &str = "RollUp";
+
+const STATS_KEY: &str = "stats";
+
+/// Facts quoted in a roll-up's content.
+const ROLLUP_SAMPLES: usize = 3;
+
+/// What compaction does with an expired fact.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExpiryAction {
+    /// Remove it.
+    #[default]
+    Delete,
+    /// Move it to the `facts_archive` tree.
+    Archive,
+    /// Replace the expired facts of each agent with one `RollUp` fact.
+    Summarize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    pub fact_type: String,
+    pub ttl_secs: Option<u64>,
+    pub max_count: Option<usize>,
+    pub keep_latest_per_agent: Option<usize>,
+    #[serde(default)]
+    pub action: ExpiryAction,
+}
+
+impl RetentionPolicy {
+    /// A policy that expires nothing until a limit is set.
+    pub fn new(fact_type: impl Into<String>) -> Self {
+        Self {
+            fact_type: fact_type.into(),
+            ttl_secs: None,
+            max_count: None,
+            keep_latest_per_agent: None,
+            action: ExpiryAction::Delete,
+        }
+    }
+
+    pub fn with_ttl(mut self, ttl_secs: u64) -> Self {
+        self.ttl_secs = Some(ttl_secs);
+        self
+    }
+
+    pub fn with_max_count(mut self, max_count: usize) -> Self {
+        self.max_count = Some(max_count);
+        self
+    }
+
+    pub fn keep_latest_per_agent(mut self, count: usize) -> Self {
+        self.keep_latest_per_agent = Some(count);
+        self
+    }
+
+    pub fn with_action(mut self, action: ExpiryAction) -> Self {
+        self.action = action;
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.fact_type.trim().is_empty() {
+            return Err("Retention policy needs a fact type".to_string());
+        }
+        if self.ttl_secs.is_none()
+            && self.max_count.is_none()
+            && self.keep_latest_per_agent.is_none()
+        {
+            return Err(format!(
+                "Retention policy for '{}' sets no TTL or count limit",
+                self.fact_type
+            ));
+        }
+        if self.fact_type == ROLLUP_FACT_TYPE && self.action == ExpiryAction::Summarize {
+            return Err(format!(
+                "{ROLLUP_FACT_TYPE} facts can't be summarized again"
+            ));
+        }
+        Ok(())
+    }
+
+    /// Indices of the expired facts among `facts`, which must be of this policy's type and
+    /// sorted oldest first (the `facts` tree order).
+    pub fn expired(&self, facts: &[AgentFact], now: u64) -> Vec<usize> {
+        let mut expired = vec![false; facts.len()];
+        if let Some(ttl) = self.ttl_secs {
+            for (i, fact) in facts.iter().enumerate() {
+                expired[i] |= fact.timestamp.saturating_add(ttl) < now;
+            }
+        }
+        if let Some(max) = self.max_count {
+            let excess = facts.len().saturating_sub(max);
+            expired.iter_mut().take(excess).for_each(|e| *e = true);
+        }
+        if let Some(keep) = self.keep_latest_per_agent {
+            let mut seen: HashMap<&str, usize> = HashMap::new();
+            for (i, fact) in facts.iter().enumerate().rev() {
+                let count = seen.entry(fact.agent_id.as_str()).or_default();
+                *count += 1;
+                expired[i] |= *count > keep;
+            }
+        }
+        (0..facts.len()).filter(|&i| expired[i]).collect()
+    }
+}
+
+/// The roll-up replacing `facts`, which one agent wrote, oldest first.
+pub fn rollup(fact_type: &str, agent_id: &str, facts: &[AgentFact]) -> AgentFact {
+    let first = facts.first().map_or(0, |f| f.timestamp);
+    let last = facts.last().map_or(0, |f| f.timestamp);
+    let samples: Vec<String> = facts
+        .iter()
+        .rev()
+        .take(ROLLUP_SAMPLES)
+        .map(|f| f.content.chars().take(80).collect())
+        .collect();
+    AgentFact {
+        agent_id: agent_id.to_string(),
+        timestamp: last,
+        fact_type: ROLLUP_FACT_TYPE.to_string(),
+        content: format!(
+            "Rolled up {} {fact_type} fact(s) from t={first} to t={last}. Latest: {}",
+            facts.len(),
+            samples.join(" | ")
+        ),
+    }
+}
+
+/// Outcome of one compaction run.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompactionReport {
+    pub ran_at: u64,
+    /// Facts of types with a policy.
+    pub scanned: usize,
+    pub deleted: usize,
+    pub archived: usize,
+    pub summarized: usize,
+    pub rollups_written: usize,
+    /// Bytes of keys and values removed from the `facts` tree and its full-text, embedding
+    /// and graph indexes, less the roll-up records written.
+    pub reclaimed_bytes: u64,
+    /// Expired facts per fact type.
+    pub expired_by_type: BTreeMap<String, usize>,
+}
+
+impl CompactionReport {
+    /// Facts removed from the `facts` tree.
+    pub fn removed(&self) -> usize {
+        self.deleted + self.archived + self.summarized
+    }
+}
+
+/// Totals over every compaction run.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionStats {
+    pub runs: u64,
+    pub facts_removed: u64,
+    pub rollups_written: u64,
+    pub bytes_reclaimed: u64,
+    pub last_run: Option<u64>,
+}
+
+/// Persistent policies and stats.
+#[derive(Debug, Clone)]
+pub struct RetentionStore {
+    tree: sled::Tree,
+}
+
+impl RetentionStore {
+    pub fn open(db: &sled::Db) -> Result<Self, sled::Error> {
+        Ok(Self {
+            tree: db.open_tree(RETENTION_TREE)?,
+        })
+    }
+
+    /// Adds or replaces the policy for `policy.fact_type`.
+    pub fn set(&self, policy: &RetentionPolicy) -> Result<(), String> {
+        policy.validate()?;
+        let value = serde_json::to_vec(policy).expect("failed to serialize RetentionPolicy");
+        self.tree
+            .insert(policy_key(&policy.fact_type), value)
+            .map_err(write_err)?;
+        self.tree.flush().map_err(write_err)?;
+        Ok(())
+    }
+
+    /// Returns whether there was a policy.
+    pub fn remove(&self, fact_type: &str) -> Result<bool, String> {
+        let removed = self
+            .tree
+            .remove(policy_key(fact_type))
+            .map_err(write_err)?
+            .is_some();
+        self.tree.flush().map_err(write_err)?;
+        Ok(removed)
+    }
+
+    pub fn get(&self, fact_type: &str) -> Option<RetentionPolicy> {
+        let v = self.tree.get(policy_key(fact_type)).ok()??;
+        serde_json::from_slice(&v).ok()
+    }
+
+    pub fn all(&self) -> Vec<RetentionPolicy> {
+        self.tree
+            .scan_prefix("policy/")
+            .values()
+            .filter_map(|v| v.ok())
+            .filter_map(|v| serde_json::from_slice(&v).ok())
+            .collect()
+    }
+
+    pub fn stats(&self) -> RetentionStats {
+        self.tree
+            .get(STATS_KEY)
+            .ok()
+            .flatten()
+            .and_then(|v| serde_json::from_slice(&v).ok())
+            .unwrap_or_default()
+    }
+
+    /// Adds `report` to the running totals.
+    pub fn record_run(&self, report: &CompactionReport) -> Result<RetentionStats, String> {
+        let mut stats = self.stats();
+        stats.runs += 1;
+        stats.facts_removed += report.removed() as u64;
+        stats.rollups_written += report.rollups_written as u64;
+        stats.bytes_reclaimed += report.reclaimed_bytes;
+        stats.last_run = Some(report.ran_at);
+        let value = serde_json::to_vec(&stats).expect("failed to serialize RetentionStats");
+        self.tree.insert(STATS_KEY, value).map_err(write_err)?;
+        self.tree.flush().map_err(write_err)?;
+        Ok(stats)
+    }
+}
+
+fn policy_key(fact_type: &str) -> String {
+    format!("policy/{fact_type}")
+}
+
+fn write_err(e: sled::Error) -> String {
+    format!("Retention write failed: {e}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fact(agent_id: &str, timestamp: u64) -> AgentFact {
+        AgentFact {
+            agent_id: agent_id.to_string(),
+            timestamp,
+            fact_type: "SearchResult".to_string(),
+            content: format!("result at {timestamp}"),
+        }
+    }
+
+    #[test]
+    fn limits_combine_and_policies_persist() {
+        let facts = vec![
+            fact("a", 10),
+            fact("b", 20),
+            fact("a", 30),
+            fact("a", 40),
+            fact("b", 50),
+        ];
+        let policy = RetentionPolicy::new("SearchResult");
+        assert!(policy.validate().is_err());
+        assert_eq!(policy.clone().with_ttl(25).expired(&facts, 50), [0, 1]);
+        assert_eq!(
+            policy.clone().with_max_count(2).expired(&facts, 50),
+            [0, 1, 2]
+        );
+        assert_eq!(
+            policy.clone().keep_latest_per_agent(1).expired(&facts, 50),
+            [0, 1, 2]
+        );
+        assert_eq!(
+            policy
+                .clone()
+                .with_ttl(35)
+                .keep_latest_per_agent(2)
+                .expired(&facts, 50),
+            [0]
+        );
+
+        let rolled = rollup("SearchResult", "a", &facts[..3]);
+        assert_eq!(rolled.fact_type, ROLLUP_FACT_TYPE);
+        assert_eq!(rolled.timestamp, 30);
+        assert!(rolled
+            .content
+            .starts_with("Rolled up 3 SearchResult fact(s) from t=10 to t=30."));
+
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("failed to open temporary sled db");
+        let store = RetentionStore::open(&db).expect("open");
+        store
+            .set(&policy.with_ttl(60).with_action(ExpiryAction::Archive))
+            .expect("set");
+        assert!(store
+            .set(
+                &RetentionPolicy::new(ROLLUP_FACT_TYPE)
+                    .with_ttl(1)
+                    .with_action(ExpiryAction::Summarize)
+            )
+            .is_err());
+        assert_eq!(store.all().len(), 1);
+        assert_eq!(
+            store.get("SearchResult").map(|p| p.action),
+            Some(ExpiryAction::Archive)
+        );
+
+        let report = CompactionReport {
+            ran_at: 100,
+            deleted: 2,
+            archived: 1,
+            reclaimed_bytes: 300,
+            ..Default::default()
+        };
+        store.record_run(&report).expect("record");
+        let stats = store.record_run(&report).expect("record");
+        assert_eq!(stats.runs, 2);
+        assert_eq!(stats.facts_removed, 6);
+        assert_eq!(stats.bytes_reclaimed, 600);
+        assert!(store.remove("SearchResult").expect("remove"));
+        assert!(store.all().is_empty());
+    }
+}
@@ -0,0 +1,291 @@
+//! Hybrid retrieval of relevant facts for planning.
+//!
+//! A [`RetrievalQuery`] ranks facts by a weighted sum of three signals:
+//!
+//! - semantic similarity between the query and the fact embedding (see [`crate::embedding`]);
+//! - keyword overlap between the query terms and the fact text;
+//! - recency, decaying by half every `half_life_secs`.
+//!
+//! Candidates are the nearest neighbours of the query, the newest facts and the full-text
+//! matches for its terms and keywords. They are narrowed by the query's [`FactFilter`],
+//! required keywords and the caller's read grants; if fewer than `k` remain, the candidate
+//! pool widens until it covers the whole knowledge base. The top `k` form a [`PlanningContext`], which also carries the rule directives (those
+//! facts trigger for [`PAGICoreModel::planning_context`](crate::PAGICoreModel::planning_context);
+//! a full rule pass for the core's own planner) and the knowledge-graph relations extracted
+//! from them.
+
+use serde::{Deserialize, Serialize};
+
+use crate::embedding::{fact_text, FactFilter};
+use crate::fulltext::{tokenize, FullTextQuery};
+use crate::AgentFact;
+
+/// Facts the planner consults per prompt.
+pub const PLANNING_CONTEXT_K: usize = 32;
+
+/// Words too common to count as keyword matches.
+const STOPWORDS: [&str; 24] = [
+    "the", "and", "for", "with", "that", "this", "from", "into", "about", "what", "when", "where",
+    "which", "please", "next", "week", "are", "was", "were", "has", "have", "will", "can", "our",
+];
+
+/// Relative weight of each signal. They need not sum to 1.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RetrievalWeights {
+    pub semantic: f32,
+    pub keyword: f32,
+    pub recency: f32,
+}
+
+impl Default for RetrievalWeights {
+    fn default() -> Self {
+        Self {
+            semantic: 0.6,
+            keyword: 0.25,
+            recency: 0.15,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RetrievalQuery {
+    pub text: String,
+    pub k: usize,
+    pub filters: FactFilter,
+    /// If non-empty, facts must contain at least one of these (case-insensitive).
+    pub keywords: Vec<String>,
+    pub weights: RetrievalWeights,
+    pub half_life_secs: u64,
+}
+
+impl RetrievalQuery {
+    pub fn new(text: impl Into<String>, k: usize) -> Self {
+        Self {
+            text: text.into(),
+            k,
+            filters: FactFilter::default(),
+            keywords: Vec::new(),
+            weights: RetrievalWeights::default(),
+            half_life_secs: 7 * 86_400,
+        }
+    }
+
+    pub fn with_filters(mut self, filters: FactFilter) -> Self {
+        self.filters = filters;
+        self
+    }
+
+    pub fn with_keywords(mut self, keywords: Vec<String>) -> Self {
+        self.keywords = keywords;
+        self
+    }
+
+    pub fn with_weights(mut self, weights: RetrievalWeights) -> Self {
+        self.weights = weights;
+        self
+    }
+
+    pub fn with_half_life(mut self, half_life_secs: u64) -> Self {
+        self.half_life_secs = half_life_secs;
+        self
+    }
+
+    /// Whether `fact` passes the filters and required keywords.
+    pub fn admits(&self, fact: &AgentFact) -> bool {
+        if !self.filters.matches(fact) {
+            return false;
+        }
+        if self.keywords.is_empty() {
+            return true;
+        }
+        let text = fact_text(fact).to_lowercase();
+        self.keywords
+            .iter()
+            .any(|k| text.contains(&k.to_lowercase()))
+    }
+
+    /// The query terms and keywords as one `OR` full-text query, for finding candidates
+    /// through the index. `None` if there is nothing to look up.
+    pub fn fulltext_query(&self) -> Option<FullTextQuery> {
+        let mut parts: Vec<FullTextQuery> = query_terms(&self.text)
+            .into_iter()
+            .map(FullTextQuery::Term)
+            .collect();
+        for keyword in &self.keywords {
+            let mut tokens = tokenize(keyword);
+            match tokens.len() {
+                0 => {}
+                1 => parts.push(FullTextQuery::Term(tokens.remove(0))),
+                _ => parts.push(FullTextQuery::Phrase(tokens)),
+            }
+        }
+        (!parts.is_empty()).then_some(FullTextQuery::Or(parts))
+    }
+
+    /// Scores `fact` given its cosine similarity to the query.
+    pub fn score(&self, key: String, fact: AgentFact, semantic: f32, now: u64) -> RetrievedFact {
+        let semantic = semantic.max(0.0);
+        let keyword = keyword_score(&query_terms(&self.text), &fact_text(&fact));
+        let recency = recency_score(fact.timestamp, now, self.half_life_secs);
+        let w = self.weights;
+        RetrievedFact {
+            key,
+            fact,
+            score: w.semantic * semantic + w.keyword * keyword + w.recency * recency,
+            semantic,
+            keyword,
+            recency,
+        }
+    }
+}
+
+/// A retrieved fact with its combined score and the signals behind it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RetrievedFact {
+    /// Key of the fact in the `facts` tree.
+    pub key: String,
+    pub fact: AgentFact,
+    pub score: f32,
+    pub semantic: f32,
+    pub keyword: f32,
+    pub recency: f32,
+}
+
+/// What the planner knows about a prompt before planning.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PlanningContext {
+    pub prompt: String,
+    /// Most relevant first.
+    pub facts: Vec<RetrievedFact>,
+    /// Directives the rule set derives from `facts`.
+    pub directives: Vec<String>,
+    /// Graph relations extracted from `facts`, as `from -relation-> to`.
+    #[serde(default)]
+    pub relations: Vec<String>,
+}
+
+impl PlanningContext {
+    /// The context as plain text, for inclusion in an LLM planning prompt.
+    pub fn render(&self) -> String {
+        let mut out = String::from("Relevant facts:\n");
+        if self.facts.is_empty() {
+            out.push_str("- (none)\n");
+        }
+        for f in &self.facts {
+            out.push_str(&format!(
+                "- [{}] {} (score {:.2}, t={}): {}\n",
+                f.fact.fact_type, f.fact.agent_id, f.score, f.fact.timestamp, f.fact.content
+            ));
+        }
+        if !self.relations.is_empty() {
+            out.push_str("Relations:\n");
+            for r in &self.relations {
+                out.push_str(&format!("- {r}\n"));
+            }
+        }
+        if !self.directives.is_empty() {
+            out.push_str("Directives:\n");
+            for d in &self.directives {
+                out.push_str(&format!("- {d}\n"));
+            }
+        }
+        out
+    }
+
+    pub fn facts(&self) -> Vec<AgentFact> {
+        self.facts.iter().map(|f| f.fact.clone()).collect()
+    }
+}
+
+/// Lowercased query words worth matching on: three or more characters, no stopwords.
+pub fn query_terms(text: &str) -> Vec<String> {
+    let mut terms: Vec<String> = text
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.chars().count() >= 3 && !STOPWORDS.contains(w))
+        .map(str::to_string)
+        .collect();
+    terms.sort();
+    terms.dedup();
+    terms
+}
+
+/// Share of `terms` that occur as words in `text`.
+pub fn keyword_score(terms: &[String], text: &str) -> f32 {
+    if terms.is_empty() {
+        return 0.0;
+    }
+    let lowered = text.to_lowercase();
+    let words: Vec<&str> = lowered.split(|c: char| !c.is_alphanumeric()).collect();
+    let hits = terms.iter().filter(|t| words.contains(&t.as_str())).count();
+    hits as f32 / terms.len() as f32
+}
+
+/// 1.0 for a fact written now, halving every `half_life_secs`.
+pub fn recency_score(timestamp: u64, now: u64, half_life_secs: u64) -> f32 {
+    let age = now.saturating_sub(timestamp) as f64;
+    (-(age / half_life_secs.max(1) as f64) * std::f64::consts::LN_2).exp() as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fact(fact_type: &str, timestamp: u64, content: &str) -> AgentFact {
+        AgentFact {
+            agent_id: "SearchAgent".to_string(),
+            timestamp,
+            fact_type: fact_type.to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn signals_combine_and_filters_gate_candidates() {
+        let now = 1_000_000;
+        let query =
+            RetrievalQuery::new("Research the top anti-aging compounds", 5).with_half_life(100);
+        assert_eq!(
+            query_terms(&query.text),
+            ["aging", "anti", "compounds", "research", "top"]
+        );
+
+        let on_topic = query.score(
+            "a".to_string(),
+            fact("SearchResult", now - 100, "anti-aging compounds: rapamycin"),
+            0.5,
+            now,
+        );
+        assert_eq!(on_topic.keyword, 0.6);
+        assert!((on_topic.recency - 0.5).abs() < 1e-6);
+        let stale = query.score(
+            "b".to_string(),
+            fact(
+                "SearchResult",
+                now - 10_000,
+                "anti-aging compounds: rapamycin",
+            ),
+            0.5,
+            now,
+        );
+        assert!(on_topic.score > stale.score);
+
+        let query = query
+            .with_filters(FactFilter::fact_type("SearchResult"))
+            .with_keywords(vec!["Rapamycin".to_string()]);
+        assert!(query.admits(&fact("SearchResult", 1, "rapamycin trial")));
+        assert!(!query.admits(&fact("SearchResult", 1, "metformin trial")));
+        assert!(!query.admits(&fact("AnalysisResult", 1, "rapamycin trial")));
+
+        let context = PlanningContext {
+            prompt: query.text.clone(),
+            facts: vec![on_topic],
+            directives: vec!["Rerun: Deep Search".to_string()],
+            relations: vec!["compound:rapamycin -inhibits-> protein:mtor".to_string()],
+        };
+        let rendered = context.render();
+        assert!(rendered.contains("[SearchResult] SearchAgent"));
+        assert!(rendered.contains("- Rerun: Deep Search"));
+        assert!(rendered.contains("Relations:\n- compound:rapamycin -inhibits-> protein:mtor"));
+    }
+}
@@ -0,0 +1,616 @@
+//! Background job scheduler.
+//!
+//! A [`Scheduler`] runs [`ScheduledJob`]s against a shared [`PAGICoreModel`] on tokio. Each
+//! job has its own loop. The loop waits for the job's [`Schedule`] (a fixed interval or a
+//! five-field cron expression, in UTC) plus random jitter, and then runs the job on the
+//! blocking pool.
+//!
+//! Runs are single-flight through a lease in the KB's `scheduler` tree. A run starts only if
+//! no other scheduler on the same KB holds an unexpired lease for that job; otherwise it is
+//! skipped. The time of each job's last successful run is kept in the same tree. Built-in
+//! jobs use it to process only facts that arrived since then.
+//!
+//! [`SchedulerHandle::shutdown`] stops all loops and waits for running jobs to finish.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{event, Level};
+
+use crate::reflection::GeneratorConfig;
+use crate::{to_hex, unix_now, AgentFact, AgentIdentity, PAGICoreModel};
+
+/// KB tree holding job leases (`lock/<job>`) and last-success times (`state/<job>`).
+pub const SCHEDULER_TREE: &str = "scheduler";
+
+/// `fact_type` of the facts [`rule_evaluation_job`] records for each directive.
+pub const SCHEDULED_DIRECTIVE_FACT_TYPE: &str = "ScheduledDirective";
+
+/// Default lease length; a run that takes longer may overlap with another scheduler's.
+const DEFAULT_LEASE: Duration = Duration::from_secs(600);
+
+/// When a job runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Schedule {
+    Interval(Duration),
+    Cron(CronSchedule),
+}
+
+impl Schedule {
+    /// Parses a cron expression; see [`CronSchedule::parse`].
+    pub fn cron(expr: &str) -> Result<Self, String> {
+        CronSchedule::parse(expr).map(Schedule::Cron)
+    }
+
+    /// Time from `now` (unix seconds) until the next run.
+    pub fn next_delay(&self, now: u64) -> Duration {
+        match self {
+            Schedule::Interval(every) => *every,
+            Schedule::Cron(cron) => cron
+                .next_after(now)
+                .map_or(Duration::from_secs(86_400), |at| {
+                    Duration::from_secs(at - now)
+                }),
+        }
+    }
+}
+
+/// A five-field cron expression (`minute hour day-of-month month day-of-week`), in UTC.
+///
+/// Fields accept `*`, numbers, ranges (`1-5`), lists (`0,30`) and steps (`*/15`, `8-18/2`).
+/// Day of week runs 0-6 from Sunday (7 is also Sunday). As in classic cron, when both day
+/// fields are restricted a day matching either one fires.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl CronSchedule {
+    pub fn parse(expr: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!("Cron expression '{expr}' must have five fields"));
+        };
+        let mut weekdays = parse_field(weekday, 0, 7)?;
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59)?,
+            hours: parse_field(hour, 0, 23)?,
+            days: parse_field(day, 1, 31)?,
+            months: parse_field(month, 1, 12)?,
+            weekdays,
+            days_restricted: day != "*",
+            weekdays_restricted: weekday != "*",
+        })
+    }
+
+    /// The first whole minute strictly after `now` (unix seconds) that matches, searching
+    /// up to a year ahead.
+    pub fn next_after(&self, now: u64) -> Option<u64> {
+        let first = (now / 60 + 1) * 60;
+        (0..366 * 24 * 60)
+            .map(|i| first + i * 60)
+            .find(|&t| self.matches(t))
+    }
+
+    fn matches(&self, t: u64) -> bool {
+        let bit = |set: u64, v: u64| set & (1 << v) != 0;
+        let days_since_epoch = t / 86_400;
+        let (month, day) = month_day(days_since_epoch);
+        // 1970-01-01 was a Thursday.
+        let weekday = (days_since_epoch + 4) % 7;
+
+        let day_ok = match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => bit(self.days, day) || bit(self.weekdays, weekday),
+            _ => bit(self.days, day) && bit(self.weekdays, weekday),
+        };
+        bit(self.minutes, (t / 60) % 60)
+            && bit(self.hours, (t / 3600) % 24)
+            && bit(self.months, month)
+            && day_ok
+    }
+}
+
+/// Bitmask of the values `field` allows within `min..=max`.
+fn parse_field(field: &str, min: u64, max: u64) -> Result<u64, String> {
+    let mut set = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<u64>()
+                    .ok()
+                    .filter(|s| *s > 0)
+                    .ok_or_else(|| format!("Invalid cron step in '{part}'"))?,
+            ),
+            None => (part, 1),
+        };
+        let (lo, hi) = if range == "*" {
+            (min, max)
+        } else if let Some((lo, hi)) = range.split_once('-') {
+            (parse_value(lo, part)?, parse_value(hi, part)?)
+        } else {
+            let v = parse_value(range, part)?;
+            // `5/10` means "from 5, every 10".
+            (v, if step > 1 { max } else { v })
+        };
+        if lo < min || hi > max || lo > hi {
+            return Err(format!("Cron field '{part}' is outside {min}-{max}"));
+        }
+        for v in (lo..=hi).step_by(step as usize) {
+            set |= 1 << v;
+        }
+    }
+    Ok(set)
+}
+
+fn parse_value(s: &str, part: &str) -> Result<u64, String> {
+    s.parse()
+        .map_err(|_| format!("Invalid cron value in '{part}'"))
+}
+
+/// (month 1-12, day 1-31) of the civil date `days` after 1970-01-01.
+fn month_day(days: u64) -> (u64, u64) {
+    // Howard Hinnant's civil_from_days, restricted to dates after the epoch.
+    let z = days + 719_468;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    (month, day)
+}
+
+/// What a job run gets besides the core.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JobContext {
+    /// Unix seconds when the previous successful run started, if any.
+    pub last_success: Option<u64>,
+    /// Unix seconds when this run started.
+    pub now: u64,
+}
+
+impl JobContext {
+    /// Lower bound for "facts since the last run". Inclusive, so facts written in the same
+    /// second as the previous run may be seen twice.
+    pub fn since(&self) -> u64 {
+        self.last_success.unwrap_or(0)
+    }
+}
+
+type JobFn = dyn Fn(&PAGICoreModel, &JobContext) -> Result<String, String> + Send + Sync;
+
+/// A named job and its schedule. The job returns a short summary or an error.
+#[derive(Clone)]
+pub struct ScheduledJob {
+    name: String,
+    schedule: Schedule,
+    jitter: Duration,
+    run: Arc<JobFn>,
+}
+
+impl std::fmt::Debug for ScheduledJob {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ScheduledJob")
+            .field("name", &self.name)
+            .field("schedule", &self.schedule)
+            .field("jitter", &self.jitter)
+            .finish()
+    }
+}
+
+impl ScheduledJob {
+    pub fn new(
+        name: impl Into<String>,
+        schedule: Schedule,
+        run: impl Fn(&PAGICoreModel, &JobContext) -> Result<String, String> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            schedule,
+            jitter: Duration::ZERO,
+            run: Arc::new(run),
+        }
+    }
+
+    /// Delays each run by a random amount up to `jitter`, so schedulers sharing a KB don't
+    /// fire in lockstep.
+    pub fn with_jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn next_delay(&self, now: u64) -> Duration {
+        let mut delay = self.schedule.next_delay(now);
+        let jitter_ms = self.jitter.as_millis() as u64;
+        if jitter_ms > 0 {
+            let mut bytes = [0u8; 8];
+            if getrandom::getrandom(&mut bytes).is_ok() {
+                delay += Duration::from_millis(u64::from_le_bytes(bytes) % jitter_ms);
+            }
+        }
+        delay
+    }
+}
+
+/// Proposes reflections from the `ExecutionResult` facts recorded since the last run (see
+/// [`PAGICoreModel::generate_reflections`]).
+pub fn reflection_job(
+    identity: AgentIdentity,
+    schedule: Schedule,
+    config: GeneratorConfig,
+) -> ScheduledJob {
+    ScheduledJob::new("reflection_analysis", schedule, move |core, ctx| {
+        let proposed = core.generate_reflections(&identity, ctx.since(), config.clone())?;
+        Ok(format!("proposed {} reflection(s)", proposed.len()))
+    })
+}
+
+/// Applies the rule set to the facts recorded since the last run and records each directive
+/// the identity may read as a `ScheduledDirective` fact. Needs `ReadFacts` and `WriteFacts`.
+pub fn rule_evaluation_job(identity: AgentIdentity, schedule: Schedule) -> ScheduledJob {
+    ScheduledJob::new("rule_evaluation", schedule, move |core, ctx| {
+        let facts: Vec<AgentFact> = core
+            .retrieve_facts_by_timestamp(&identity, u128::from(ctx.since()))?
+            .into_iter()
+            .filter(|f| f.fact_type != SCHEDULED_DIRECTIVE_FACT_TYPE)
+            .collect();
+        let directives = core.authorized_directives(&identity, core.apply_rules_to_facts(facts))?;
+        for directive in &directives {
+            core.record_fact(
+                &identity,
+                AgentFact {
+                    agent_id: identity.id.clone(),
+                    timestamp: ctx.now,
+                    fact_type: SCHEDULED_DIRECTIVE_FACT_TYPE.to_string(),
+                    content: directive.clone(),
+                },
+            )?;
+        }
+        Ok(format!("{} directive(s)", directives.len()))
+    })
+}
+
+/// Applies the retention policies (see [`PAGICoreModel::compact_facts`]). Needs
+/// `WritePolicy` on the `retention` tree.
+pub fn retention_job(identity: AgentIdentity, schedule: Schedule) -> ScheduledJob {
+    ScheduledJob::new("retention_compaction", schedule, move |core, _ctx| {
+        let report = core.compact_facts(&identity)?;
+        Ok(format!(
+            "removed {} fact(s), wrote {} roll-up(s), reclaimed {} byte(s)",
+            report.removed(),
+            report.rollups_written,
+            report.reclaimed_bytes
+        ))
+    })
+}
+
+/// Latest state of one job, for monitoring.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JobReport {
+    pub job: String,
+    pub runs: u64,
+    /// Runs skipped because another scheduler held the lease.
+    pub skipped: u64,
+    pub failures: u64,
+    pub last_started: Option<u64>,
+    pub last_result: Option<Result<String, String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Lease {
+    owner: String,
+    expires_at: u64,
+}
+
+/// Builder for a set of jobs sharing a lease length.
+#[derive(Debug, Default)]
+pub struct Scheduler {
+    jobs: Vec<ScheduledJob>,
+    lease: Option<Duration>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_job(mut self, job: ScheduledJob) -> Self {
+        self.jobs.push(job);
+        self
+    }
+
+    /// How long a run holds its job's lease (default 10 minutes).
+    pub fn with_lease(mut self, lease: Duration) -> Self {
+        self.lease = Some(lease);
+        self
+    }
+
+    /// Spawns one loop per job. Must be called inside a tokio runtime.
+    pub fn start(self, core: Arc<PAGICoreModel>) -> Result<SchedulerHandle, String> {
+        let tree = core
+            .knowledge_base
+            .open_tree(SCHEDULER_TREE)
+            .map_err(|e| format!("Failed to open scheduler tree: {e}"))?;
+        let mut owner = [0u8; 8];
+        getrandom::getrandom(&mut owner)
+            .map_err(|e| format!("Failed to generate scheduler id: {e}"))?;
+        let runner = Arc::new(JobRunner {
+            tree,
+            owner: to_hex(&owner),
+            lease: self.lease.unwrap_or(DEFAULT_LEASE),
+            reports: Mutex::new(HashMap::new()),
+        });
+
+        let (shutdown, _) = watch::channel(false);
+        let tasks = self
+            .jobs
+            .into_iter()
+            .map(|job| {
+                let runner = runner.clone();
+                let core = core.clone();
+                let mut stop = shutdown.subscribe();
+                tokio::spawn(async move {
+                    loop {
+                        let delay = job.next_delay(unix_now());
+                        tokio::select! {
+                            _ = stop.changed() => break,
+                            _ = tokio::time::sleep(delay) => {}
+                        }
+                        let (runner, core, job) = (runner.clone(), core.clone(), job.clone());
+                        let _ =
+                            tokio::task::spawn_blocking(move || runner.run_once(&core, &job)).await;
+                    }
+                })
+            })
+            .collect();
+
+        Ok(SchedulerHandle {
+            shutdown,
+            tasks,
+            runner,
+        })
+    }
+}
+
+struct JobRunner {
+    tree: sled::Tree,
+    owner: String,
+    lease: Duration,
+    reports: Mutex<HashMap<String, JobReport>>,
+}
+
+impl JobRunner {
+    /// Runs `job` if its lease is free. Returns whether it ran.
+    fn run_once(&self, core: &PAGICoreModel, job: &ScheduledJob) -> bool {
+        let now = unix_now();
+        match self.acquire(&job.name, now) {
+            Ok(true) => {}
+            Ok(false) => {
+                self.report(&job.name, |r| r.skipped += 1);
+                return false;
+            }
+            Err(e) => {
+                event!(Level::ERROR, job = %job.name, error = %e, "Failed to acquire job lease");
+                return false;
+            }
+        }
+
+        let ctx = JobContext {
+            last_success: self.last_success(&job.name),
+            now,
+        };
+        let result = (job.run)(core, &ctx);
+        match &result {
+            Ok(summary) => {
+                event!(Level::INFO, job = %job.name, %summary, "Scheduled job finished");
+                if let Err(e) = self
+                    .tree
+                    .insert(format!("state/{}", job.name), &now.to_be_bytes())
+                {
+                    event!(Level::ERROR, job = %job.name, error = %e, "Failed to store job state");
+                }
+            }
+            Err(e) => event!(Level::WARN, job = %job.name, error = %e, "Scheduled job failed"),
+        }
+        if let Err(e) = self.release(&job.name) {
+            event!(Level::ERROR, job = %job.name, error = %e, "Failed to release job lease");
+        }
+        self.report(&job.name, |r| {
+            r.runs += 1;
+            r.failures += u64::from(result.is_err());
+            r.last_started = Some(now);
+            r.last_result = Some(result);
+        });
+        true
+    }
+
+    fn acquire(&self, job: &str, now: u64) -> Result<bool, sled::Error> {
+        let key = format!("lock/{job}");
+        let lease = serde_json::to_vec(&Lease {
+            owner: self.owner.clone(),
+            expires_at: now + self.lease.as_secs(),
+        })
+        .expect("failed to serialize lease");
+        loop {
+            let current = self.tree.get(&key)?;
+            if let Some(held) = current
+                .as_ref()
+                .and_then(|v| serde_json::from_slice::<Lease>(v).ok())
+            {
+                if held.owner != self.owner && held.expires_at > now {
+                    return Ok(false);
+                }
+            }
+            if self
+                .tree
+                .compare_and_swap(&key, current, Some(lease.clone()))?
+                .is_ok()
+            {
+                return Ok(true);
+            }
+        }
+    }
+
+    fn release(&self, job: &str) -> Result<(), sled::Error> {
+        let key = format!("lock/{job}");
+        let Some(current) = self.tree.get(&key)? else {
+            return Ok(());
+        };
+        let ours = serde_json::from_slice::<Lease>(&current).is_ok_and(|l| l.owner == self.owner);
+        if ours {
+            // A failed swap means someone else took over an expired lease; leave it.
+            let _ = self
+                .tree
+                .compare_and_swap(&key, Some(current), None as Option<&[u8]>)?;
+        }
+        Ok(())
+    }
+
+    fn last_success(&self, job: &str) -> Option<u64> {
+        let v = self.tree.get(format!("state/{job}")).ok()??;
+        Some(u64::from_be_bytes(v.as_ref().try_into().ok()?))
+    }
+
+    fn report(&self, job: &str, update: impl FnOnce(&mut JobReport)) {
+        let mut reports = self.reports.lock().expect("scheduler reports poisoned");
+        let report = reports.entry(job.to_string()).or_insert_with(|| JobReport {
+            job: job.to_string(),
+            ..Default::default()
+        });
+        update(report);
+    }
+}
+
+/// Running scheduler. Dropping it also stops the loops, but without waiting for in-flight
+/// runs; call [`SchedulerHandle::shutdown`] to wait for them and get the final reports.
+pub struct SchedulerHandle {
+    shutdown: watch::Sender<bool>,
+    tasks: Vec<JoinHandle<()>>,
+    runner: Arc<JobRunner>,
+}
+
+impl SchedulerHandle {
+    /// Per-job run counts and last results, ordered by job name.
+    pub fn reports(&self) -> Vec<JobReport> {
+        let mut reports: Vec<JobReport> = self
+            .runner
+            .reports
+            .lock()
+            .expect("scheduler reports poisoned")
+            .values()
+            .cloned()
+            .collect();
+        reports.sort_by(|a, b| a.job.cmp(&b.job));
+        reports
+    }
+
+    /// Stops scheduling new runs, waits for in-flight runs to finish and returns the final
+    /// reports.
+    pub async fn shutdown(mut self) -> Vec<JobReport> {
+        let _ = self.shutdown.send(true);
+        for task in &mut self.tasks {
+            let _ = task.await;
+        }
+        self.reports()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cron_expressions_find_the_next_matching_minute() {
+        // 2024-01-01 00:00:00 UTC, a Monday.
+        let monday = 1_704_067_200;
+        let weekday_mornings = CronSchedule::parse("*/15 9-17 * * 1-5").expect("parse");
+        assert_eq!(weekday_mornings.next_after(monday), Some(monday + 9 * 3600));
+        assert_eq!(
+            weekday_mornings.next_after(monday + 9 * 3600),
+            Some(monday + 9 * 3600 + 15 * 60)
+        );
+
+        // First of the month at 06:30: 2024-02-01 is 31 days later.
+        let monthly = CronSchedule::parse("30 6 1 * *").expect("parse");
+        assert_eq!(
+            monthly.next_after(monday + 7 * 3600),
+            Some(monday + 31 * 86_400 + 6 * 3600 + 1800)
+        );
+
+        // Sundays as 7.
+        let sundays = CronSchedule::parse("0 0 * * 7").expect("parse");
+        assert_eq!(sundays.next_after(monday), Some(monday + 6 * 86_400));
+
+        assert!(CronSchedule::parse("* * *").is_err());
+        assert!(CronSchedule::parse("60 * * * *").is_err());
+        assert!(CronSchedule::parse("*/0 * * * *").is_err());
+    }
+
+    #[tokio::test]
+    async fn jobs_run_on_interval_skip_held_leases_and_stop_on_shutdown() {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("failed to open temporary sled db");
+        let core = Arc::new(PAGICoreModel::from_db(db.clone()).require_credentials(false));
+
+        // Another scheduler holds the lease for "blocked".
+        let tree = db.open_tree(SCHEDULER_TREE).unwrap();
+        let foreign = Lease {
+            owner: "other".to_string(),
+            expires_at: unix_now() + 3600,
+        };
+        tree.insert("lock/blocked", serde_json::to_vec(&foreign).unwrap())
+            .unwrap();
+
+        let every = Schedule::Interval(Duration::from_millis(20));
+        let handle = Scheduler::new()
+            .with_job(ScheduledJob::new("tick", every.clone(), |_, ctx| {
+                Ok(format!("since {}", ctx.since()))
+            }))
+            .with_job(ScheduledJob::new(
+                "blocked",
+                every,
+                |_, _| Ok(String::new()),
+            ))
+            .start(core)
+            .expect("start");
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let reports = handle.shutdown().await;
+        assert_eq!(reports.len(), 2);
+        for r in &reports {
+            match r.job.as_str() {
+                "tick" => {
+                    assert!(r.runs >= 2, "{r:?}");
+                    assert_eq!(r.skipped, 0);
+                }
+                "blocked" => {
+                    assert_eq!(r.runs, 0);
+                    assert!(r.skipped >= 2);
+                }
+                other => panic!("unexpected job {other}"),
+            }
+        }
+
+        assert!(tree.get("state/tick").unwrap().is_some());
+        assert!(tree.get("lock/tick").unwrap().is_none());
+    }
+}
@@ -0,0 +1,267 @@
+//! Streaming agent output.
+//!
+//! [`BaseAgent::run`](crate::BaseAgent::run) only yields a final `String`, which leaves long
+//! research jobs silent until they finish. A [`StreamingAgent`] instead returns a stream of
+//! [`AgentEvent`]s; [`execute_streaming`] drives that stream, folds the events into an
+//! [`AgentOutcome`] and forwards them to subscribers, which
+//! [`IpcBus::forward_agent_events`](crate::IpcBus::forward_agent_events) can relay to IPC
+//! clients.
+
+use std::pin::Pin;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tracing::{event, Level};
+
+use crate::{AgentFact, AgentIdentity, BaseAgent, PAGICoreModel};
+
+/// Severity attached to [`AgentEvent::Log`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+/// A single incremental event produced by a [`StreamingAgent`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "payload")]
+pub enum AgentEvent {
+    /// Coarse progress report. `percent` is optional for agents that can't estimate it.
+    Progress {
+        percent: Option<u8>,
+        message: String,
+    },
+    /// A chunk of the final result. Chunks are concatenated in arrival order.
+    PartialResult(String),
+    /// A fact the agent wants persisted. The executor records it under the agent's identity,
+    /// replacing whatever `agent_id` the agent set.
+    FactEmitted(AgentFact),
+    /// Free-form diagnostic output.
+    Log { level: LogLevel, message: String },
+    /// An error reported by the agent, or raised by the executor while handling an event
+    /// (e.g. a rejected fact write).
+    Error(String),
+}
+
+/// Boxed event stream returned by [`StreamingAgent::run_stream`].
+pub type AgentEventStream = Pin<Box<dyn Stream<Item = AgentEvent> + Send>>;
+
+/// An [`AgentEvent`] tagged with the agent that produced it, as seen by subscribers.
+#[derive(Debug, Clone)]
+pub struct AgentEventEnvelope {
+    pub agent_id: String,
+    pub event: AgentEvent,
+}
+
+/// Optional streaming contract for agents that can report partial output.
+pub trait StreamingAgent: Send + Sync {
+    /// Starts processing `task_input` and returns the stream of events it produces.
+    ///
+    /// The stream ends when the agent is done; there is no separate "finished" event.
+    fn run_stream(
+        &self,
+        identity: &AgentIdentity,
+        core: Arc<PAGICoreModel>,
+        task_input: &str,
+    ) -> AgentEventStream;
+}
+
+/// Aggregated result of a streamed agent run.
+#[derive(Debug, Clone, Default)]
+pub struct AgentOutcome {
+    pub agent_id: String,
+    /// Concatenation of all [`AgentEvent::PartialResult`] chunks.
+    pub result: String,
+    /// The last progress report seen, if any.
+    pub last_progress: Option<(Option<u8>, String)>,
+    /// Facts emitted by the agent and successfully recorded.
+    pub facts: Vec<AgentFact>,
+    pub logs: Vec<(LogLevel, String)>,
+    /// Errors reported by the agent or raised while handling events (e.g. a rejected fact
+    /// write).
+    pub errors: Vec<String>,
+}
+
+/// Drives a streaming agent to completion.
+///
+/// Every event is folded into the returned [`AgentOutcome`] and then forwarded to
+/// `subscribers` (if given). Emitted facts are written through
+/// [`PAGICoreModel::record_fact`], so the agent's scopes still apply; subscribers see the
+/// fact as recorded, or an [`AgentEvent::Error`] if the write was rejected.
+#[tracing::instrument(
+    level = "trace",
+    skip(agent, identity, core, task_input, subscribers),
+    fields(identity_id = %identity.id)
+)]
+pub async fn execute_streaming(
+    agent: &dyn StreamingAgent,
+    identity: &AgentIdentity,
+    core: Arc<PAGICoreModel>,
+    task_input: &str,
+    subscribers: Option<&broadcast::Sender<AgentEventEnvelope>>,
+) -> AgentOutcome {
+    let mut outcome = AgentOutcome {
+        agent_id: identity.id.clone(),
+        ..Default::default()
+    };
+    let mut events = agent.run_stream(identity, core.clone(), task_input);
+
+    while let Some(ev) = events.next().await {
+        let forward = match ev {
+            AgentEvent::Progress { percent, message } => {
+                outcome.last_progress = Some((percent, message.clone()));
+                AgentEvent::Progress { percent, message }
+            }
+            AgentEvent::PartialResult(chunk) => {
+                outcome.result.push_str(&chunk);
+                AgentEvent::PartialResult(chunk)
+            }
+            AgentEvent::FactEmitted(fact) => {
+                // Agents can't attribute facts to someone else.
+                let fact = AgentFact {
+                    agent_id: identity.id.clone(),
+                    ..fact
+                };
+                match core.record_fact(identity, fact.clone()) {
+                    Ok(()) => {
+                        outcome.facts.push(fact.clone());
+                        AgentEvent::FactEmitted(fact)
+                    }
+                    Err(e) => {
+                        event!(Level::WARN, identity_id = %identity.id, error = %e, "Emitted fact rejected");
+                        outcome.errors.push(e.clone());
+                        AgentEvent::Error(e)
+                    }
+                }
+            }
+            AgentEvent::Log { level, message } => {
+                outcome.logs.push((level, message.clone()));
+                AgentEvent::Log { level, message }
+            }
+            AgentEvent::Error(e) => {
+                outcome.errors.push(e.clone());
+                AgentEvent::Error(e)
+            }
+        };
+
+        if let Some(tx) = subscribers {
+            // No live subscribers is not an error; the outcome is still aggregated.
+            let _ = tx.send(AgentEventEnvelope {
+                agent_id: identity.id.clone(),
+                event: forward,
+            });
+        }
+    }
+
+    outcome
+}
+
+/// Adapts a [`StreamingAgent`] to the plain [`BaseAgent`] contract.
+///
+/// Useful for registries that only hold `BaseAgent` trait objects: the stream is driven
+/// without subscribers and the aggregated result string is returned.
+pub struct StreamingAdapter<A>(pub A);
+
+#[async_trait]
+impl<A: StreamingAgent> BaseAgent for StreamingAdapter<A> {
+    async fn run(
+        &self,
+        identity: &AgentIdentity,
+        core: Arc<PAGICoreModel>,
+        task_input: &str,
+    ) -> String {
+        execute_streaming(&self.0, identity, core, task_input, None)
+            .await
+            .result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AuthScope, ResourcePattern};
+
+    struct ChunkyAgent;
+
+    impl StreamingAgent for ChunkyAgent {
+        fn run_stream(
+            &self,
+            _identity: &AgentIdentity,
+            _core: Arc<PAGICoreModel>,
+            task_input: &str,
+        ) -> AgentEventStream {
+            // Claims another agent's name; the executor must not honour it.
+            let fact = AgentFact {
+                agent_id: "CybersecurityAgent".to_string(),
+                timestamp: 7,
+                fact_type: "ResearchResult".to_string(),
+                content: task_input.to_string(),
+            };
+            Box::pin(futures::stream::iter(vec![
+                AgentEvent::Progress {
+                    percent: Some(50),
+                    message: "halfway".to_string(),
+                },
+                AgentEvent::PartialResult("hello ".to_string()),
+                AgentEvent::FactEmitted(fact.clone()),
+                // Outside the writer's grant; rejected.
+                AgentEvent::FactEmitted(AgentFact {
+                    fact_type: "CalendarEvent".to_string(),
+                    ..fact
+                }),
+                AgentEvent::PartialResult("world".to_string()),
+            ]))
+        }
+    }
+
+    #[tokio::test]
+    async fn execute_streaming_forwards_and_aggregates_events() {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("failed to open temporary sled db");
+        let core = Arc::new(PAGICoreModel::from_db(db).require_credentials(false));
+        let identity = AgentIdentity::new(
+            "SearchAgent",
+            vec![
+                AuthScope::ReadFacts,
+                AuthScope::scoped(
+                    AuthScope::WriteFacts,
+                    ResourcePattern::fact_type("ResearchResult"),
+                ),
+            ],
+        );
+        let (tx, mut rx) = broadcast::channel(16);
+
+        let outcome =
+            execute_streaming(&ChunkyAgent, &identity, core.clone(), "q", Some(&tx)).await;
+
+        assert_eq!(outcome.result, "hello world");
+        assert_eq!(outcome.facts.len(), 1);
+        assert_eq!(outcome.errors.len(), 1);
+        assert_eq!(
+            outcome.last_progress,
+            Some((Some(50), "halfway".to_string()))
+        );
+
+        let mut forwarded = Vec::new();
+        while let Ok(envelope) = rx.try_recv() {
+            forwarded.push(envelope.event);
+        }
+        assert_eq!(forwarded.len(), 5);
+        // Subscribers see the fact as recorded and the rejection as an error.
+        assert!(matches!(&forwarded[2], AgentEvent::FactEmitted(f) if f.agent_id == "SearchAgent"));
+        assert!(matches!(&forwarded[3], AgentEvent::Error(e) if *e == outcome.errors[0]));
+
+        let stored = core
+            .retrieve_facts_by_timestamp(&identity, 0)
+            .expect("read allowed");
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].agent_id, "SearchAgent");
+    }
+}
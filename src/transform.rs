@@ -0,0 +1,387 @@
+//! Reflection-driven plan transformations.
+//!
+//! A reflection directive is free text ("Split the query into smaller concurrent
+//! sub-queries", "Add a verification step", "Use BrowserAgent instead"). [`PlanTransform::
+//! from_directive`] maps it onto a list of generic [`PlanTransform`]s, and
+//! [`apply_transforms`] rewrites any plan with them. Tasks of other agents are left alone.
+//!
+//! Transforms only touch tasks whose `input_data` is a JSON object, except
+//! [`PlanTransform::ChangeAgent`], which only rewrites `agent_type`. Every task a transform
+//! produces or changes gets a `directive_applied` field naming the directive.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::Task;
+
+/// Agent that runs the steps added by [`PlanTransform::AddVerification`] by default.
+pub const DEFAULT_VERIFIER: &str = "ReflectiveAgent";
+
+/// Sub-queries produced when a split directive doesn't say how many.
+const DEFAULT_SPLIT_PARTS: usize = 3;
+
+/// Upper bound on sub-queries per task.
+const MAX_SPLIT_PARTS: usize = 8;
+
+/// Angles used to split a query that isn't already a list.
+const SPLIT_FACETS: [&str; 5] = [
+    "overview",
+    "mechanisms and evidence",
+    "recent studies",
+    "risks and limitations",
+    "open questions",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PlanTransform {
+    /// Replaces each `agent_type` task that has a `query` with `parts` sub-query tasks.
+    SplitQuery { agent_type: String, parts: usize },
+    /// Adds a `verifier` task after the last `agent_type` task.
+    AddVerification {
+        agent_type: String,
+        verifier: String,
+    },
+    /// Hands `from` tasks to `to`.
+    ChangeAgent { from: String, to: String },
+    /// Sets `timeout_ms` on `agent_type` tasks.
+    AddTimeout { agent_type: String, timeout_ms: u64 },
+}
+
+impl PlanTransform {
+    /// The transforms a directive about `target_agent` asks for, in the order they should
+    /// be applied. Empty if the directive doesn't describe a plan change.
+    ///
+    /// An agent change comes first, so the remaining transforms target the new agent.
+    pub fn from_directive(target_agent: &str, directive: &str) -> Vec<PlanTransform> {
+        let lowered = directive.to_lowercase();
+        let has = |needles: &[&str]| needles.iter().any(|n| lowered.contains(n));
+        let mut transforms = Vec::new();
+
+        let mut agent = target_agent.to_string();
+        if has(&[
+            "instead",
+            "switch to",
+            "route to",
+            "reassign",
+            "hand off",
+            "hand over",
+        ]) {
+            if let Some(to) = agent_names(directive).find(|a| a != target_agent) {
+                transforms.push(PlanTransform::ChangeAgent {
+                    from: target_agent.to_string(),
+                    to: to.clone(),
+                });
+                agent = to;
+            }
+        }
+        if has(&["split", "concurr", "sub-quer", "subquer", "parallel"]) {
+            transforms.push(PlanTransform::SplitQuery {
+                agent_type: agent.clone(),
+                parts: split_parts(&lowered),
+            });
+        }
+        if has(&["timeout", "time out", "time limit", "deadline"]) {
+            if let Some(timeout_ms) = duration_ms(&lowered) {
+                transforms.push(PlanTransform::AddTimeout {
+                    agent_type: agent.clone(),
+                    timeout_ms,
+                });
+            }
+        }
+        if has(&[
+            "verif",
+            "validat",
+            "cross-check",
+            "double-check",
+            "fact-check",
+        ]) {
+            transforms.push(PlanTransform::AddVerification {
+                agent_type: agent,
+                verifier: DEFAULT_VERIFIER.to_string(),
+            });
+        }
+        transforms
+    }
+
+    /// Applies this transform to `plan`, recording `directive` on the tasks it touches.
+    pub fn apply(&self, plan: Vec<Task>, directive: &str) -> Vec<Task> {
+        match self {
+            PlanTransform::SplitQuery { agent_type, parts } => plan
+                .into_iter()
+                .flat_map(|task| {
+                    if &task.agent_type != agent_type {
+                        return vec![task];
+                    }
+                    split_task(&task, *parts, directive).unwrap_or_else(|| vec![task])
+                })
+                .collect(),
+            PlanTransform::AddVerification {
+                agent_type,
+                verifier,
+            } => {
+                let mut plan = plan;
+                let Some(last) = plan.iter().rposition(|t| &t.agent_type == agent_type) else {
+                    return plan;
+                };
+                let verified = plan.iter().filter(|t| &t.agent_type == agent_type).count();
+                plan.insert(
+                    last + 1,
+                    Task {
+                        agent_type: verifier.clone(),
+                        input_data: serde_json::json!({
+                            "action": "Verify results",
+                            "verifies": agent_type,
+                            "tasks": verified,
+                            "directive_applied": directive,
+                        })
+                        .to_string(),
+                    },
+                );
+                plan
+            }
+            PlanTransform::ChangeAgent { from, to } => plan
+                .into_iter()
+                .map(|mut task| {
+                    if &task.agent_type == from {
+                        task.agent_type = to.clone();
+                        update_input(&mut task, |input| {
+                            input.insert("reassigned_from".to_string(), Value::from(from.as_str()));
+                            input.insert("directive_applied".to_string(), Value::from(directive));
+                        });
+                    }
+                    task
+                })
+                .collect(),
+            PlanTransform::AddTimeout {
+                agent_type,
+                timeout_ms,
+            } => plan
+                .into_iter()
+                .map(|mut task| {
+                    if &task.agent_type == agent_type {
+                        update_input(&mut task, |input| {
+                            input.insert("timeout_ms".to_string(), Value::from(*timeout_ms));
+                            input.insert("directive_applied".to_string(), Value::from(directive));
+                        });
+                    }
+                    task
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Applies `transforms` in order.
+pub fn apply_transforms(
+    plan: Vec<Task>,
+    transforms: &[PlanTransform],
+    directive: &str,
+) -> Vec<Task> {
+    transforms
+        .iter()
+        .fold(plan, |plan, transform| transform.apply(plan, directive))
+}
+
+/// Sub-query tasks for `task`, or `None` if it has no string `query`.
+fn split_task(task: &Task, parts: usize, directive: &str) -> Option<Vec<Task>> {
+    let Ok(Value::Object(input)) = serde_json::from_str::<Value>(&task.input_data) else {
+        return None;
+    };
+    let query = input.get("query")?.as_str()?.to_string();
+    let queries = sub_queries(&query, parts);
+    let count = queries.len();
+    Some(
+        queries
+            .into_iter()
+            .enumerate()
+            .map(|(i, sub)| {
+                let mut input = input.clone();
+                input.insert("query".to_string(), Value::from(sub));
+                input.insert(
+                    "subquery".to_string(),
+                    serde_json::json!({ "index": i + 1, "of": count, "parent": query }),
+                );
+                input.insert("directive_applied".to_string(), Value::from(directive));
+                Task {
+                    agent_type: task.agent_type.clone(),
+                    input_data: Value::Object(input).to_string(),
+                }
+            })
+            .collect(),
+    )
+}
+
+/// Splits a list-like query ("a, b and c") into its items, up to `parts` with the last one
+/// taking any remaining items; otherwise asks about `parts` facets of it.
+fn sub_queries(query: &str, parts: usize) -> Vec<String> {
+    let mut items: Vec<String> = query
+        .split([',', ';'])
+        .flat_map(|s| s.split(" and "))
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect();
+    if items.len() >= 2 {
+        if items.len() > parts {
+            let rest = items.split_off(parts.max(1) - 1).join(", ");
+            items.push(rest);
+        }
+        return items;
+    }
+    (0..parts)
+        .map(|i| match SPLIT_FACETS.get(i) {
+            Some(facet) => format!("{query}: {facet}"),
+            None => format!("{query} (part {} of {parts})", i + 1),
+        })
+        .collect()
+}
+
+fn update_input(task: &mut Task, change: impl FnOnce(&mut Map<String, Value>)) {
+    if let Ok(Value::Object(mut input)) = serde_json::from_str::<Value>(&task.input_data) {
+        change(&mut input);
+        task.input_data = Value::Object(input).to_string();
+    }
+}
+
+/// Words like `BrowserAgent` in the directive.
+fn agent_names(directive: &str) -> impl Iterator<Item = String> + '_ {
+    directive
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|w| w.len() > "Agent".len() && w.ends_with("Agent"))
+        .filter(|w| w.starts_with(|c: char| c.is_ascii_uppercase()))
+        .map(str::to_string)
+}
+
+/// "into 4 sub-queries" / "into four parts"; otherwise the default.
+fn split_parts(lowered: &str) -> usize {
+    const WORDS: [&str; 7] = ["two", "three", "four", "five", "six", "seven", "eight"];
+    let words: Vec<&str> = lowered.split_whitespace().collect();
+    words
+        .windows(2)
+        .filter(|w| w[0] == "into" || w[0] == "in")
+        .find_map(|w| {
+            w[1].parse::<usize>()
+                .ok()
+                .or_else(|| WORDS.iter().position(|n| *n == w[1]).map(|i| i + 2))
+        })
+        .unwrap_or(DEFAULT_SPLIT_PARTS)
+        .clamp(2, MAX_SPLIT_PARTS)
+}
+
+/// The first duration like `30s`, `500ms`, `2 minutes` or `45 seconds`, in milliseconds.
+/// `None` if there is none or it doesn't fit in a `u64`.
+fn duration_ms(lowered: &str) -> Option<u64> {
+    let words: Vec<&str> = lowered
+        .split_whitespace()
+        .map(|w| w.trim_matches(|c: char| !c.is_ascii_alphanumeric()))
+        .collect();
+    let unit_ms = |unit: &str| match unit {
+        "ms" | "millisecond" | "milliseconds" => Some(1),
+        "s" | "sec" | "secs" | "second" | "seconds" => Some(1_000),
+        "m" | "min" | "mins" | "minute" | "minutes" => Some(60_000),
+        _ => None,
+    };
+    let (value, unit_ms) = words.iter().enumerate().find_map(|(i, word)| {
+        let digits = word
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(word.len());
+        let value: u64 = word[..digits].parse().ok()?;
+        let unit = match &word[digits..] {
+            "" => *words.get(i + 1)?,
+            unit => unit,
+        };
+        Some((value, unit_ms(unit)?))
+    })?;
+    value.checked_mul(unit_ms)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(agent_type: &str, input: Value) -> Task {
+        Task {
+            agent_type: agent_type.to_string(),
+            input_data: input.to_string(),
+        }
+    }
+
+    fn input(task: &Task) -> Value {
+        serde_json::from_str(&task.input_data).unwrap()
+    }
+
+    #[test]
+    fn directives_map_to_transforms_that_rewrite_any_plan() {
+        let directive = "Use BrowserAgent instead, split into 2 sub-queries with a 30s timeout \
+                         and verify the results";
+        let transforms = PlanTransform::from_directive("SearchAgent", directive);
+        assert_eq!(
+            transforms,
+            vec![
+                PlanTransform::ChangeAgent {
+                    from: "SearchAgent".to_string(),
+                    to: "BrowserAgent".to_string(),
+                },
+                PlanTransform::SplitQuery {
+                    agent_type: "BrowserAgent".to_string(),
+                    parts: 2,
+                },
+                PlanTransform::AddTimeout {
+                    agent_type: "BrowserAgent".to_string(),
+                    timeout_ms: 30_000,
+                },
+                PlanTransform::AddVerification {
+                    agent_type: "BrowserAgent".to_string(),
+                    verifier: DEFAULT_VERIFIER.to_string(),
+                },
+            ]
+        );
+        assert!(PlanTransform::from_directive("SearchAgent", "Retry with backoff").is_empty());
+
+        let plan = vec![
+            task(
+                "SearchAgent",
+                serde_json::json!({ "query": "rust async runtimes" }),
+            ),
+            task("CalendarAgent", serde_json::json!({ "title": "Review" })),
+        ];
+        let plan = apply_transforms(plan, &transforms, directive);
+        let agents: Vec<&str> = plan.iter().map(|t| t.agent_type.as_str()).collect();
+        assert_eq!(
+            agents,
+            [
+                "BrowserAgent",
+                "BrowserAgent",
+                "ReflectiveAgent",
+                "CalendarAgent"
+            ]
+        );
+        let first = input(&plan[0]);
+        assert_eq!(first["query"], "rust async runtimes: overview");
+        assert_eq!(first["subquery"]["of"], 2);
+        assert_eq!(first["timeout_ms"], 30_000);
+        assert_eq!(first["reassigned_from"], "SearchAgent");
+        assert_eq!(input(&plan[2])["tasks"], 2);
+        assert!(input(&plan[3]).get("directive_applied").is_none());
+
+        // List-like queries split on their items.
+        let split = PlanTransform::SplitQuery {
+            agent_type: "SearchAgent".to_string(),
+            parts: 3,
+        };
+        let plan = split.apply(
+            vec![task(
+                "SearchAgent",
+                serde_json::json!({ "query": "rapamycin, metformin and spermidine" }),
+            )],
+            "Split",
+        );
+        let queries: Vec<Value> = plan.iter().map(|t| input(t)["query"].clone()).collect();
+        assert_eq!(queries, ["rapamycin", "metformin", "spermidine"]);
+        // Items beyond `parts` go to the last sub-query rather than being dropped.
+        assert_eq!(sub_queries("a, b, c, d", 2), ["a", "b, c, d"]);
+
+        assert_eq!(duration_ms("timeout 2 minutes"), Some(120_000));
+        assert_eq!(duration_ms("timeout 999999999999999999 minutes"), None);
+    }
+}